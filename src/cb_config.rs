@@ -1,43 +1,308 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::debug_println;
+use crate::macros::MacroContext;
+use crate::platform::{codeblocks_compiler_dirs, codeblocks_portable_dirs, codeblocks_user_config_dirs};
+
+/// 未指定 <gcv><ACTIVE> 时使用的全局变量集合
+const DEFAULT_GLOBAL_SET: &str = "default";
+
+/// 未设置时由 base 推导的全局变量成员 (与 Code::Blocks 一致：include 缺省为 base/include)
+const DERIVED_GLOBAL_MEMBERS: [&str; 4] = ["include", "lib", "obj", "bin"];
+
+/// PARENT 链的最大深度，防止配置中的循环引用
+const MAX_PARENT_DEPTH: usize = 16;
 
 /// Code::Blocks default.conf 中单个编译器条目
-#[derive(Debug, Clone)]
+///
+/// Code::Blocks 只保存与父编译器不同的设置，未保存的字段在解析后从 PARENT 链继承
+#[derive(Debug, Clone, Default)]
 pub struct CbCompilerEntry {
     pub compiler_id: String,
+    /// 复制来源的编译器 ID (对应 PARENT)
+    pub parent: Option<String>,
     /// 工具链安装根路径 (对应 MASTER_PATH)
     pub master_path: Option<String>,
     /// 额外的头文件目录 (对应 INCLUDE_DIRS，分号分隔)
     pub include_dirs: Vec<String>,
     /// 额外的库目录 (对应 LIBRARY_DIRS，分号分隔)
     pub library_dirs: Vec<String>,
+    /// 资源编译器的头文件目录 (对应 RES_INCLUDE_DIRS)
+    pub res_include_dirs: Vec<String>,
+    /// 查找工具的额外目录 (对应 EXTRA_PATHS)
+    pub extra_paths: Vec<String>,
+    /// 全局编译选项 (对应 COMPILER_OPTIONS)
+    pub compiler_options: Vec<String>,
+    /// 全局链接选项 (对应 LINKER_OPTIONS)
+    pub linker_options: Vec<String>,
+    /// C 编译器可执行文件名 (对应 C_COMPILER，如 riscv32-elf-gcc.exe)
+    pub c_compiler: Option<String>,
+    /// C++ 编译器可执行文件名 (对应 CPP_COMPILER)
+    pub cpp_compiler: Option<String>,
+    /// 链接可执行文件和动态库的程序 (对应 LINKER)
+    pub linker: Option<String>,
+    /// 静态库归档程序 (对应 LIB_LINKER)
+    pub lib_linker: Option<String>,
+    /// make 程序 (对应 MAKE)
+    pub make: Option<String>,
+}
+
+impl CbCompilerEntry {
+    /// 未设置的字段从父编译器条目继承
+    fn inherit_from(&mut self, parent: &CbCompilerEntry) {
+        fn inherit_option(value: &mut Option<String>, parent: &Option<String>) {
+            if value.is_none() {
+                value.clone_from(parent);
+            }
+        }
+        fn inherit_list(value: &mut Vec<String>, parent: &[String]) {
+            if value.is_empty() {
+                value.extend_from_slice(parent);
+            }
+        }
+
+        inherit_option(&mut self.master_path, &parent.master_path);
+        inherit_option(&mut self.c_compiler, &parent.c_compiler);
+        inherit_option(&mut self.cpp_compiler, &parent.cpp_compiler);
+        inherit_option(&mut self.linker, &parent.linker);
+        inherit_option(&mut self.lib_linker, &parent.lib_linker);
+        inherit_option(&mut self.make, &parent.make);
+        inherit_list(&mut self.include_dirs, &parent.include_dirs);
+        inherit_list(&mut self.library_dirs, &parent.library_dirs);
+        inherit_list(&mut self.res_include_dirs, &parent.res_include_dirs);
+        inherit_list(&mut self.extra_paths, &parent.extra_paths);
+        inherit_list(&mut self.compiler_options, &parent.compiler_options);
+        inherit_list(&mut self.linker_options, &parent.linker_options);
+    }
+}
+
+/// Code::Blocks 全局变量 (default.conf 的 <gcv> 中定义，CBP 中以 `$(#name.member)` 引用)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CbGlobalVariable {
+    /// 成员名 (小写) -> 值；内置成员为 base/include/lib/obj/bin/cflags/lflags，也可以有自定义成员
+    pub members: HashMap<String, String>,
+}
+
+impl CbGlobalVariable {
+    /// 获取成员值，include/lib/obj/bin 未设置时由 base 推导
+    pub fn member(&self, name: &str) -> Option<String> {
+        let name = name.to_lowercase();
+        if let Some(value) = self.members.get(&name) {
+            return Some(value.clone());
+        }
+        if DERIVED_GLOBAL_MEMBERS.contains(&name.as_str()) {
+            return self.members.get("base").map(|base| format!("{}/{}", base, name));
+        }
+        None
+    }
+
+    /// 全部成员 (包括由 base 推导出的成员)
+    pub fn resolved_members(&self) -> Vec<(String, String)> {
+        let mut names: Vec<&str> = self.members.keys().map(String::as_str).collect();
+        if self.members.contains_key("base") {
+            names.extend(DERIVED_GLOBAL_MEMBERS);
+        }
+        names.sort_unstable();
+        names.dedup();
+        names
+            .into_iter()
+            .filter_map(|name| self.member(name).map(|value| (name.to_string(), value)))
+            .collect()
+    }
+}
+
+/// Code::Blocks 编译器定义中的一个选项 (编译器设置对话框中的复选框)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CbCompilerOption {
+    pub name: String,
+    pub category: String,
+    /// 勾选时添加的编译选项
+    pub option: String,
+    /// 勾选时添加的链接选项 (additionalLibs)
+    pub additional_libs: String,
+    /// 与之互斥的选项 (空格分隔)
+    pub supersedes: String,
+}
+
+/// Code::Blocks 编译器定义 (compiler_<id>.xml + options_<id>.xml)
+#[derive(Debug, Clone, Default)]
+pub struct CbCompilerDefinition {
+    pub compiler_id: String,
+    pub name: String,
+    /// compiler_<id>.xml 的路径
+    pub source: PathBuf,
+    /// <Path type="master"> 中的候选安装路径 (Search path / Fallback path)，按顺序
+    pub master_paths: Vec<String>,
+    /// 可执行文件名 (<Program name="C|CPP|LD|LIB|MAKE">)
+    pub c_compiler: Option<String>,
+    pub cpp_compiler: Option<String>,
+    pub linker: Option<String>,
+    pub lib_linker: Option<String>,
+    pub make: Option<String>,
+    /// 选项开关 (<Switch>)，如 includeDirs -> -I、defines -> -D、objectExtension -> o
+    pub switches: HashMap<String, String>,
+    /// 可勾选的编译选项 (<Option>)
+    pub options: Vec<CbCompilerOption>,
+    /// 命令模板 (<Command>)，如 CompileObject -> $compiler $options $includes -c $file -o $object
+    pub commands: HashMap<String, String>,
+}
+
+impl CbCompilerDefinition {
+    /// 默认安装路径：第一个存在的候选路径，都不存在时使用最后一个 (Fallback)
+    pub fn default_master_path(&self) -> Option<String> {
+        self.master_paths
+            .iter()
+            .find(|path| Path::new(path).is_dir())
+            .or(self.master_paths.last())
+            .cloned()
+    }
 }
 
 /// 从 Code::Blocks default.conf 读取的编译器配置集合
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct CbCompilerConfig {
     /// compiler_id -> CbCompilerEntry
     pub compilers: HashMap<String, CbCompilerEntry>,
+    /// compiler_id -> 编译器定义 (compilers/*.xml)
+    pub definitions: HashMap<String, CbCompilerDefinition>,
     /// 默认编译器 ID
     pub default_compiler: Option<String>,
+    /// 全局变量集合：set 名称 -> 变量名 (小写) -> 变量
+    pub global_variable_sets: HashMap<String, HashMap<String, CbGlobalVariable>>,
+    /// 当前激活的全局变量集合 (<gcv><ACTIVE>)
+    pub active_global_set: String,
 }
 
-/// 定位 Code::Blocks default.conf 文件
-/// 路径: %APPDATA%\CodeBlocks\default.conf
-/// 文件不存在时返回 None
-pub fn find_default_conf() -> Option<PathBuf> {
-    let appdata = std::env::var("APPDATA").ok()?;
-    let path = PathBuf::from(appdata).join("CodeBlocks").join("default.conf");
-    debug_println!("[DEBUG cb_config] Looking for default.conf at: {}", path.display());
-    if path.exists() {
-        debug_println!("[DEBUG cb_config] Found default.conf");
-        Some(path)
-    } else {
-        debug_println!("[DEBUG cb_config] default.conf not found");
-        None
+impl CbCompilerConfig {
+    /// 编译器的 PARENT 链 (由近到远，不含自身)；最后一项可能不在 default.conf 中 (未修改过的内置编译器)
+    pub fn ancestors(&self, compiler_id: &str) -> Vec<String> {
+        let mut chain: Vec<String> = Vec::new();
+        let mut current = self.compilers.get(compiler_id).and_then(|e| e.parent.clone());
+        while let Some(parent) = current {
+            if parent == compiler_id || chain.contains(&parent) || chain.len() >= MAX_PARENT_DEPTH {
+                debug_println!("[DEBUG cb_config] PARENT cycle at '{}' for compiler '{}'", parent, compiler_id);
+                break;
+            }
+            current = self.compilers.get(&parent).and_then(|e| e.parent.clone());
+            chain.push(parent);
+        }
+        chain
+    }
+
+    /// 当前激活集合中的全局变量
+    pub fn active_global_variables(&self) -> Option<&HashMap<String, CbGlobalVariable>> {
+        self.global_variable_sets.get(&self.active_global_set)
     }
+
+    /// 创建包含激活集合全局变量的宏上下文
+    pub fn global_macros(&self) -> MacroContext {
+        let mut macros = MacroContext::default();
+        if let Some(variables) = self.active_global_variables() {
+            macros.set_global_variables(variables);
+        }
+        macros
+    }
+}
+
+/// 指定 default.conf 路径的环境变量 (优先级低于 --cb-config)
+pub const CB_CONFIG_ENV: &str = "CBP2CLANGD_CB_CONFIG";
+
+/// default.conf 候选路径的来源，按查找优先级排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultConfSource {
+    /// --cb-config 命令行参数
+    CommandLine,
+    /// CBP2CLANGD_CB_CONFIG 环境变量
+    Environment,
+    /// 便携版 Code::Blocks：default.conf 与 codeblocks 可执行文件在同一目录
+    Portable,
+    /// Code::Blocks 的用户配置目录 (%APPDATA%\CodeBlocks、~/.config/codeblocks)
+    UserProfile,
+}
+
+impl std::fmt::Display for DefaultConfSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DefaultConfSource::CommandLine => write!(f, "--cb-config"),
+            DefaultConfSource::Environment => write!(f, "{} environment variable", CB_CONFIG_ENV),
+            DefaultConfSource::Portable => write!(f, "portable Code::Blocks install"),
+            DefaultConfSource::UserProfile => write!(f, "Code::Blocks user profile"),
+        }
+    }
+}
+
+/// default.conf 的候选位置及其来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefaultConfLocation {
+    pub path: PathBuf,
+    pub source: DefaultConfSource,
+}
+
+/// default.conf 的候选位置，按优先级排列：
+/// --cb-config > CBP2CLANGD_CB_CONFIG > 便携版安装目录 > 用户配置目录
+pub fn default_conf_candidates(cli_path: Option<&Path>) -> Vec<DefaultConfLocation> {
+    let env_path = std::env::var_os(CB_CONFIG_ENV).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let mut candidates = explicit_default_conf_candidates(cli_path, env_path);
+    let discovered = [
+        (DefaultConfSource::Portable, codeblocks_portable_dirs()),
+        (DefaultConfSource::UserProfile, codeblocks_user_config_dirs()),
+    ];
+    for (source, dirs) in discovered {
+        candidates.extend(dirs.into_iter().map(|dir| DefaultConfLocation {
+            path: dir.join("default.conf"),
+            source,
+        }));
+    }
+    candidates
+}
+
+/// 用户显式指定的 default.conf (命令行参数和环境变量)
+fn explicit_default_conf_candidates(cli_path: Option<&Path>, env_path: Option<PathBuf>) -> Vec<DefaultConfLocation> {
+    let cli = cli_path.map(|path| DefaultConfLocation {
+        path: path.to_path_buf(),
+        source: DefaultConfSource::CommandLine,
+    });
+    let env = env_path.map(|path| DefaultConfLocation {
+        path,
+        source: DefaultConfSource::Environment,
+    });
+    cli.into_iter().chain(env).collect()
+}
+
+/// 在候选位置中选择第一个存在的 default.conf
+/// 显式指定 (命令行 / 环境变量) 的文件不存在时输出警告，继续查找后面的候选位置
+fn select_default_conf(candidates: Vec<DefaultConfLocation>) -> Option<DefaultConfLocation> {
+    for candidate in candidates {
+        if candidate.path.is_file() {
+            debug_println!(
+                "[DEBUG cb_config] Using default.conf at {} (from {})",
+                candidate.path.display(),
+                candidate.source
+            );
+            return Some(candidate);
+        }
+        debug_println!(
+            "[DEBUG cb_config] No default.conf at {} (from {})",
+            candidate.path.display(),
+            candidate.source
+        );
+        if matches!(candidate.source, DefaultConfSource::CommandLine | DefaultConfSource::Environment) {
+            eprintln!(
+                "Warning: default.conf given by {} not found: {}",
+                candidate.source,
+                candidate.path.display()
+            );
+        }
+    }
+    debug_println!("[DEBUG cb_config] default.conf not found");
+    None
+}
+
+/// 定位 Code::Blocks default.conf 文件 (查找顺序见 `default_conf_candidates`)
+/// 文件不存在时返回 None
+pub fn find_default_conf(cli_path: Option<&Path>) -> Option<DefaultConfLocation> {
+    select_default_conf(default_conf_candidates(cli_path))
 }
 
 /// 解析 Code::Blocks default.conf XML 内容
@@ -52,9 +317,25 @@ pub fn find_default_conf() -> Option<PathBuf> {
 ///         <NAME><str><![CDATA[RISC-V 32-bit GCC V2]]></str></NAME>
 ///         <MASTER_PATH><str><![CDATA[C:\path\to\toolchain]]></str></MASTER_PATH>
 ///         <INCLUDE_DIRS><str><![CDATA[path1;path2;]]></str></INCLUDE_DIRS>
+///         <C_COMPILER><str><![CDATA[riscv32-elf-gcc.exe]]></str></C_COMPILER>
+///         <COMPILER_OPTIONS><str><![CDATA[-Os;-g;]]></str></COMPILER_OPTIONS>
 ///       </riscv32-v2>
+///       <my-rv32>
+///         <PARENT><str><![CDATA[riscv32-v2]]></str></PARENT>
+///       </my-rv32>
 ///     </sets>
 ///   </compiler>
+///   <gcv>
+///     <ACTIVE><str><![CDATA[default]]></str></ACTIVE>
+///     <sets>
+///       <default>
+///         <sdk>
+///           <BASE><str><![CDATA[C:\sdk]]></str></BASE>
+///           <INCLUDE><str><![CDATA[C:\sdk\inc]]></str></INCLUDE>
+///         </sdk>
+///       </default>
+///     </sets>
+///   </gcv>
 /// </CodeBlocksConfig>
 /// ```
 pub fn parse_default_conf(xml_content: &str) -> Result<CbCompilerConfig, Box<dyn std::error::Error>> {
@@ -84,23 +365,31 @@ pub fn parse_default_conf(xml_content: &str) -> Result<CbCompilerConfig, Box<dyn
                     continue;
                 }
 
-                let compiler_id = tag.to_string();
-                let master_path = extract_str_field(&child, "MASTER_PATH");
-                let include_dirs = extract_list_field(&child, "INCLUDE_DIRS");
-                let library_dirs = extract_list_field(&child, "LIBRARY_DIRS");
+                let entry = CbCompilerEntry {
+                    compiler_id: tag.to_string(),
+                    parent: extract_str_field(&child, "PARENT"),
+                    master_path: extract_str_field(&child, "MASTER_PATH"),
+                    include_dirs: extract_list_field(&child, "INCLUDE_DIRS"),
+                    library_dirs: extract_list_field(&child, "LIBRARY_DIRS"),
+                    res_include_dirs: extract_list_field(&child, "RES_INCLUDE_DIRS"),
+                    extra_paths: extract_list_field(&child, "EXTRA_PATHS"),
+                    compiler_options: extract_list_field(&child, "COMPILER_OPTIONS"),
+                    linker_options: extract_list_field(&child, "LINKER_OPTIONS"),
+                    c_compiler: extract_str_field(&child, "C_COMPILER"),
+                    cpp_compiler: extract_str_field(&child, "CPP_COMPILER"),
+                    linker: extract_str_field(&child, "LINKER"),
+                    lib_linker: extract_str_field(&child, "LIB_LINKER"),
+                    make: extract_str_field(&child, "MAKE"),
+                };
 
                 debug_println!(
-                    "[DEBUG cb_config] Found compiler: id={}, master_path={:?}",
-                    compiler_id,
-                    master_path
+                    "[DEBUG cb_config] Found compiler: id={}, parent={:?}, master_path={:?}",
+                    entry.compiler_id,
+                    entry.parent,
+                    entry.master_path
                 );
 
-                compilers.insert(compiler_id.clone(), CbCompilerEntry {
-                    compiler_id,
-                    master_path,
-                    include_dirs,
-                    library_dirs,
-                });
+                compilers.insert(entry.compiler_id.clone(), entry);
             }
         }
     }
@@ -111,18 +400,306 @@ pub fn parse_default_conf(xml_content: &str) -> Result<CbCompilerConfig, Box<dyn
         default_compiler
     );
 
-    Ok(CbCompilerConfig {
+    let (active_global_set, global_variable_sets) = parse_global_variables(&root);
+    let mut config = CbCompilerConfig {
         compilers,
+        definitions: HashMap::new(),
         default_compiler,
-    })
+        global_variable_sets,
+        active_global_set,
+    };
+
+    // 未保存的设置沿 PARENT 链继承 (由近到远)
+    let inherited: Vec<(String, CbCompilerEntry)> = config
+        .compilers
+        .keys()
+        .map(|id| {
+            let mut entry = config.compilers[id].clone();
+            for ancestor in config.ancestors(id) {
+                if let Some(parent) = config.compilers.get(&ancestor) {
+                    entry.inherit_from(parent);
+                }
+            }
+            (id.clone(), entry)
+        })
+        .collect();
+    config.compilers.extend(inherited);
+
+    // 编译器路径中同样可以引用全局变量 (例如 MASTER_PATH 为 $(#riscv))
+    let macros = config.global_macros();
+    for entry in config.compilers.values_mut() {
+        entry.master_path = entry.master_path.as_deref().map(|p| macros.expand(p));
+        entry.include_dirs = macros.expand_all(&entry.include_dirs);
+        entry.library_dirs = macros.expand_all(&entry.library_dirs);
+        entry.res_include_dirs = macros.expand_all(&entry.res_include_dirs);
+        entry.extra_paths = macros.expand_all(&entry.extra_paths);
+        entry.compiler_options = macros.expand_all(&entry.compiler_options);
+        entry.linker_options = macros.expand_all(&entry.linker_options);
+    }
+
+    Ok(config)
+}
+
+/// 解析 <gcv> 节点，返回 (激活的集合名称, 全部集合)
+fn parse_global_variables(
+    root: &roxmltree::Node,
+) -> (String, HashMap<String, HashMap<String, CbGlobalVariable>>) {
+    let mut sets = HashMap::new();
+    let Some(gcv_node) = root.children().find(|n| n.tag_name().name() == "gcv") else {
+        return (DEFAULT_GLOBAL_SET.to_string(), sets);
+    };
+
+    let active = extract_str_field(&gcv_node, "ACTIVE").unwrap_or_else(|| DEFAULT_GLOBAL_SET.to_string());
+
+    if let Some(sets_node) = gcv_node.children().find(|n| n.tag_name().name() == "sets") {
+        for set_node in sets_node.children().filter(|n| n.is_element()) {
+            let mut variables = HashMap::new();
+            for var_node in set_node.children().filter(|n| n.is_element()) {
+                let members: HashMap<String, String> = var_node
+                    .children()
+                    .filter(|n| n.is_element())
+                    .filter_map(|member| {
+                        let tag = member.tag_name().name();
+                        extract_str_field(&var_node, tag).map(|value| (tag.to_lowercase(), value))
+                    })
+                    .collect();
+                let name = var_node.tag_name().name().to_lowercase();
+                debug_println!(
+                    "[DEBUG cb_config] Found global variable: set={}, name={}, members={:?}",
+                    set_node.tag_name().name(),
+                    name,
+                    members
+                );
+                variables.insert(name, CbGlobalVariable { members });
+            }
+            sets.insert(set_node.tag_name().name().to_string(), variables);
+        }
+    }
+
+    if !sets.contains_key(&active) {
+        debug_println!("[DEBUG cb_config] Active global variable set '{}' not found", active);
+    }
+    (active, sets)
 }
 
 /// 便捷函数：查找并加载 Code::Blocks 编译器配置
-/// 文件不存在或读取失败时返回 None（静默降级到 hardcoded 默认值）
-pub fn load_cb_compiler_config() -> Option<CbCompilerConfig> {
-    let path = find_default_conf()?;
-    let content = std::fs::read_to_string(&path).ok()?;
-    parse_default_conf(&content).ok()
+/// 文件不存在时返回 None（静默降级到内置的工具链描述），读取或解析失败时输出警告后同样返回 None
+pub fn load_cb_compiler_config(cli_path: Option<&Path>) -> Option<CbCompilerConfig> {
+    let location = find_default_conf(cli_path)?;
+    let result = std::fs::read_to_string(&location.path)
+        .map_err(|e| e.into())
+        .and_then(|content| parse_default_conf(&content));
+    match result {
+        Ok(config) => Some(config),
+        Err(e) => {
+            eprintln!("Warning: Failed to load {}: {}", location.path.display(), e);
+            None
+        }
+    }
+}
+
+/// 编译器定义的搜索目录：用户指定的目录 (--cb-compilers) 优先，其次是 Code::Blocks 的用户目录和安装目录
+pub fn compiler_definition_dirs(user_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = user_dir.map(Path::to_path_buf).into_iter().collect();
+    dirs.extend(codeblocks_compiler_dirs());
+    dirs
+}
+
+/// 加载目录中的 Code::Blocks 编译器定义 (compiler_<id>.xml 及同目录的 options_<id>.xml)
+/// 目录按优先级排列，同一编译器 ID 以最先找到的定义为准；无法解析的文件输出警告后跳过
+pub fn load_compiler_definitions(dirs: &[PathBuf]) -> HashMap<String, CbCompilerDefinition> {
+    let mut definitions = HashMap::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        debug_println!("[DEBUG cb_config] Scanning compiler definitions in {}", dir.display());
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("compiler_") && n.ends_with(".xml"))
+            })
+            .collect();
+        files.sort();
+
+        for path in files {
+            match load_compiler_definition(&path) {
+                Ok(definition) => {
+                    if definitions.contains_key(&definition.compiler_id) {
+                        debug_println!(
+                            "[DEBUG cb_config] Compiler definition '{}' in {} shadowed by an earlier directory",
+                            definition.compiler_id,
+                            path.display()
+                        );
+                        continue;
+                    }
+                    debug_println!(
+                        "[DEBUG cb_config] Found compiler definition: id={}, name={}, file={}",
+                        definition.compiler_id,
+                        definition.name,
+                        path.display()
+                    );
+                    definitions.insert(definition.compiler_id.clone(), definition);
+                }
+                Err(e) => eprintln!("Warning: Failed to load compiler definition {}: {}", path.display(), e),
+            }
+        }
+    }
+    definitions
+}
+
+/// 读取单个 compiler_<id>.xml，并合并同目录下的 options_<id>.xml
+fn load_compiler_definition(path: &Path) -> Result<CbCompilerDefinition, Box<dyn std::error::Error>> {
+    let compiler_xml = std::fs::read_to_string(path)?;
+    // options 文件按文件名中的 ID 查找 (与 Code::Blocks 一致)
+    let file_id = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .and_then(|s| s.strip_prefix("compiler_"))
+        .unwrap_or_default();
+    let options_path = path.with_file_name(format!("options_{}.xml", file_id));
+    let options_xml = std::fs::read_to_string(&options_path).ok();
+    if options_xml.is_none() {
+        debug_println!("[DEBUG cb_config] No options file at {}", options_path.display());
+    }
+
+    let mut definition = parse_compiler_definition(&compiler_xml, options_xml.as_deref())?;
+    definition.source = path.to_path_buf();
+    Ok(definition)
+}
+
+/// 解析编译器定义
+///
+/// XML 结构示例:
+/// ```xml
+/// <CodeBlocks_compiler name="ARM GCC" id="arm-none-eabi">
+///   <Path type="master">
+///     <if platform="windows"><Fallback path="C:\GNU Arm"/></if>
+///     <else><Fallback path="/usr"/></else>
+///   </Path>
+/// </CodeBlocks_compiler>
+///
+/// <CodeBlocks_compiler_options>
+///   <Program name="C" value="arm-none-eabi-gcc"/>
+///   <Switch name="includeDirs" value="-I"/>
+///   <Category name="Warnings">
+///     <Option name="Enable all common compiler warnings" option="-Wall"/>
+///   </Category>
+///   <Command name="CompileObject" value="$compiler $options $includes -c $file -o $object"/>
+/// </CodeBlocks_compiler_options>
+/// ```
+///
+/// `<if platform="...">` / `<else>` 按宿主平台求值；`<if exec="...">` 需要运行程序，视为不成立
+pub fn parse_compiler_definition(
+    compiler_xml: &str,
+    options_xml: Option<&str>,
+) -> Result<CbCompilerDefinition, Box<dyn std::error::Error>> {
+    let doc = roxmltree::Document::parse(compiler_xml)?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "CodeBlocks_compiler" {
+        return Err(format!("unexpected root element <{}>", root.tag_name().name()).into());
+    }
+    let compiler_id = root.attribute("id").ok_or("missing compiler id")?.to_string();
+
+    let mut definition = CbCompilerDefinition {
+        name: root.attribute("name").unwrap_or(&compiler_id).to_string(),
+        compiler_id,
+        ..Default::default()
+    };
+
+    for path_node in active_children(root).into_iter().filter(|n| n.tag_name().name() == "Path") {
+        if path_node.attribute("type") != Some("master") {
+            continue;
+        }
+        for search in active_children(path_node) {
+            if matches!(search.tag_name().name(), "Search" | "Fallback")
+                && let Some(path) = search.attribute("path")
+            {
+                definition.master_paths.push(path.to_string());
+            }
+        }
+    }
+
+    if let Some(options_xml) = options_xml {
+        let doc = roxmltree::Document::parse(options_xml)?;
+        parse_compiler_options(doc.root_element(), "", &mut definition);
+    }
+    Ok(definition)
+}
+
+/// 解析 options_<id>.xml 中的 Program / Switch / Option / Command (Category 递归处理)
+fn parse_compiler_options(node: roxmltree::Node, category: &str, definition: &mut CbCompilerDefinition) {
+    for child in active_children(node) {
+        let attr = |name: &str| child.attribute(name).unwrap_or_default().to_string();
+        match child.tag_name().name() {
+            "Program" => {
+                let value = Some(attr("value")).filter(|v| !v.is_empty());
+                match child.attribute("name") {
+                    Some("C") => definition.c_compiler = value,
+                    Some("CPP") => definition.cpp_compiler = value,
+                    Some("LD") => definition.linker = value,
+                    Some("LIB") => definition.lib_linker = value,
+                    Some("MAKE") => definition.make = value,
+                    _ => {}
+                }
+            }
+            "Switch" => {
+                definition.switches.insert(attr("name"), attr("value"));
+            }
+            "Category" => parse_compiler_options(child, child.attribute("name").unwrap_or(category), definition),
+            "Option" => definition.options.push(CbCompilerOption {
+                name: attr("name"),
+                category: child.attribute("category").unwrap_or(category).to_string(),
+                option: attr("option"),
+                additional_libs: attr("additionalLibs"),
+                supersedes: attr("supersedes"),
+            }),
+            "Command" => {
+                definition.commands.entry(attr("name")).or_insert_with(|| attr("value"));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 子元素中在当前平台生效的部分：展开成立的 <if> 和前一个 <if> 不成立时的 <else>
+fn active_children<'a, 'input>(node: roxmltree::Node<'a, 'input>) -> Vec<roxmltree::Node<'a, 'input>> {
+    let mut result = Vec::new();
+    let mut previous_if = None;
+    for child in node.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "if" => {
+                let holds = child.attribute("platform").is_some_and(platform_matches);
+                if holds {
+                    result.extend(active_children(child));
+                }
+                previous_if = Some(holds);
+            }
+            "else" => {
+                if previous_if == Some(false) {
+                    result.extend(active_children(child));
+                }
+                previous_if = None;
+            }
+            _ => {
+                previous_if = None;
+                result.push(child);
+            }
+        }
+    }
+    result
+}
+
+/// Code::Blocks 的 platform 条件 (windows / linux / macosx / unix，可用逗号或 | 组合)
+fn platform_matches(platforms: &str) -> bool {
+    platforms.split([',', '|']).any(|platform| match platform.trim() {
+        "windows" => cfg!(windows),
+        "macosx" | "darwin" => cfg!(target_os = "macos"),
+        "unix" => cfg!(unix),
+        other => other == std::env::consts::OS,
+    })
 }
 
 /// 从 XML 节点提取 `<TAG><str><![CDATA[value]]></str></TAG>` 格式的字符串值
@@ -282,6 +859,61 @@ mod tests {
         assert!(config.compilers.is_empty());
     }
 
+    #[test]
+    fn test_parse_global_variables() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocksConfig version="1">
+    <compiler>
+        <sets>
+            <riscv32-v2>
+                <MASTER_PATH><str><![CDATA[$(#rv32)]]></str></MASTER_PATH>
+                <INCLUDE_DIRS><str><![CDATA[$(#sdk.include);]]></str></INCLUDE_DIRS>
+            </riscv32-v2>
+        </sets>
+    </compiler>
+    <gcv>
+        <ACTIVE><str><![CDATA[work]]></str></ACTIVE>
+        <sets>
+            <default>
+                <sdk>
+                    <BASE><str><![CDATA[/opt/sdk-default]]></str></BASE>
+                </sdk>
+            </default>
+            <work>
+                <sdk>
+                    <BASE><str><![CDATA[/opt/sdk]]></str></BASE>
+                    <LIB><str><![CDATA[/opt/sdk/lib64]]></str></LIB>
+                    <CFLAGS><str><![CDATA[-DSDK=1]]></str></CFLAGS>
+                </sdk>
+                <RV32>
+                    <BASE><str><![CDATA[/opt/rv32]]></str></BASE>
+                </RV32>
+            </work>
+        </sets>
+    </gcv>
+</CodeBlocksConfig>"#;
+
+        let config = parse_default_conf(xml).unwrap();
+        assert_eq!(config.active_global_set, "work");
+        assert_eq!(config.global_variable_sets.len(), 2);
+
+        let sdk = config.active_global_variables().unwrap().get("sdk").unwrap();
+        assert_eq!(sdk.member("base"), Some("/opt/sdk".to_string()));
+        // 未设置的 include 由 base 推导，已设置的 lib 保持原值
+        assert_eq!(sdk.member("include"), Some("/opt/sdk/include".to_string()));
+        assert_eq!(sdk.member("LIB"), Some("/opt/sdk/lib64".to_string()));
+        assert_eq!(sdk.member("cflags"), Some("-DSDK=1".to_string()));
+        assert!(sdk.member("lflags").is_none());
+
+        // 编译器路径中的全局变量使用激活集合展开
+        let entry = config.compilers.get("riscv32-v2").unwrap();
+        assert_eq!(entry.master_path, Some("/opt/rv32".to_string()));
+        assert_eq!(entry.include_dirs, vec!["/opt/sdk/include"]);
+
+        let macros = config.global_macros();
+        assert_eq!(macros.expand("-I$(#sdk.include) $(#sdk.cflags)"), "-I/opt/sdk/include -DSDK=1");
+    }
+
     #[test]
     fn test_skip_set_nnn_nodes() {
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -302,4 +934,188 @@ mod tests {
         assert_eq!(config.compilers.len(), 1);
         assert!(config.compilers.contains_key("riscv32-v2"));
     }
+
+    #[test]
+    fn test_parse_full_compiler_set_with_parent() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocksConfig version="1">
+    <compiler>
+        <sets>
+            <riscv32-v2>
+                <MASTER_PATH><str><![CDATA[C:\RV32-V2]]></str></MASTER_PATH>
+                <C_COMPILER><str><![CDATA[riscv32-elf-gcc.exe]]></str></C_COMPILER>
+                <CPP_COMPILER><str><![CDATA[riscv32-elf-g++.exe]]></str></CPP_COMPILER>
+                <LINKER><str><![CDATA[riscv32-elf-g++.exe]]></str></LINKER>
+                <LIB_LINKER><str><![CDATA[riscv32-elf-ar.exe]]></str></LIB_LINKER>
+                <MAKE><str><![CDATA[make.exe]]></str></MAKE>
+                <COMPILER_OPTIONS><str><![CDATA[-Os;-g;]]></str></COMPILER_OPTIONS>
+                <LINKER_OPTIONS><str><![CDATA[-nostartfiles;]]></str></LINKER_OPTIONS>
+                <EXTRA_PATHS><str><![CDATA[C:\tools\bin;]]></str></EXTRA_PATHS>
+                <RES_INCLUDE_DIRS><str><![CDATA[C:\res;]]></str></RES_INCLUDE_DIRS>
+            </riscv32-v2>
+            <my-rv32>
+                <PARENT><str><![CDATA[riscv32-v2]]></str></PARENT>
+                <COMPILER_OPTIONS><str><![CDATA[-O2;]]></str></COMPILER_OPTIONS>
+            </my-rv32>
+            <my-rv32-debug>
+                <PARENT><str><![CDATA[my-rv32]]></str></PARENT>
+                <MASTER_PATH><str><![CDATA[D:\RV32-Debug]]></str></MASTER_PATH>
+            </my-rv32-debug>
+            <loop-a>
+                <PARENT><str><![CDATA[loop-b]]></str></PARENT>
+            </loop-a>
+            <loop-b>
+                <PARENT><str><![CDATA[loop-a]]></str></PARENT>
+            </loop-b>
+        </sets>
+    </compiler>
+</CodeBlocksConfig>"#;
+
+        let config = parse_default_conf(xml).unwrap();
+        let base = config.compilers.get("riscv32-v2").unwrap();
+        assert_eq!(base.c_compiler.as_deref(), Some("riscv32-elf-gcc.exe"));
+        assert_eq!(base.linker.as_deref(), Some("riscv32-elf-g++.exe"));
+        assert_eq!(base.lib_linker.as_deref(), Some("riscv32-elf-ar.exe"));
+        assert_eq!(base.make.as_deref(), Some("make.exe"));
+        assert_eq!(base.compiler_options, vec!["-Os", "-g"]);
+        assert_eq!(base.linker_options, vec!["-nostartfiles"]);
+        assert_eq!(base.extra_paths, vec!["C:\\tools\\bin"]);
+        assert_eq!(base.res_include_dirs, vec!["C:\\res"]);
+
+        // 未保存的设置沿 PARENT 链继承，自身保存的设置优先
+        let debug = config.compilers.get("my-rv32-debug").unwrap();
+        assert_eq!(config.ancestors("my-rv32-debug"), vec!["my-rv32", "riscv32-v2"]);
+        assert_eq!(debug.master_path.as_deref(), Some("D:\\RV32-Debug"));
+        assert_eq!(debug.compiler_options, vec!["-O2"]);
+        assert_eq!(debug.cpp_compiler.as_deref(), Some("riscv32-elf-g++.exe"));
+        assert_eq!(debug.linker_options, vec!["-nostartfiles"]);
+
+        // 循环引用不会死循环
+        assert_eq!(config.ancestors("loop-a"), vec!["loop-b"]);
+        assert!(config.compilers.get("loop-a").unwrap().master_path.is_none());
+    }
+
+    #[test]
+    fn test_parse_compiler_definition() {
+        let compiler_xml = r#"<?xml version="1.0"?>
+<CodeBlocks_compiler name="GNU GCC Compiler for ARM" id="arm-none-eabi">
+    <Path type="master">
+        <if platform="windows">
+            <Fallback path="C:\GNU Arm"/>
+        </if>
+        <else>
+            <Search envVar="PATH" for="C" />
+            <Fallback path="/opt/arm"/>
+        </else>
+    </Path>
+    <Path type="extra"><Fallback path="/ignored"/></Path>
+</CodeBlocks_compiler>"#;
+        let options_xml = r#"<?xml version="1.0"?>
+<CodeBlocks_compiler_options>
+    <if platform="windows">
+        <Program name="C" value="arm-none-eabi-gcc.exe"/>
+    </if>
+    <else>
+        <Program name="C" value="arm-none-eabi-gcc"/>
+    </else>
+    <Program name="LIB" value="arm-none-eabi-ar"/>
+    <Switch name="objectExtension" value="obj"/>
+    <Category name="Optimization">
+        <Option name="Optimize for size" option="-Os" supersedes="-O2"/>
+        <Option name="Link with newlib-nano" additionalLibs="--specs=nano.specs" category="Linker"/>
+    </Category>
+    <if exec="arm-none-eabi-gcc --version">
+        <Option name="Never" option="-never"/>
+    </if>
+    <Command name="CompileObject" value="$compiler $options -c $file -o $object"/>
+</CodeBlocks_compiler_options>"#;
+
+        let def = parse_compiler_definition(compiler_xml, Some(options_xml)).unwrap();
+        assert_eq!(def.compiler_id, "arm-none-eabi");
+        assert_eq!(def.name, "GNU GCC Compiler for ARM");
+        assert_eq!(def.lib_linker.as_deref(), Some("arm-none-eabi-ar"));
+        assert_eq!(def.switches.get("objectExtension").map(String::as_str), Some("obj"));
+        assert_eq!(
+            def.commands.get("CompileObject").map(String::as_str),
+            Some("$compiler $options -c $file -o $object")
+        );
+
+        // if/else 按宿主平台求值，exec 条件视为不成立
+        if cfg!(windows) {
+            assert_eq!(def.master_paths, vec!["C:\\GNU Arm"]);
+            assert_eq!(def.c_compiler.as_deref(), Some("arm-none-eabi-gcc.exe"));
+        } else {
+            assert_eq!(def.master_paths, vec!["/opt/arm"]);
+            assert_eq!(def.c_compiler.as_deref(), Some("arm-none-eabi-gcc"));
+        }
+        assert_eq!(def.options.len(), 2);
+        assert_eq!(def.options[0].category, "Optimization");
+        assert_eq!(def.options[0].supersedes, "-O2");
+        assert_eq!(def.options[1].category, "Linker");
+        assert_eq!(def.options[1].additional_libs, "--specs=nano.specs");
+
+        assert!(parse_compiler_definition("<CodeBlocks_compiler name=\"x\"/>", None).is_err());
+        assert!(parse_compiler_definition("<CodeBlocksConfig/>", None).is_err());
+    }
+
+    #[test]
+    fn test_load_compiler_definitions_first_dir_wins() {
+        let root = std::env::temp_dir().join(format!("cbp2clangd_compiler_defs_{}", std::process::id()));
+        let user_dir = root.join("user");
+        let install_dir = root.join("install");
+        std::fs::create_dir_all(&user_dir).unwrap();
+        std::fs::create_dir_all(&install_dir).unwrap();
+
+        let compiler = |name: &str| format!(r#"<CodeBlocks_compiler name="{}" id="sdcc"/>"#, name);
+        std::fs::write(user_dir.join("compiler_sdcc.xml"), compiler("User SDCC")).unwrap();
+        std::fs::write(
+            user_dir.join("options_sdcc.xml"),
+            r#"<CodeBlocks_compiler_options><Program name="C" value="sdcc"/></CodeBlocks_compiler_options>"#,
+        )
+        .unwrap();
+        std::fs::write(install_dir.join("compiler_sdcc.xml"), compiler("Stock SDCC")).unwrap();
+        std::fs::write(install_dir.join("compiler_broken.xml"), "<CodeBlocks_compiler").unwrap();
+        std::fs::write(install_dir.join("options_other.xml"), "<CodeBlocks_compiler_options/>").unwrap();
+
+        let definitions = load_compiler_definitions(&[user_dir.clone(), root.join("missing"), install_dir]);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(definitions.len(), 1);
+        let sdcc = definitions.get("sdcc").unwrap();
+        assert_eq!(sdcc.name, "User SDCC");
+        assert_eq!(sdcc.c_compiler.as_deref(), Some("sdcc"));
+        assert_eq!(sdcc.source, user_dir.join("compiler_sdcc.xml"));
+    }
+
+    #[test]
+    fn test_select_default_conf_by_priority() {
+        let dir = std::env::temp_dir().join(format!("cbp2clangd_default_conf_{}", std::process::id()));
+        let portable = dir.join("portable");
+        std::fs::create_dir_all(&portable).unwrap();
+        std::fs::write(portable.join("default.conf"), "<CodeBlocksConfig/>").unwrap();
+        std::fs::write(dir.join("ci.conf"), "<CodeBlocksConfig/>").unwrap();
+
+        let explicit = explicit_default_conf_candidates(Some(&dir.join("cli.conf")), Some(dir.join("ci.conf")));
+        assert_eq!(explicit[0].source, DefaultConfSource::CommandLine);
+        assert_eq!(explicit[1].source, DefaultConfSource::Environment);
+        let portable_candidate = DefaultConfLocation {
+            path: portable.join("default.conf"),
+            source: DefaultConfSource::Portable,
+        };
+
+        // 命令行指定的文件不存在时使用环境变量指定的文件
+        let mut candidates = explicit.clone();
+        candidates.push(portable_candidate.clone());
+        let chosen = select_default_conf(candidates).unwrap();
+        assert_eq!(chosen.source, DefaultConfSource::Environment);
+        assert_eq!(chosen.path, dir.join("ci.conf"));
+
+        // 没有显式指定时使用便携版安装目录中的 default.conf
+        let chosen = select_default_conf(vec![portable_candidate.clone()]).unwrap();
+        assert_eq!(chosen, portable_candidate);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(select_default_conf(explicit).is_none());
+        assert!(explicit_default_conf_candidates(None, None).is_empty());
+    }
 }
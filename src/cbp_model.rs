@@ -0,0 +1,233 @@
+use std::fmt::Write;
+
+/// CBP 中的一个 XML 元素：保留全部属性 (按原始顺序) 和子元素 (按原始顺序)
+///
+/// `<Compiler>` / `<Linker>` / `<ResourceCompiler>` / `<ExtraCommands>` 中的 `<Add>` 可以混合出现
+/// (option、directory、library、before、after)，`ProjectInfo` 中扁平化的字段丢失了它们之间的顺序，
+/// 需要精确信息的地方使用这里的原始结构
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CbpElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub text: Option<String>, // 非空白的文本内容 (已去掉首尾空白)
+    pub children: Vec<CbpElement>,
+}
+
+impl CbpElement {
+    /// 从 roxmltree 节点递归构建 (注释和处理指令被忽略)
+    pub fn from_node(node: roxmltree::Node) -> Self {
+        let text: String = node.children().filter(|n| n.is_text()).filter_map(|n| n.text()).collect();
+        let text = text.trim();
+        CbpElement {
+            name: node.tag_name().name().to_string(),
+            attributes: node
+                .attributes()
+                .map(|attr| (attr.name().to_string(), attr.value().to_string()))
+                .collect(),
+            text: (!text.is_empty()).then(|| text.to_string()),
+            children: node.children().filter(|n| n.is_element()).map(CbpElement::from_node).collect(),
+        }
+    }
+
+    /// 获取属性值
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// 第一个指定名称的子元素
+    pub fn child(&self, name: &str) -> Option<&CbpElement> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// 所有指定名称的子元素 (按原始顺序)
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a CbpElement> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// 子元素中的 `<Add>` (按原始顺序)
+    pub fn adds(&self) -> impl Iterator<Item = &CbpElement> {
+        self.children_named("Add")
+    }
+
+    /// 写出为 XML (子元素缩进一个 tab，与 Code::Blocks 保存的格式一致)
+    pub fn write_xml(&self, out: &mut String, depth: usize) {
+        let indent = "\t".repeat(depth);
+        let _ = write!(out, "{}<{}", indent, self.name);
+        for (name, value) in &self.attributes {
+            let _ = write!(out, " {}=\"{}\"", name, escape_xml(value));
+        }
+        match (&self.text, self.children.is_empty()) {
+            (None, true) => out.push_str(" />\n"),
+            (Some(text), true) => {
+                let _ = writeln!(out, ">{}</{}>", escape_xml(text), self.name);
+            }
+            (text, false) => {
+                out.push_str(">\n");
+                if let Some(text) = text {
+                    let _ = writeln!(out, "{}\t{}", indent, escape_xml(text));
+                }
+                for child in &self.children {
+                    child.write_xml(out, depth + 1);
+                }
+                let _ = writeln!(out, "{}</{}>", indent, self.name);
+            }
+        }
+    }
+}
+
+/// 无损的 CBP 项目结构，与扁平化的 `ProjectInfo` 一起保存
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CbpProject {
+    /// 根元素 `<CodeBlocks_project_file>` (包含 `<FileVersion>` 和 `<Project>`)
+    pub root: CbpElement,
+}
+
+impl CbpProject {
+    /// 解析 CBP 内容，保留所有元素和属性
+    pub fn parse(xml_content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let doc = roxmltree::Document::parse(xml_content)?;
+        Ok(Self::from_document(&doc))
+    }
+
+    /// 从已解析的文档构建 (parser 与扁平化的 `ProjectInfo` 共用同一次解析)
+    pub fn from_document(doc: &roxmltree::Document) -> Self {
+        CbpProject {
+            root: CbpElement::from_node(doc.root_element()),
+        }
+    }
+
+    /// `<Project>` 元素
+    pub fn project(&self) -> Option<&CbpElement> {
+        self.root.child("Project")
+    }
+
+    /// 项目级的 `<Compiler>` / `<Linker>` / `<ResourceCompiler>` / `<ExtraCommands>` 等子元素
+    pub fn project_section(&self, name: &str) -> Option<&CbpElement> {
+        self.project()?.child(name)
+    }
+
+    /// 所有 Build Target (`<Build><Target>`)，按原始顺序
+    pub fn targets(&self) -> impl Iterator<Item = &CbpElement> {
+        self.project()
+            .into_iter()
+            .flat_map(|project| project.children_named("Build"))
+            .flat_map(|build| build.children_named("Target"))
+    }
+
+    /// 按名称查找 Build Target
+    pub fn target(&self, title: &str) -> Option<&CbpElement> {
+        self.targets().find(|target| target.attribute("title") == Some(title))
+    }
+
+    /// Build Target 的 `<Compiler>` / `<Linker>` / `<ResourceCompiler>` / `<ExtraCommands>` 等子元素
+    pub fn target_section(&self, title: &str, name: &str) -> Option<&CbpElement> {
+        self.target(title)?.child(name)
+    }
+
+    /// 所有 `<Unit>`，按原始顺序
+    pub fn units(&self) -> impl Iterator<Item = &CbpElement> {
+        self.project().into_iter().flat_map(|project| project.children_named("Unit"))
+    }
+
+    /// 写出为 CBP 文件内容
+    pub fn to_xml(&self) -> String {
+        let mut out = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\" ?>\n".to_string();
+        self.root.write_xml(&mut out, 0);
+        out
+    }
+}
+
+/// 转义 XML 属性值和文本中的特殊字符
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CBP: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Build>
+            <Target title="Debug">
+                <Option output="bin/app.elf" />
+                <Compiler>
+                    <Add option="-g" />
+                    <Add directory="inc" />
+                    <Add option="-DDEBUG" />
+                </Compiler>
+                <ResourceCompiler>
+                    <Add directory="res" />
+                </ResourceCompiler>
+            </Target>
+        </Build>
+        <Linker>
+            <Add library="a" />
+            <Add option="-Wl,--start-group" />
+            <Add library="b" />
+            <Add option="-Wl,--end-group" />
+        </Linker>
+        <ExtraCommands>
+            <Add before='echo "a &amp; b"' />
+            <Mode after="always" />
+        </ExtraCommands>
+        <Unit filename="main.c">
+            <Option compilerVar="CC" />
+            <Add option="-O3" />
+        </Unit>
+        <Extensions>
+            <code_completion />
+        </Extensions>
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    #[test]
+    fn test_preserves_order_and_attributes() {
+        let model = CbpProject::parse(CBP).unwrap();
+
+        let linker: Vec<_> = model
+            .project_section("Linker")
+            .unwrap()
+            .adds()
+            .map(|add| add.attributes[0].clone())
+            .collect();
+        assert_eq!(
+            linker,
+            vec![
+                ("library".to_string(), "a".to_string()),
+                ("option".to_string(), "-Wl,--start-group".to_string()),
+                ("library".to_string(), "b".to_string()),
+                ("option".to_string(), "-Wl,--end-group".to_string()),
+            ]
+        );
+
+        let compiler: Vec<_> = model.target_section("Debug", "Compiler").unwrap().adds().collect();
+        assert_eq!(compiler[1].attribute("directory"), Some("inc"));
+        assert_eq!(compiler[2].attribute("option"), Some("-DDEBUG"));
+        let resources = model.target_section("Debug", "ResourceCompiler").unwrap();
+        assert_eq!(resources.adds().next().unwrap().attribute("directory"), Some("res"));
+
+        let extra = model.project_section("ExtraCommands").unwrap();
+        assert_eq!(extra.adds().next().unwrap().attribute("before"), Some("echo \"a & b\""));
+        assert_eq!(extra.child("Mode").unwrap().attribute("after"), Some("always"));
+
+        let unit = model.units().next().unwrap();
+        assert_eq!(unit.attribute("filename"), Some("main.c"));
+        assert_eq!(unit.adds().next().unwrap().attribute("option"), Some("-O3"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let model = CbpProject::parse(CBP).unwrap();
+        let written = model.to_xml();
+        assert!(written.contains("<Add before=\"echo &quot;a &amp; b&quot;\" />"), "{}", written);
+        assert_eq!(CbpProject::parse(&written).unwrap(), model);
+    }
+}
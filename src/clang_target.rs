@@ -0,0 +1,194 @@
+use crate::debug_println;
+use crate::models::MarchInfo;
+
+/// 无法推断时使用的目标三元组 (RV32 工具链)
+pub const DEFAULT_TARGET_TRIPLE: &str = "riscv32-unknown-elf";
+
+/// 三元组中可作为架构的首段
+const KNOWN_ARCHS: &[&str] = &[
+    "arm", "armv", "thumb", "aarch64", "riscv32", "riscv64", "x86_64", "i386", "i486", "i586", "i686", "avr",
+    "msp430", "mips", "powerpc", "ppc",
+];
+
+/// 三元组中可作为操作系统/环境的末段
+const KNOWN_ENVS: &[&str] = &[
+    "elf", "eabi", "eabihf", "gnu", "gnueabi", "gnueabihf", "musl", "mingw32", "linux", "darwin", "none", "windows",
+];
+
+/// 工具名后缀，从编译器 ID / 工具前缀中去掉后再解析三元组
+const TOOL_SUFFIXES: &[&str] = &["gcc", "g++", "cc", "c++"];
+
+/// 从工具前缀 (`arm-none-eabi-`) 或 Code::Blocks 编译器 ID (`riscv32-v2`、`arm-elf-gcc`、`gcc`) 推断目标三元组
+pub fn infer_target_triple(name: &str) -> Option<String> {
+    let lower = name.trim().trim_end_matches('-').to_ascii_lowercase();
+    let mut parts: Vec<&str> = lower.split('-').filter(|p| !p.is_empty()).collect();
+    if parts.len() > 1 && TOOL_SUFFIXES.contains(parts.last()?) {
+        parts.pop();
+    }
+
+    // 宿主 gcc / clang (Code::Blocks 的 "gcc"、"clang" / MinGW)
+    let arch = *parts.first()?;
+    if parts.len() == 1 && matches!(arch, "gcc" | "clang" | "mingw" | "mingw32" | "mingw64") {
+        return Some(host_triple());
+    }
+    if !KNOWN_ARCHS.iter().any(|known| arch.starts_with(known)) {
+        return None;
+    }
+
+    // 完整前缀：arch-[vendor-]os[-env]
+    if parts.len() >= 2 && KNOWN_ENVS.contains(parts.last()?) {
+        return Some(normalize_triple(&parts.join("-")));
+    }
+
+    // 只有架构可用 (如 riscv32-v2)：按裸机工具链的惯例补全
+    let triple = if arch.starts_with("riscv") {
+        format!("{}-unknown-elf", arch)
+    } else if arch.starts_with("arm") || arch.starts_with("thumb") {
+        "arm-none-eabi".to_string()
+    } else if arch.starts_with("x86_64") || arch.starts_with('i') {
+        host_triple().replacen(std::env::consts::ARCH, arch, 1)
+    } else {
+        format!("{}-unknown-elf", arch)
+    };
+    Some(triple)
+}
+
+/// 补全只有两段的三元组 (`riscv32-elf` -> `riscv32-unknown-elf`)
+pub fn normalize_triple(triple: &str) -> String {
+    let parts: Vec<&str> = triple.trim().split('-').collect();
+    if parts.len() == 2 {
+        format!("{}-unknown-{}", parts[0], parts[1])
+    } else {
+        triple.trim().to_string()
+    }
+}
+
+/// 宿主 gcc 的目标三元组
+pub fn host_triple() -> String {
+    let arch = std::env::consts::ARCH;
+    match std::env::consts::OS {
+        "windows" => format!("{}-w64-mingw32", arch),
+        "macos" => format!("{}-apple-darwin", arch),
+        _ => format!("{}-unknown-linux-gnu", arch),
+    }
+}
+
+/// 传给 clangd 的目标相关选项
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClangTarget {
+    pub triple: String,      // -target 的值
+    pub add: Vec<String>,    // 转换后的 -march/-mabi/-mcpu
+    pub remove: Vec<String>, // 需要从 compile_commands.json 的命令中移除的 gcc 选项
+}
+
+impl ClangTarget {
+    /// 根据三元组和 gcc 的 -march/-mabi/-mcpu 选项生成 clang 选项
+    /// - RISC-V: 去掉 clang 不认识的自定义扩展 (_x*)，并按 rv32/rv64 (ilp32/lp64) 修正三元组的架构位宽
+    /// - 其他架构: 选项原样传递
+    ///
+    /// cflags 按 全局 -> target 的顺序传入，同一选项以最后出现的为准
+    pub fn new(triple: &str, march_info: Option<&MarchInfo>, cflags: &[String]) -> Self {
+        let last_value = |prefix: &str| {
+            cflags.iter().rev().find_map(|flag| flag.strip_prefix(prefix)).map(str::to_string)
+        };
+        let mabi = last_value("-mabi=");
+        let mcpu = last_value("-mcpu=");
+
+        let mut target = ClangTarget {
+            triple: normalize_triple(triple),
+            add: Vec::new(),
+            remove: Vec::new(),
+        };
+        let is_riscv = target.triple.starts_with("riscv");
+
+        if let Some(march_info) = march_info.filter(|m| !m.full_march.is_empty()) {
+            let march = match &march_info.base_march {
+                Some(base_march) if is_riscv && march_info.has_custom_extension => base_march.clone(),
+                _ => march_info.full_march.clone(),
+            };
+            if is_riscv {
+                let isa = march.trim_start_matches("-march=");
+                if isa.starts_with("rv64") {
+                    target.set_arch("riscv64");
+                } else if isa.starts_with("rv32") {
+                    target.set_arch("riscv32");
+                }
+            }
+            target.add.push(march);
+            target.remove.push(march_info.full_march.clone());
+        } else if is_riscv && let Some(mabi) = &mabi {
+            // 没有 -march 时按 ABI 推断位宽
+            if mabi.starts_with("lp64") {
+                target.set_arch("riscv64");
+            } else if mabi.starts_with("ilp32") {
+                target.set_arch("riscv32");
+            }
+        }
+
+        if let Some(mabi) = mabi {
+            target.add.push(format!("-mabi={}", mabi));
+        }
+        if let Some(mcpu) = mcpu {
+            target.add.push(format!("-mcpu={}", mcpu));
+        }
+
+        debug_println!("[DEBUG clang_target] {:?}", target);
+        target
+    }
+
+    /// 替换三元组的架构段
+    fn set_arch(&mut self, arch: &str) {
+        if let Some((_, rest)) = self.triple.split_once('-') {
+            self.triple = format!("{}-{}", arch, rest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_target_triple() {
+        assert_eq!(infer_target_triple("arm-none-eabi-").as_deref(), Some("arm-none-eabi"));
+        assert_eq!(infer_target_triple("riscv64-unknown-elf-").as_deref(), Some("riscv64-unknown-elf"));
+        assert_eq!(infer_target_triple("x86_64-w64-mingw32-").as_deref(), Some("x86_64-w64-mingw32"));
+        assert_eq!(infer_target_triple("riscv32-elf-").as_deref(), Some("riscv32-unknown-elf"));
+        assert_eq!(infer_target_triple("arm-none-eabi-gcc").as_deref(), Some("arm-none-eabi"));
+        // Code::Blocks 编译器 ID
+        assert_eq!(infer_target_triple("riscv32-v2").as_deref(), Some("riscv32-unknown-elf"));
+        assert_eq!(infer_target_triple("gcc"), Some(host_triple()));
+        assert_eq!(infer_target_triple("sdcc"), None);
+    }
+
+    #[test]
+    fn test_clang_target_riscv_translation() {
+        let march_info = MarchInfo {
+            full_march: "-march=rv64imac_zicsr_xvendor".to_string(),
+            base_march: Some("-march=rv64imac_zicsr".to_string()),
+            has_custom_extension: true,
+        };
+        let cflags = vec!["-mabi=ilp32".to_string(), "-O2".to_string(), "-mabi=lp64".to_string()];
+        let target = ClangTarget::new("riscv32-elf", Some(&march_info), &cflags);
+        assert_eq!(target.triple, "riscv64-unknown-elf");
+        assert_eq!(target.add, vec!["-march=rv64imac_zicsr", "-mabi=lp64"]);
+        assert_eq!(target.remove, vec!["-march=rv64imac_zicsr_xvendor"]);
+
+        // 没有 -march 时按 -mabi 推断位宽
+        let target = ClangTarget::new("riscv32-unknown-elf", None, &["-mabi=lp64d".to_string()]);
+        assert_eq!(target.triple, "riscv64-unknown-elf");
+    }
+
+    #[test]
+    fn test_clang_target_arm_passthrough() {
+        let march_info = MarchInfo {
+            full_march: "-march=armv7e-m_xfoo".to_string(),
+            base_march: Some("-march=armv7e-m".to_string()),
+            has_custom_extension: true,
+        };
+        let cflags = vec!["-mcpu=cortex-m4".to_string()];
+        let target = ClangTarget::new("arm-none-eabi", Some(&march_info), &cflags);
+        assert_eq!(target.triple, "arm-none-eabi");
+        assert_eq!(target.add, vec!["-march=armv7e-m_xfoo", "-mcpu=cortex-m4"]);
+    }
+}
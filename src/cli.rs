@@ -1,7 +1,9 @@
 use std::env;
 use std::path::{Path, PathBuf};
 
-use crate::parser::parse_cbp_file;
+use crate::cb_config::load_cb_compiler_config;
+use crate::models::CompileCommandsFormat;
+use crate::parser::parse_cbp_file_with_path;
 
 /// 转换命令参数（原有的 CBP 转换功能）
 pub struct ConvertArgs {
@@ -12,6 +14,11 @@ pub struct ConvertArgs {
     pub test_mode: bool,
     pub ninja_path: Option<String>,
     pub no_header_insertion: bool,
+    pub target: Option<String>,
+    pub compile_commands_format: CompileCommandsFormat, // compile_commands.json 使用 command 还是 arguments
+    pub probe_compiler: bool, // 运行编译器探测系统 include 路径
+    pub cb_compilers_dir: Option<PathBuf>, // 额外的 Code::Blocks 编译器定义目录 (compiler_<id>.xml)
+    pub cb_config_path: Option<PathBuf>, // --cb-config 指定的 default.conf
 }
 
 /// 合并 compile_commands.json 命令参数
@@ -19,6 +26,23 @@ pub struct MergeCompileCommandsArgs {
     pub json_paths: Vec<PathBuf>,
     pub output_dir: PathBuf,
     pub debug: bool,
+    pub target: Option<String>,
+    pub cb_config_path: Option<PathBuf>, // --cb-config 指定的 default.conf
+}
+
+/// 工作区转换命令参数
+pub struct WorkspaceArgs {
+    pub workspace_path: PathBuf,
+    pub output_dir: PathBuf,
+    pub debug: bool,
+    pub linker_type: String,
+    pub ninja_path: Option<String>,
+    pub no_header_insertion: bool,
+    pub target: Option<String>,
+    pub compile_commands_format: CompileCommandsFormat, // compile_commands.json 使用 command 还是 arguments
+    pub probe_compiler: bool, // 运行编译器探测系统 include 路径
+    pub cb_compilers_dir: Option<PathBuf>, // 额外的 Code::Blocks 编译器定义目录 (compiler_<id>.xml)
+    pub cb_config_path: Option<PathBuf>, // --cb-config 指定的 default.conf
 }
 
 /// 命令行命令枚举
@@ -29,6 +53,8 @@ pub enum Command {
     Convert(ConvertArgs),
     /// 合并多个 compile_commands.json
     MergeCompileCommands(MergeCompileCommandsArgs),
+    /// 转换 Code::Blocks 工作区中的所有项目
+    Workspace(WorkspaceArgs),
 }
 
 /// 解析命令行参数
@@ -57,6 +83,11 @@ pub fn parse_args() -> Result<Command, Box<dyn std::error::Error>> {
         return parse_merge_compile_commands(args, debug);
     }
 
+    // 检查是否是 workspace 子命令
+    if args.len() >= 2 && args[1] == "workspace" {
+        return parse_workspace(args, debug);
+    }
+
     // 默认是 convert 命令
     parse_convert(args, debug)
 }
@@ -99,6 +130,44 @@ fn parse_merge_compile_commands(
         }
     }
 
+    // 检查并移除 --target 参数
+    let mut target = None;
+    if let Some(pos) = args.iter().position(|arg| arg == "--target") {
+        if direct_json {
+            eprintln!("Error: --target is not allowed with --json mode");
+            print_merge_usage(&program_name);
+            std::process::exit(1);
+        }
+        if pos + 1 < args.len() {
+            target = Some(args[pos + 1].clone());
+            args.remove(pos + 1);
+            args.remove(pos);
+        } else {
+            eprintln!("Error: --target requires an argument");
+            print_merge_usage(&program_name);
+            std::process::exit(1);
+        }
+    }
+
+    // 检查并移除--cb-config参数：指定 Code::Blocks default.conf 的位置
+    let mut cb_config_path = None;
+    if let Some(pos) = args.iter().position(|arg| arg == "--cb-config") {
+        if pos + 1 < args.len() {
+            let path = PathBuf::from(&args[pos + 1]);
+            if !path.is_file() {
+                eprintln!("Error: default.conf not found: {}", path.display());
+                std::process::exit(1);
+            }
+            cb_config_path = Some(path);
+            args.remove(pos + 1);
+            args.remove(pos);
+        } else {
+            eprintln!("Error: --cb-config option requires an argument");
+            print_merge_usage(&program_name);
+            std::process::exit(1);
+        }
+    }
+
     // 剩余的都是输入文件路径
     let input_paths: Vec<PathBuf> = args.into_iter().map(PathBuf::from).collect();
 
@@ -156,6 +225,8 @@ fn parse_merge_compile_commands(
             }
         }
         let mut json_paths: Vec<PathBuf> = Vec::new();
+        // object_output 中可能引用 default.conf 中的全局变量
+        let cb_config = load_cb_compiler_config(cb_config_path.as_deref());
         for cbp_path in &input_paths {
             if !cbp_path.exists() {
                 eprintln!("Warning: CBP file not found, skipping: {}", cbp_path.display());
@@ -163,24 +234,33 @@ fn parse_merge_compile_commands(
             }
 
             let xml_content = std::fs::read_to_string(cbp_path)?;
-            let project_info = parse_cbp_file(&xml_content)?;
+            let project_info = parse_cbp_file_with_path(&xml_content, cbp_path, cb_config.as_ref())?;
 
             let project_dir = cbp_path
                 .parent()
                 .unwrap_or_else(|| Path::new("."));
 
-            let object_output = project_info.targets.first()
-                .map(|t| t.object_output.clone())
-                .unwrap_or_else(|| "./".to_string());
-            let abs_object_output = project_dir.join(&object_output);
-
-            let normalized_output_dir = crate::utils::get_clean_absolute_path(
-                project_dir,
-                Path::new(&abs_object_output),
-            );
-
-            let compile_commands_path = PathBuf::from(&normalized_output_dir).join("compile_commands.json");
-            json_paths.push(compile_commands_path);
+            // 使用 --target 指定的 target，未指定时使用第一个 target；虚拟 target 合并其全部成员的数据库
+            let selected_targets = match project_info.resolve_targets(target.as_deref()) {
+                Ok(targets) => targets,
+                Err(e) => {
+                    eprintln!("Error: {} ({})", e, cbp_path.display());
+                    std::process::exit(1);
+                }
+            };
+            for selected in selected_targets {
+                let abs_object_output = project_dir.join(&selected.object_output);
+
+                let normalized_output_dir = crate::utils::get_clean_absolute_path(
+                    project_dir,
+                    Path::new(&abs_object_output),
+                );
+
+                let compile_commands_path = PathBuf::from(&normalized_output_dir).join("compile_commands.json");
+                if !json_paths.contains(&compile_commands_path) {
+                    json_paths.push(compile_commands_path);
+                }
+            }
         }
 
         if json_paths.is_empty() {
@@ -202,6 +282,150 @@ fn parse_merge_compile_commands(
         json_paths,
         output_dir,
         debug,
+        target,
+        cb_config_path,
+    }))
+}
+
+/// 解析 workspace 子命令
+fn parse_workspace(
+    mut args: Vec<String>,
+    debug: bool,
+) -> Result<Command, Box<dyn std::error::Error>> {
+    let program_name = args[0].clone();
+
+    // 移除子命令名（索引 1）和程序名（索引 0），现在只保留参数
+    args.remove(1);
+    args.remove(0);
+
+    // 检查是否是禁用头文件插入模式
+    let no_header_insertion = args.iter().any(|arg| arg == "--no-header-insertion");
+    if let Some(pos) = args.iter().position(|arg| arg == "--no-header-insertion") {
+        args.remove(pos);
+    }
+
+    // 检查并移除--arguments 标志：compile_commands.json 使用 arguments 数组
+    let compile_commands_format = take_compile_commands_format(&mut args);
+
+    // 检查并移除--probe-compiler 标志：运行编译器探测系统 include 路径
+    let probe_compiler = args.iter().any(|arg| arg == "--probe-compiler");
+    if let Some(pos) = args.iter().position(|arg| arg == "--probe-compiler") {
+        args.remove(pos);
+    }
+
+    // 检查并移除--cb-compilers参数：额外的 Code::Blocks 编译器定义目录
+    let mut cb_compilers_dir = None;
+    if let Some(pos) = args.iter().position(|arg| arg == "--cb-compilers") {
+        if pos + 1 < args.len() {
+            cb_compilers_dir = Some(PathBuf::from(&args[pos + 1]));
+            args.remove(pos + 1);
+            args.remove(pos);
+        } else {
+            eprintln!("Error: --cb-compilers option requires an argument");
+            print_workspace_usage(&program_name);
+            std::process::exit(1);
+        }
+    }
+
+    // 检查并移除--cb-config参数：指定 Code::Blocks default.conf 的位置
+    let mut cb_config_path = None;
+    if let Some(pos) = args.iter().position(|arg| arg == "--cb-config") {
+        if pos + 1 < args.len() {
+            let path = PathBuf::from(&args[pos + 1]);
+            if !path.is_file() {
+                eprintln!("Error: default.conf not found: {}", path.display());
+                std::process::exit(1);
+            }
+            cb_config_path = Some(path);
+            args.remove(pos + 1);
+            args.remove(pos);
+        } else {
+            eprintln!("Error: --cb-config option requires an argument");
+            print_workspace_usage(&program_name);
+            std::process::exit(1);
+        }
+    }
+
+    // 检查并移除--linker/-l参数
+    let mut linker_type = "gcc".to_string();
+    if let Some(linker_pos) = args.iter().position(|arg| arg == "--linker" || arg == "-l") {
+        if linker_pos + 1 < args.len() {
+            linker_type = args[linker_pos + 1].clone();
+            args.remove(linker_pos + 1);
+            args.remove(linker_pos);
+        } else {
+            eprintln!("Error: --linker/-l option requires an argument");
+            print_workspace_usage(&program_name);
+            std::process::exit(1);
+        }
+    }
+
+    // 检查并移除--ninja/-n参数
+    let mut ninja_path = None;
+    if let Some(ninja_pos) = args.iter().position(|arg| arg == "--ninja" || arg == "-n") {
+        if ninja_pos + 1 < args.len() {
+            ninja_path = Some(args[ninja_pos + 1].clone());
+            args.remove(ninja_pos + 1);
+            args.remove(ninja_pos);
+        } else {
+            eprintln!("Error: --ninja/-n option requires an argument");
+            print_workspace_usage(&program_name);
+            std::process::exit(1);
+        }
+    }
+
+    // 检查并移除--target参数
+    let mut target = None;
+    if let Some(target_pos) = args.iter().position(|arg| arg == "--target") {
+        if target_pos + 1 < args.len() {
+            target = Some(args[target_pos + 1].clone());
+            args.remove(target_pos + 1);
+            args.remove(target_pos);
+        } else {
+            eprintln!("Error: --target option requires an argument");
+            print_workspace_usage(&program_name);
+            std::process::exit(1);
+        }
+    }
+
+    // 剩余参数：<file.workspace> [output_dir]
+    if args.is_empty() || args.len() > 2 {
+        print_workspace_usage(&program_name);
+        std::process::exit(1);
+    }
+
+    let workspace_path = PathBuf::from(&args[0]);
+    if workspace_path.extension().and_then(|e| e.to_str()) != Some("workspace") {
+        eprintln!("Error: Expected .workspace file: {}", workspace_path.display());
+        std::process::exit(1);
+    }
+    if !workspace_path.is_file() {
+        eprintln!("File not found: {}", workspace_path.display());
+        std::process::exit(1);
+    }
+
+    let workspace_dir = workspace_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf();
+    let output_dir = match args.get(1) {
+        // 相对路径基于 .workspace 文件所在目录
+        Some(dir) => workspace_dir.join(dir),
+        None => workspace_dir,
+    };
+
+    Ok(Command::Workspace(WorkspaceArgs {
+        workspace_path,
+        output_dir,
+        debug,
+        linker_type,
+        ninja_path,
+        no_header_insertion,
+        target,
+        compile_commands_format,
+        probe_compiler,
+        cb_compilers_dir,
+        cb_config_path,
     }))
 }
 
@@ -224,6 +448,48 @@ fn parse_convert(
         args.remove(pos);
     }
 
+    // 检查并移除--arguments 标志：compile_commands.json 使用 arguments 数组
+    let compile_commands_format = take_compile_commands_format(&mut args);
+
+    // 检查并移除--probe-compiler 标志：运行编译器探测系统 include 路径
+    let probe_compiler = args.iter().any(|arg| arg == "--probe-compiler");
+    if let Some(pos) = args.iter().position(|arg| arg == "--probe-compiler") {
+        args.remove(pos);
+    }
+
+    // 检查并移除--cb-compilers参数：额外的 Code::Blocks 编译器定义目录
+    let mut cb_compilers_dir = None;
+    if let Some(pos) = args.iter().position(|arg| arg == "--cb-compilers") {
+        if pos + 1 < args.len() {
+            cb_compilers_dir = Some(PathBuf::from(&args[pos + 1]));
+            args.remove(pos + 1);
+            args.remove(pos);
+        } else {
+            eprintln!("Error: --cb-compilers option requires an argument");
+            print_convert_usage(&program_name);
+            std::process::exit(1);
+        }
+    }
+
+    // 检查并移除--cb-config参数：指定 Code::Blocks default.conf 的位置
+    let mut cb_config_path = None;
+    if let Some(pos) = args.iter().position(|arg| arg == "--cb-config") {
+        if pos + 1 < args.len() {
+            let path = PathBuf::from(&args[pos + 1]);
+            if !path.is_file() {
+                eprintln!("Error: default.conf not found: {}", path.display());
+                std::process::exit(1);
+            }
+            cb_config_path = Some(path);
+            args.remove(pos + 1);
+            args.remove(pos);
+        } else {
+            eprintln!("Error: --cb-config option requires an argument");
+            print_convert_usage(&program_name);
+            std::process::exit(1);
+        }
+    }
+
     // 检查并移除--linker/-l参数
     let mut linker_type = "gcc".to_string();
     if let Some(linker_pos) = args.iter().position(|arg| arg == "--linker" || arg == "-l") {
@@ -252,6 +518,20 @@ fn parse_convert(
         }
     }
 
+    // 检查并移除--target参数
+    let mut target = None;
+    if let Some(target_pos) = args.iter().position(|arg| arg == "--target") {
+        if target_pos + 1 < args.len() {
+            target = Some(args[target_pos + 1].clone());
+            args.remove(target_pos + 1);
+            args.remove(target_pos);
+        } else {
+            eprintln!("Error: --target option requires an argument");
+            print_convert_usage(&program_name);
+            std::process::exit(1);
+        }
+    }
+
     // 测试模式：允许 args.len() == 1
     if is_test_mode {
         return Ok(Command::Convert(ConvertArgs {
@@ -262,6 +542,11 @@ fn parse_convert(
             test_mode: true,
             ninja_path,
             no_header_insertion: false,
+            target,
+            compile_commands_format,
+            probe_compiler,
+            cb_compilers_dir,
+        cb_config_path,
         }));
     }
 
@@ -313,21 +598,60 @@ fn parse_convert(
         test_mode: false,
         ninja_path,
         no_header_insertion,
+        target,
+        compile_commands_format,
+        probe_compiler,
+        cb_compilers_dir,
+        cb_config_path,
     }))
 }
 
+/// 检查并移除 --arguments 标志，返回 compile_commands.json 的写法
+fn take_compile_commands_format(args: &mut Vec<String>) -> CompileCommandsFormat {
+    match args.iter().position(|arg| arg == "--arguments") {
+        Some(pos) => {
+            args.remove(pos);
+            CompileCommandsFormat::Arguments
+        }
+        None => CompileCommandsFormat::Command,
+    }
+}
+
 /// 打印 merge-compile-commands 的使用说明
 fn print_merge_usage(program: &str) {
     eprintln!(
-        "Usage: {} merge-compile-commands [--json] <file1> <file2> [file3...] [--output-dir <dir>] [--debug]",
+        "Usage: {} merge-compile-commands [--json] <file1> <file2> [file3...] [--output-dir <dir>] [--target <name>] [--cb-config <path>] [--debug]",
         program
     );
     eprintln!("Options:");
     eprintln!("  --json              Treat input files as compile_commands.json directly (not .cbp)");
     eprintln!("  --output-dir <dir>  Specify workspace root directory for .clangd file (CBP mode only)");
+    eprintln!("  --target <name>     Use the compile_commands.json of this Build Target (CBP mode only)");
+    eprintln!("                      A virtual target merges the databases of all its members");
+    eprintln!("  --cb-config <path>  Code::Blocks default.conf used to expand global variables (CBP mode only)");
     eprintln!("  --debug             Enable debug logging");
 }
 
+/// 打印 workspace 的使用说明
+fn print_workspace_usage(program: &str) {
+    eprintln!(
+        "Usage: {} workspace [OPTIONS] <file.workspace> [output_dir]",
+        program
+    );
+    eprintln!("Converts every project of a Code::Blocks workspace and merges their");
+    eprintln!("compile_commands.json into <output_dir> (default: the workspace directory)");
+    eprintln!("Options:");
+    eprintln!("  --no-header-insertion    Disable header insertion in clangd completion");
+    eprintln!("  --linker <type>, -l      Specify linker type (gcc or ld)");
+    eprintln!("  --target <name>          Build Target or virtual target used in every project (default: first target)");
+    eprintln!("  --arguments              Write compile_commands.json entries as \"arguments\" arrays");
+    eprintln!("  --probe-compiler         Query the compiler for its system include dirs (-E -v)");
+    eprintln!("  --cb-compilers <dir>     Extra directory with Code::Blocks compiler_<id>.xml definitions");
+    eprintln!("  --cb-config <path>       Code::Blocks default.conf to use (overrides CBP2CLANGD_CB_CONFIG)");
+    eprintln!("  --ninja <path>, -n       Specify custom ninja executable path");
+    eprintln!("  --debug                  Enable debug logging");
+}
+
 /// 打印 convert 命令的使用说明
 fn print_convert_usage(program: &str) {
     eprintln!("cbp2clangd v{}", env!("CARGO_PKG_VERSION"));
@@ -337,6 +661,7 @@ fn print_convert_usage(program: &str) {
     eprintln!("Usage:");
     eprintln!("  {} [OPTIONS] <project.cbp> [output_dir]", program);
     eprintln!("  {} merge-compile-commands [--json] <file1> <file2> ... [--output-dir <dir>] [--debug]", program);
+    eprintln!("  {} workspace [OPTIONS] <file.workspace> [output_dir]", program);
     eprintln!("  {} --version | -v", program);
     eprintln!();
     eprintln!("Commands:");
@@ -344,6 +669,9 @@ fn print_convert_usage(program: &str) {
     eprintln!("                            Merge multiple compile_commands.json files from CBP projects,"
     );
     eprintln!("                            or from JSON files directly with --json flag");
+    eprintln!("  workspace <file.workspace> [output_dir]");
+    eprintln!("                            Convert all projects of a Code::Blocks workspace and merge");
+    eprintln!("                            their compile_commands.json at the workspace root");
     eprintln!();
     eprintln!("Options:");
     eprintln!("  --debug                  Enable debug logging");
@@ -351,6 +679,12 @@ fn print_convert_usage(program: &str) {
     eprintln!("  --no-header-insertion    Disable header insertion in clangd completion");
     eprintln!("  --linker <type>          Specify linker type (gcc or ld)");
     eprintln!("  -l <type>                Short form for --linker");
+    eprintln!("  --target <name>          Build Target used for .clangd (default: first target)");
+    eprintln!("                           A virtual target uses its first member");
+    eprintln!("  --arguments              Write compile_commands.json entries as \"arguments\" arrays");
+    eprintln!("  --probe-compiler         Query the compiler for its system include dirs (-E -v)");
+    eprintln!("  --cb-compilers <dir>     Extra directory with Code::Blocks compiler_<id>.xml definitions");
+    eprintln!("  --cb-config <path>       Code::Blocks default.conf to use (overrides CBP2CLANGD_CB_CONFIG)");
     eprintln!("  --ninja <path>           Specify custom ninja executable path");
     eprintln!("  -n <path>                Short form for --ninja");
     eprintln!("  --output-dir <dir>       Specify workspace root directory (for merge-compile-commands)");
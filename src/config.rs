@@ -1,7 +1,12 @@
 use std::fmt;
 
-use crate::cb_config::CbCompilerConfig;
+use crate::cb_config::{CbCompilerConfig, CbCompilerDefinition, CbCompilerEntry};
+use crate::clang_target::{DEFAULT_TARGET_TRIPLE, infer_target_triple, normalize_triple};
 use crate::debug_println;
+use crate::models::SourceLanguage;
+use crate::probe::{CompilerProbe, probe_compiler_cached};
+use crate::platform::{default_toolchain_root, exe_name, join_host};
+use crate::toolchains::{BUILTIN_TOOLCHAIN_IDS, DefaultRoot, Tool, ToolNames, ToolchainDef, detect_version_dir};
 
 /// 工具链解析失败错误类型
 #[derive(Debug)]
@@ -33,68 +38,96 @@ impl fmt::Display for ToolchainResolveError {
 
 impl std::error::Error for ToolchainResolveError {}
 
-/// Hardcoded 工具链版本信息 (default.conf 中不包含此信息)
-struct HardcodedToolchainInfo {
-    version_name: String,
-    gcc_version: String,
+/// Code::Blocks 配置中的可执行文件名 (default.conf 的编译器条目或 options_<id>.xml 的 <Program>)
+struct CbExecutables<'a> {
+    c_compiler: Option<&'a String>,
+    cpp_compiler: Option<&'a String>,
+    linker: Option<&'a String>,
+    lib_linker: Option<&'a String>,
 }
 
-/// 获取 hardcoded 的版本映射
-fn get_hardcoded_defaults(id: &str) -> Option<HardcodedToolchainInfo> {
-    match id {
-        "riscv32" => Some(HardcodedToolchainInfo {
-            version_name: "V1".to_string(),
-            gcc_version: "6.1.0".to_string(),
-        }),
-        "riscv32-v2" => Some(HardcodedToolchainInfo {
-            version_name: "V2".to_string(),
-            gcc_version: "10.2.0".to_string(),
-        }),
-        "riscv32-v3" => Some(HardcodedToolchainInfo {
-            version_name: "V3".to_string(),
-            gcc_version: "14.2.0".to_string(),
-        }),
-        _ => None,
+impl<'a> From<&'a CbCompilerEntry> for CbExecutables<'a> {
+    fn from(entry: &'a CbCompilerEntry) -> Self {
+        CbExecutables {
+            c_compiler: entry.c_compiler.as_ref(),
+            cpp_compiler: entry.cpp_compiler.as_ref(),
+            linker: entry.linker.as_ref(),
+            lib_linker: entry.lib_linker.as_ref(),
+        }
     }
 }
 
-/// 获取所有 hardcoded 的编译器 ID 列表
-fn get_hardcoded_ids() -> Vec<String> {
-    vec![
-        "riscv32".to_string(),
-        "riscv32-v2".to_string(),
-        "riscv32-v3".to_string(),
-    ]
+impl<'a> From<&'a CbCompilerDefinition> for CbExecutables<'a> {
+    fn from(definition: &'a CbCompilerDefinition) -> Self {
+        CbExecutables {
+            c_compiler: definition.c_compiler.as_ref(),
+            cpp_compiler: definition.cpp_compiler.as_ref(),
+            linker: definition.linker.as_ref(),
+            lib_linker: definition.lib_linker.as_ref(),
+        }
+    }
 }
 
-/// 从 compiler_id 推导 version_name
-/// 例如 "riscv32-v4" -> "V4"，"riscv32" -> "V1"
-fn derive_version_name(compiler_id: &str) -> String {
-    if let Some(dash_pos) = compiler_id.rfind('-') {
-        let suffix = &compiler_id[dash_pos + 1..];
-        // 如果后缀以 'v' 开头，提取版本号
-        if let Some(stripped) = suffix.strip_prefix('v') {
-            format!("V{}", stripped)
-        } else {
-            format!("V{}", suffix)
+/// 去掉可执行文件名的 .exe 后缀 (按宿主平台重新追加)
+fn exe_stem(name: &str) -> String {
+    let lower = name.to_ascii_lowercase();
+    match lower.strip_suffix(".exe") {
+        Some(stripped) => name[..stripped.len()].to_string(),
+        None => name.to_string(),
+    }
+}
+
+/// 使用 Code::Blocks 配置中的可执行文件名替换工具链描述中的默认工具名
+fn apply_cb_executables(tools: &mut ToolNames, executables: CbExecutables) {
+    if let Some(cc) = executables.c_compiler {
+        // 汇编驱动默认与 C 编译器相同
+        if tools.asm == tools.cc {
+            tools.asm = exe_stem(cc);
         }
-    } else {
-        "V1".to_string()
+        tools.cc = exe_stem(cc);
+    }
+    if let Some(cxx) = executables.cpp_compiler {
+        tools.cxx = exe_stem(cxx);
+    }
+    if let Some(linker) = executables.linker {
+        tools.link = exe_stem(linker);
+    }
+    if let Some(ar) = executables.lib_linker {
+        tools.ar = exe_stem(ar);
+    }
+    debug_println!("[DEBUG config] Tools after Code::Blocks overrides: {:?}", tools);
+}
+
+/// 将 Code::Blocks 编译器定义应用到工具链描述：可执行文件、默认安装路径和选项开关
+fn apply_cb_definition(def: &mut ToolchainDef, definition: &CbCompilerDefinition) {
+    apply_cb_executables(&mut def.tools, definition.into());
+    if let Some(master_path) = definition.default_master_path() {
+        def.default_root = DefaultRoot::Fixed(master_path);
+    }
+    if let Some(object_extension) = definition.switches.get("objectExtension") {
+        def.switches.object_extension.clone_from(object_extension);
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct ToolchainConfig {
-    pub version_name: String,                // e.g., "V2"
-    pub gcc_version: String,                 // e.g., "10.2.0"
+    pub compiler_id: String,                 // CBP 中的编译器 ID, e.g., "riscv32-v2"
+    pub version_name: String,                // e.g., "V2" (仅 RV32 系列使用)
+    pub gcc_version: String,                 // e.g., "10.2.0"；为空时从工具链目录探测
+    pub def: ToolchainDef,                   // 工具前缀、工具名和 include 布局
     pub toolchain_base_path: Option<String>, // 自定义工具链基础路径
     pub cb_include_dirs: Vec<String>,        // 来自 default.conf 的额外 include 路径
+    pub cb_library_dirs: Vec<String>,        // 来自 default.conf 的额外库目录 (链接时作为 -L)
+    pub cb_extra_paths: Vec<String>,         // 来自 default.conf 的 EXTRA_PATHS，bin 目录下找不到工具时查找
+    pub cb_compiler_options: Vec<String>,    // 来自 default.conf 的全局编译选项，位于项目选项之前
+    pub cb_linker_options: Vec<String>,      // 来自 default.conf 的全局链接选项，位于项目选项之前
+    pub system_probe: Option<CompilerProbe>, // 运行编译器探测到的系统 include 路径等 (--probe-compiler)
 }
 
 impl ToolchainConfig {
     /// 根据编译器 ID 和可选的 Code::Blocks 配置解析工具链
     ///
-    /// 优先使用 default.conf 中的配置，降级到 hardcoded 默认值
+    /// 优先使用 default.conf 中的配置，降级到内置的工具链描述
     /// 当编译器 ID 在所有来源中都找不到时返回错误
     pub fn resolve_toolchain(
         compiler_id: &str,
@@ -106,73 +139,82 @@ impl ToolchainConfig {
             cb_config.is_some()
         );
 
-        // 获取 hardcoded 版本信息
-        let hardcoded = get_hardcoded_defaults(compiler_id);
-        let version_name = hardcoded
-            .as_ref()
-            .map(|h| h.version_name.clone())
-            .unwrap_or_else(|| derive_version_name(compiler_id));
-        let gcc_version = hardcoded
-            .as_ref()
-            .map(|h| h.gcc_version.clone())
-            .unwrap_or_else(|| "14.2.0".to_string());
-
-        // 尝试从 default.conf 获取 master_path
-        let (toolchain_base_path, cb_include_dirs) = if let Some(config) = cb_config {
-            if let Some(entry) = config.compilers.get(compiler_id) {
-                debug_println!(
-                    "[DEBUG config] Found compiler '{}' in default.conf, master_path={:?}",
-                    compiler_id,
-                    entry.master_path
-                );
-                (entry.master_path.clone(), entry.include_dirs.clone())
-            } else {
-                // default.conf 中找不到该编译器
-                debug_println!(
-                    "[DEBUG config] Compiler '{}' not found in default.conf",
-                    compiler_id
-                );
-
-                // 如果有 hardcoded 默认值，允许降级使用
-                if hardcoded.is_some() {
-                    debug_println!(
-                        "[DEBUG config] Using hardcoded default path for '{}'",
-                        compiler_id
-                    );
-                    (None, Vec::new())
-                } else {
-                    // 既不在 default.conf 中也没有 hardcoded 默认值 -> 报错
-                    let mut available: Vec<String> = config.compilers.keys().cloned().collect();
-                    available.sort();
-                    return Err(ToolchainResolveError::UnknownCompiler {
-                        compiler_id: compiler_id.to_string(),
-                        available,
-                    });
-                }
+        // 编译器自身及其 PARENT 链 (default.conf 中复制出来的编译器)
+        let mut lineage = vec![compiler_id.to_string()];
+        if let Some(config) = cb_config {
+            lineage.extend(config.ancestors(compiler_id));
+        }
+        let entry = cb_config.and_then(|config| config.compilers.get(compiler_id));
+        // 内置的工具链描述 (RV32 系列 + Code::Blocks 默认编译器)
+        let builtin = lineage.iter().find_map(|id| ToolchainDef::builtin(id));
+        // Code::Blocks 安装或用户目录中的编译器定义 (compilers/*.xml)
+        let definition = cb_config.and_then(|config| lineage.iter().find_map(|id| config.definitions.get(id)));
+
+        let known = entry.is_some() || ToolchainDef::builtin(compiler_id).is_some()
+            || cb_config.is_some_and(|config| config.definitions.contains_key(compiler_id));
+        if !known {
+            // default.conf、内置描述和编译器定义中都没有 -> 报错
+            debug_println!("[DEBUG config] Compiler '{}' has no definition anywhere", compiler_id);
+            let mut available: Vec<String> = BUILTIN_TOOLCHAIN_IDS.iter().map(|id| id.to_string()).collect();
+            if let Some(config) = cb_config {
+                available.extend(config.compilers.keys().cloned());
+                available.extend(config.definitions.keys().cloned());
             }
-        } else {
-            // 没有 default.conf，降级到 hardcoded
-            if hardcoded.is_none() {
-                let available = get_hardcoded_ids();
-                return Err(ToolchainResolveError::UnknownCompiler {
-                    compiler_id: compiler_id.to_string(),
-                    available,
-                });
+            available.sort();
+            available.dedup();
+            return Err(ToolchainResolveError::UnknownCompiler {
+                compiler_id: compiler_id.to_string(),
+                available,
+            });
+        }
+
+        debug_println!(
+            "[DEBUG config] Compiler '{}': default.conf={}, built-in={}, definition={:?}",
+            compiler_id,
+            entry.is_some(),
+            builtin.is_some(),
+            definition.map(|d| &d.source)
+        );
+        let mut def = match (builtin, definition) {
+            (Some(def), _) => def,
+            (None, Some(definition)) => {
+                let c_compiler = definition.c_compiler.as_deref().map(exe_stem).unwrap_or_default();
+                ToolchainDef::from_c_compiler(&c_compiler)
             }
-            (None, Vec::new())
+            (None, None) => ToolchainDef::fallback(compiler_id),
         };
+        // 优先级：default.conf 中保存的设置 > 编译器定义 > 内置描述
+        if let Some(definition) = definition {
+            apply_cb_definition(&mut def, definition);
+        }
+        if let Some(entry) = entry {
+            debug_println!(
+                "[DEBUG config] Found compiler '{}' in default.conf, master_path={:?}",
+                compiler_id,
+                entry.master_path
+            );
+            apply_cb_executables(&mut def.tools, entry.into());
+        }
+        let list = |field: fn(&CbCompilerEntry) -> &Vec<String>| entry.map(|e| field(e).clone()).unwrap_or_default();
 
         let config = ToolchainConfig {
-            version_name,
-            gcc_version,
-            toolchain_base_path,
-            cb_include_dirs,
+            compiler_id: compiler_id.to_string(),
+            version_name: def.version_name.clone().unwrap_or_default(),
+            gcc_version: def.gcc_version.clone().unwrap_or_default(),
+            def,
+            toolchain_base_path: entry.and_then(|e| e.master_path.clone()),
+            cb_include_dirs: list(|e| &e.include_dirs),
+            cb_library_dirs: list(|e| &e.library_dirs),
+            cb_extra_paths: list(|e| &e.extra_paths),
+            cb_compiler_options: list(|e| &e.compiler_options),
+            cb_linker_options: list(|e| &e.linker_options),
+            system_probe: None,
         };
         debug_println!("[DEBUG config] Resolved toolchain: {:?}", config);
         Ok(config)
     }
 
-    /// 旧 API：仅使用 hardcoded 默认值
+    /// 旧 API：仅使用内置的工具链描述
     /// 保留向后兼容 (parser.rs 等调用点)
     pub fn from_compiler_id(id: &str) -> Option<Self> {
         debug_println!(
@@ -197,10 +239,10 @@ impl ToolchainConfig {
             );
             custom_path.clone()
         } else {
-            let default_path = format!(
-                "C:\\Program Files (x86)\\RV32-Toolchain\\RV32-{}",
-                self.version_name
-            );
+            let default_path = match &self.def.default_root {
+                DefaultRoot::Rv32Versioned => default_toolchain_root(&self.version_name),
+                DefaultRoot::Fixed(path) => path.clone(),
+            };
             debug_println!(
                 "[DEBUG config] Using default toolchain path: {}",
                 default_path
@@ -211,11 +253,47 @@ impl ToolchainConfig {
         path
     }
 
+    /// 工具的可执行文件名 (含工具前缀，不含路径和 .exe)，也用作工具不存在时的占位符
+    pub fn tool_name(&self, tool: Tool) -> String {
+        self.def.tools.get(tool).to_string()
+    }
+
+    /// 拼接工具链 bin 目录下某个工具的完整路径 (按宿主平台追加 .exe)
+    /// 工具不在 bin 目录下时，依次在 default.conf 的 EXTRA_PATHS 中查找
+    pub fn tool_path(&self, tool: Tool) -> String {
+        let name = exe_name(&self.tool_name(tool));
+        let default_path = join_host(&[&self.get_base_path(), "bin", &name]);
+        if std::path::Path::new(&default_path).exists() {
+            return default_path;
+        }
+        self.cb_extra_paths
+            .iter()
+            .map(|dir| join_host(&[dir.trim_end_matches(['/', '\\']), &name]))
+            .find(|path| std::path::Path::new(path).exists())
+            .unwrap_or(default_path)
+    }
+
+    /// 工具链中各工具的前缀 (riscv32-elf-gcc 中的 riscv32-elf-)
+    pub fn tool_prefix(&self) -> &str {
+        &self.def.tool_prefix
+    }
+
+    /// clangd 使用的目标三元组：探测到的编译器 Target -> 由编译器 ID 推断 -> 由工具前缀推断 -> 默认值
+    pub fn target_triple(&self) -> String {
+        if let Some(triple) = self.system_probe.as_ref().and_then(|p| p.target_triple.as_ref()) {
+            debug_println!("[DEBUG config] Using probed target triple: {}", triple);
+            return normalize_triple(triple);
+        }
+        let triple = infer_target_triple(&self.compiler_id)
+            .or_else(|| infer_target_triple(self.tool_prefix()))
+            .unwrap_or_else(|| DEFAULT_TARGET_TRIPLE.to_string());
+        debug_println!("[DEBUG config] Inferred target triple for '{}': {}", self.compiler_id, triple);
+        triple
+    }
+
     pub fn compiler_path(&self) -> String {
         debug_println!("[DEBUG config] Building compiler path...");
-        let base_path = self.get_base_path();
-        debug_println!("[DEBUG config] Base path: {}", base_path);
-        let compiler_path = format!("{}\\bin\\riscv32-elf-gcc.exe", base_path);
+        let compiler_path = self.tool_path(Tool::Cc);
         debug_println!("[DEBUG config] Final compiler path: {}", compiler_path);
         debug_println!(
             "[DEBUG config] Compiler path exists: {}",
@@ -224,20 +302,40 @@ impl ToolchainConfig {
         compiler_path
     }
 
+    /// C++ 编译器路径 (C++ 源文件或 Unit 指定 compilerVar="CPP" 时使用)
+    pub fn cpp_compiler_path(&self) -> String {
+        let cpp_compiler_path = self.tool_path(Tool::Cxx);
+        debug_println!("[DEBUG config] C++ compiler path: {}", cpp_compiler_path);
+        cpp_compiler_path
+    }
+
+    /// 汇编文件 (.s/.S) 使用的驱动路径，通过 gcc 驱动以支持 .S 的预处理
+    pub fn assembler_path(&self) -> String {
+        let assembler_path = self.tool_path(Tool::As);
+        debug_println!("[DEBUG config] Assembler driver path: {}", assembler_path);
+        assembler_path
+    }
+
+    /// 按源文件语言选择编译器驱动路径
+    pub fn driver_path(&self, language: SourceLanguage) -> String {
+        match language {
+            SourceLanguage::C => self.compiler_path(),
+            SourceLanguage::Cpp => self.cpp_compiler_path(),
+            SourceLanguage::Asm => self.assembler_path(),
+        }
+    }
+
     /// 获取链接器路径，根据类型返回gcc或ld
     pub fn linker_path(&self, linker_type: &str) -> String {
         debug_println!(
             "[DEBUG config] Building linker path for type: {}",
             linker_type
         );
-        let base_path = self.get_base_path();
-        debug_println!("[DEBUG config] Base path: {}", base_path);
-
         let linker_path = if linker_type == "ld" {
-            format!("{}\\bin\\riscv32-elf-ld.exe", base_path)
+            self.tool_path(Tool::Ld)
         } else {
-            // 默认使用gcc作为链接器
-            self.compiler_path()
+            // 默认使用 gcc 驱动作为链接器 (default.conf 的 LINKER)
+            self.tool_path(Tool::Link)
         };
 
         debug_println!("[DEBUG config] Final linker path: {}", linker_path);
@@ -251,9 +349,7 @@ impl ToolchainConfig {
     /// 获取ar路径，用于创建静态库
     pub fn ar_path(&self) -> String {
         debug_println!("[DEBUG config] Building ar path...");
-        let base_path = self.get_base_path();
-        debug_println!("[DEBUG config] Base path: {}", base_path);
-        let ar_path = format!("{}\\bin\\riscv32-elf-ar.exe", base_path);
+        let ar_path = self.tool_path(Tool::Ar);
         debug_println!("[DEBUG config] Final ar path: {}", ar_path);
         debug_println!(
             "[DEBUG config] Ar path exists: {}",
@@ -262,41 +358,30 @@ impl ToolchainConfig {
         ar_path
     }
 
+    /// objcopy 路径 (生成 hex/bin 的 post-build 步骤中使用)
+    pub fn objcopy_path(&self) -> String {
+        self.tool_path(Tool::Objcopy)
+    }
+
+    /// size 路径
+    pub fn size_path(&self) -> String {
+        self.tool_path(Tool::Size)
+    }
+
     pub fn include_paths(&self) -> Vec<String> {
         debug_println!("[DEBUG config] Building include paths...");
         let base = self.get_base_path();
-        let gcc_ver = &self.gcc_version;
         debug_println!("[DEBUG config] Base path: {}", base);
 
         let mut paths = Vec::new();
 
-        // 工具链标准 include 路径
-        let path1 = format!("{}\\lib\\gcc\\riscv32-elf\\{}\\include", base, gcc_ver);
-        let path2 = format!(
-            "{}\\lib\\gcc\\riscv32-elf\\{}\\include-fixed",
-            base, gcc_ver
-        );
-        let path3 = format!("{}\\riscv32-elf\\include", base);
-
-        debug_println!("[DEBUG config] Include path 1: {}", path1);
-        debug_println!(
-            "[DEBUG config] Include path 1 exists: {}",
-            std::path::Path::new(&path1).exists()
-        );
-        debug_println!("[DEBUG config] Include path 2: {}", path2);
-        debug_println!(
-            "[DEBUG config] Include path 2 exists: {}",
-            std::path::Path::new(&path2).exists()
-        );
-        debug_println!("[DEBUG config] Include path 3: {}", path3);
-        debug_println!(
-            "[DEBUG config] Include path 3 exists: {}",
-            std::path::Path::new(&path3).exists()
-        );
-
-        paths.push(path1);
-        paths.push(path2);
-        paths.push(path3);
+        // 探测到的系统 include 路径与编译器实际使用的完全一致，优先于按 gcc 版本拼接的路径
+        if let Some(probe) = &self.system_probe {
+            debug_println!("[DEBUG config] Using probed system include dirs: {:?}", probe.system_includes);
+            paths.extend(probe.system_includes.iter().cloned());
+        } else {
+            paths.extend(self.default_system_include_paths(&base));
+        }
 
         // 追加来自 default.conf 的额外 include 路径
         for dir in &self.cb_include_dirs {
@@ -307,6 +392,62 @@ impl ToolchainConfig {
         paths
     }
 
+    /// 运行编译器探测系统 include 路径、目标三元组和内置宏 (结果按编译器路径缓存)
+    /// 编译器不存在或探测失败时保留按 gcc 版本拼接的默认路径，返回是否探测成功
+    pub fn probe_system_includes(&mut self) -> bool {
+        if !self.is_compiler_available() {
+            debug_println!("[DEBUG config] Compiler not available, skipping probe");
+            return false;
+        }
+        self.system_probe = probe_compiler_cached(&self.compiler_path());
+        self.system_probe.is_some()
+    }
+
+    /// 按工具链描述的 include 布局拼接的标准 include 路径
+    /// 布局中的 {version} 使用已知的 gcc 版本，未知时取该目录下版本号最高的子目录 (找不到则跳过该路径)
+    fn default_system_include_paths(&self, base: &str) -> Vec<String> {
+        let mut paths = Vec::new();
+
+        for layout in &self.def.include_layout {
+            let segments: Vec<&str> = layout.split('/').collect();
+            let version = match segments.iter().position(|s| *s == "{version}") {
+                Some(_) if !self.gcc_version.is_empty() => Some(self.gcc_version.clone()),
+                Some(pos) => {
+                    let mut parent = vec![base];
+                    parent.extend(&segments[..pos]);
+                    detect_version_dir(&join_host(&parent))
+                }
+                None => None,
+            };
+
+            let mut parts = vec![base];
+            for segment in &segments {
+                match (*segment, &version) {
+                    ("{version}", Some(version)) => parts.push(version),
+                    ("{version}", None) => {
+                        debug_println!("[DEBUG config] No compiler version for include layout {}, skipped", layout);
+                        parts.clear();
+                        break;
+                    }
+                    _ => parts.push(segment),
+                }
+            }
+            if parts.is_empty() {
+                continue;
+            }
+
+            let path = join_host(&parts);
+            debug_println!("[DEBUG config] Include path: {}", path);
+            debug_println!(
+                "[DEBUG config] Include path exists: {}",
+                std::path::Path::new(&path).exists()
+            );
+            paths.push(path);
+        }
+
+        paths
+    }
+
     /// 检查编译器路径是否存在
     pub fn is_compiler_available(&self) -> bool {
         let path = self.compiler_path();
@@ -343,12 +484,20 @@ mod tests {
     }
 
     #[test]
+    #[cfg(windows)]
     fn test_path_generation() {
         let config = ToolchainConfig {
+            compiler_id: "test".to_string(),
             version_name: "TestVer".to_string(),
             gcc_version: "1.0.0".to_string(),
+            def: ToolchainDef::rv32("TestVer", Some("1.0.0")),
             toolchain_base_path: Some("C:\\CustomToolchain".to_string()),
             cb_include_dirs: Vec::new(),
+            cb_library_dirs: Vec::new(),
+            cb_extra_paths: Vec::new(),
+            cb_compiler_options: Vec::new(),
+            cb_linker_options: Vec::new(),
+            system_probe: None,
         };
 
         // 测试自定义路径
@@ -360,6 +509,37 @@ mod tests {
         assert!(config.linker_path("ld").ends_with("ld.exe"));
     }
 
+    #[test]
+    #[cfg(not(windows))]
+    fn test_path_generation_posix() {
+        let config = ToolchainConfig {
+            compiler_id: "test".to_string(),
+            version_name: "TestVer".to_string(),
+            gcc_version: "1.0.0".to_string(),
+            def: ToolchainDef::rv32("TestVer", Some("1.0.0")),
+            toolchain_base_path: Some("/opt/custom-toolchain".to_string()),
+            cb_include_dirs: Vec::new(),
+            cb_library_dirs: Vec::new(),
+            cb_extra_paths: Vec::new(),
+            cb_compiler_options: Vec::new(),
+            cb_linker_options: Vec::new(),
+            system_probe: None,
+        };
+
+        // POSIX 上使用正斜杠且没有 .exe 后缀
+        assert_eq!(config.compiler_path(), "/opt/custom-toolchain/bin/riscv32-elf-gcc");
+        assert_eq!(config.ar_path(), "/opt/custom-toolchain/bin/riscv32-elf-ar");
+        assert_eq!(config.linker_path("ld"), "/opt/custom-toolchain/bin/riscv32-elf-ld");
+        assert_eq!(
+            config.include_paths()[0],
+            "/opt/custom-toolchain/lib/gcc/riscv32-elf/1.0.0/include"
+        );
+
+        // 未配置 MASTER_PATH 时落到 /opt 下的默认安装位置
+        let default = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+        assert_eq!(default.get_base_path(), "/opt/RV32-Toolchain/RV32-V2");
+    }
+
     #[test]
     fn test_resolve_toolchain_from_cb_config() {
         let mut compilers = HashMap::new();
@@ -370,17 +550,20 @@ mod tests {
                 master_path: Some("D:\\CustomToolchain".to_string()),
                 include_dirs: vec!["D:\\extra\\include".to_string()],
                 library_dirs: Vec::new(),
+                ..Default::default()
             },
         );
         let cb_config = CbCompilerConfig {
             compilers,
             default_compiler: None,
+            ..Default::default()
         };
 
         let toolchain =
             ToolchainConfig::resolve_toolchain("riscv32-v2", Some(&cb_config)).unwrap();
         assert_eq!(toolchain.toolchain_base_path, Some("D:\\CustomToolchain".to_string()));
         assert_eq!(toolchain.cb_include_dirs, vec!["D:\\extra\\include"]);
+        #[cfg(windows)]
         assert_eq!(toolchain.compiler_path(), "D:\\CustomToolchain\\bin\\riscv32-elf-gcc.exe");
     }
 
@@ -394,11 +577,13 @@ mod tests {
                 master_path: Some("D:\\V2".to_string()),
                 include_dirs: Vec::new(),
                 library_dirs: Vec::new(),
+                ..Default::default()
             },
         );
         let cb_config = CbCompilerConfig {
             compilers,
             default_compiler: None,
+            ..Default::default()
         };
 
         let result = ToolchainConfig::resolve_toolchain("unknown-compiler", Some(&cb_config));
@@ -426,6 +611,7 @@ mod tests {
         let cb_config = CbCompilerConfig {
             compilers: HashMap::new(),
             default_compiler: None,
+            ..Default::default()
         };
 
         let toolchain =
@@ -437,10 +623,17 @@ mod tests {
     #[test]
     fn test_cb_include_dirs_appended() {
         let config = ToolchainConfig {
+            compiler_id: "test".to_string(),
             version_name: "TestVer".to_string(),
             gcc_version: "1.0.0".to_string(),
+            def: ToolchainDef::rv32("TestVer", Some("1.0.0")),
             toolchain_base_path: Some("C:\\CustomToolchain".to_string()),
             cb_include_dirs: vec!["D:\\extra1".to_string(), "D:\\extra2".to_string()],
+            cb_library_dirs: Vec::new(),
+            cb_extra_paths: Vec::new(),
+            cb_compiler_options: Vec::new(),
+            cb_linker_options: Vec::new(),
+            system_probe: None,
         };
 
         let paths = config.include_paths();
@@ -451,10 +644,162 @@ mod tests {
     }
 
     #[test]
-    fn test_derive_version_name() {
-        assert_eq!(derive_version_name("riscv32-v4"), "V4");
-        assert_eq!(derive_version_name("riscv32-v10"), "V10");
-        assert_eq!(derive_version_name("riscv32"), "V1");
-        assert_eq!(derive_version_name("riscv32-custom"), "Vcustom");
+    fn test_probed_include_paths_replace_defaults() {
+        let mut config = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+        config.cb_include_dirs = vec!["D:\\extra".to_string()];
+        config.system_probe = Some(CompilerProbe {
+            system_includes: vec!["/opt/RV32/lib/gcc/riscv32-elf/10.2.1/include".to_string()],
+            target_triple: Some("riscv32-elf".to_string()),
+            defines: Vec::new(),
+        });
+
+        // 探测结果替代按 gcc_version (10.2.0) 拼接的路径，default.conf 的额外路径仍然追加
+        let paths = config.include_paths();
+        assert_eq!(paths, vec!["/opt/RV32/lib/gcc/riscv32-elf/10.2.1/include", "-ID:\\extra"]);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_generic_gcc_toolchains() {
+        // Code::Blocks 的默认编译器 ID 无需 default.conf
+        let avr = ToolchainConfig::resolve_toolchain("avr-gcc", None).unwrap();
+        assert_eq!(avr.compiler_path(), "/usr/bin/avr-gcc");
+        assert_eq!(avr.objcopy_path(), "/usr/bin/avr-objcopy");
+        assert_eq!(avr.tool_name(Tool::Cxx), "avr-g++");
+
+        let clang = ToolchainConfig::resolve_toolchain("clang", None).unwrap();
+        assert_eq!(clang.cpp_compiler_path(), "/usr/bin/clang++");
+        assert_eq!(clang.ar_path(), "/usr/bin/llvm-ar");
+        assert_eq!(clang.target_triple(), crate::clang_target::host_triple());
+
+        // 未知 gcc 版本时从 lib/gcc/<target>/ 下探测
+        let root = std::env::temp_dir().join(format!("cbp2clangd_avr_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("lib/gcc/avr/7.3.0/include")).unwrap();
+        let mut avr = avr;
+        avr.toolchain_base_path = Some(root.to_string_lossy().into_owned());
+        let base = avr.get_base_path();
+        assert_eq!(
+            avr.include_paths(),
+            vec![
+                format!("{}/lib/gcc/avr/7.3.0/include", base),
+                format!("{}/lib/gcc/avr/7.3.0/include-fixed", base),
+                format!("{}/avr/include", base),
+            ]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // default.conf 中非 RV32 的自定义编译器按宿主 gcc 处理
+        let mut compilers = HashMap::new();
+        compilers.insert(
+            "my-gcc".to_string(),
+            CbCompilerEntry {
+                compiler_id: "my-gcc".to_string(),
+                master_path: Some("/opt/my-gcc".to_string()),
+                include_dirs: Vec::new(),
+                library_dirs: Vec::new(),
+                ..Default::default()
+            },
+        );
+        let cb_config = CbCompilerConfig {
+            compilers,
+            ..Default::default()
+        };
+        let custom = ToolchainConfig::resolve_toolchain("my-gcc", Some(&cb_config)).unwrap();
+        assert_eq!(custom.compiler_path(), "/opt/my-gcc/bin/gcc");
+        assert!(custom.include_paths().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_toolchain_uses_cb_executables_and_options() {
+        let mut compilers = HashMap::new();
+        compilers.insert(
+            "my-arm".to_string(),
+            CbCompilerEntry {
+                compiler_id: "my-arm".to_string(),
+                parent: Some("arm-elf-gcc".to_string()),
+                master_path: Some("/opt/arm".to_string()),
+                c_compiler: Some("arm-none-eabi-gcc.exe".to_string()),
+                cpp_compiler: Some("arm-none-eabi-g++.EXE".to_string()),
+                linker: Some("arm-none-eabi-g++".to_string()),
+                lib_linker: Some("arm-none-eabi-ar".to_string()),
+                compiler_options: vec!["-mthumb".to_string()],
+                linker_options: vec!["-specs=nano.specs".to_string()],
+                library_dirs: vec!["/opt/arm/lib/thumb".to_string()],
+                ..Default::default()
+            },
+        );
+        let cb_config = CbCompilerConfig {
+            compilers,
+            ..Default::default()
+        };
+
+        let toolchain = ToolchainConfig::resolve_toolchain("my-arm", Some(&cb_config)).unwrap();
+        // 未修改的工具沿用 PARENT (arm-elf-gcc) 的内置描述
+        assert_eq!(toolchain.tool_prefix(), "arm-elf-");
+        assert_eq!(toolchain.tool_name(Tool::Cc), "arm-none-eabi-gcc");
+        assert_eq!(toolchain.tool_name(Tool::As), "arm-none-eabi-gcc");
+        assert_eq!(toolchain.tool_name(Tool::Cxx), "arm-none-eabi-g++");
+        assert_eq!(toolchain.tool_name(Tool::Link), "arm-none-eabi-g++");
+        assert_eq!(toolchain.tool_name(Tool::Ar), "arm-none-eabi-ar");
+        assert_eq!(toolchain.tool_name(Tool::Objcopy), "arm-elf-objcopy");
+        assert_eq!(toolchain.cb_compiler_options, vec!["-mthumb"]);
+        assert_eq!(toolchain.cb_linker_options, vec!["-specs=nano.specs"]);
+        assert_eq!(toolchain.cb_library_dirs, vec!["/opt/arm/lib/thumb"]);
+        #[cfg(not(windows))]
+        assert_eq!(toolchain.linker_path("gcc"), "/opt/arm/bin/arm-none-eabi-g++");
+    }
+
+    #[test]
+    fn test_resolve_toolchain_from_compiler_definition() {
+        let mut definitions = HashMap::new();
+        definitions.insert(
+            "arm-none-eabi".to_string(),
+            CbCompilerDefinition {
+                compiler_id: "arm-none-eabi".to_string(),
+                master_paths: vec!["/opt/gcc-arm".to_string()],
+                c_compiler: Some("arm-none-eabi-gcc".to_string()),
+                cpp_compiler: Some("arm-none-eabi-g++".to_string()),
+                lib_linker: Some("arm-none-eabi-ar".to_string()),
+                switches: HashMap::from([("objectExtension".to_string(), "obj".to_string())]),
+                ..Default::default()
+            },
+        );
+        let cb_config = CbCompilerConfig {
+            definitions,
+            ..Default::default()
+        };
+
+        // 只在编译器定义中出现的 ID 可以解析，工具前缀由 C 编译器名推断
+        let toolchain = ToolchainConfig::resolve_toolchain("arm-none-eabi", Some(&cb_config)).unwrap();
+        assert_eq!(toolchain.tool_prefix(), "arm-none-eabi-");
+        assert_eq!(toolchain.tool_name(Tool::Cxx), "arm-none-eabi-g++");
+        assert_eq!(toolchain.tool_name(Tool::Objcopy), "arm-none-eabi-objcopy");
+        assert_eq!(toolchain.def.switches.object_extension, "obj");
+        assert_eq!(toolchain.def.default_root, DefaultRoot::Fixed("/opt/gcc-arm".to_string()));
+        assert_eq!(toolchain.def.include_layout[2], "arm-none-eabi/include");
+
+        match ToolchainConfig::resolve_toolchain("sdcc", Some(&cb_config)) {
+            Err(ToolchainResolveError::UnknownCompiler { available, .. }) => {
+                assert!(available.contains(&"arm-none-eabi".to_string()));
+                assert!(available.contains(&"avr-gcc".to_string()));
+            }
+            other => panic!("Expected UnknownCompiler error, got {:?}", other.map(|t| t.compiler_id)),
+        }
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_tool_path_searches_extra_paths() {
+        let extra = std::env::temp_dir().join(format!("cbp2clangd_extra_{}", std::process::id()));
+        std::fs::create_dir_all(&extra).unwrap();
+        std::fs::write(extra.join("riscv32-elf-ar"), "").unwrap();
+
+        let mut toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+        toolchain.toolchain_base_path = Some("/nonexistent/rv32".to_string());
+        toolchain.cb_extra_paths = vec![format!("{}/", extra.display())];
+        assert_eq!(toolchain.ar_path(), format!("{}/riscv32-elf-ar", extra.display()));
+        // 都找不到时仍返回 bin 目录下的路径
+        assert_eq!(toolchain.compiler_path(), "/nonexistent/rv32/bin/riscv32-elf-gcc");
+        std::fs::remove_dir_all(&extra).unwrap();
     }
 }
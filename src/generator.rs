@@ -1,46 +1,48 @@
+use crate::clang_target::ClangTarget;
 use crate::config::ToolchainConfig;
 use crate::debug_println;
-use crate::models::CompileCommand;
+use crate::models::{CompileCommand, CompileCommandsFormat, SourceLanguage, TargetType};
 use crate::parser::ProjectInfo;
-use crate::utils::{escape_ninja_path, get_clean_absolute_path, get_short_path, quote_if_needed};
+use crate::toolchains::Tool;
+use crate::platform::{
+    archive_command, command_sequence, env_assignment, to_host_separators, touch_command, with_environment, with_trailing_separator,
+};
+use crate::utils::{escape_ninja_path, get_clean_absolute_path, get_short_path};
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Component, Path, PathBuf};
 use std::fs;
 
-/// 辅助函数：将Path转换为Windows风格的字符串路径（使用反斜杠作为分隔符）
+/// 辅助函数：将Path转换为宿主平台风格的字符串路径（Windows 反斜杠，POSIX 正斜杠）
 fn normalize_path(path: &Path) -> String {
     let path_str = path.to_string_lossy().into_owned();
 
     // 1. 处理 \\?\UNC\Server\Share 类型的路径 -> \\Server\Share
-    if path_str.starts_with("\\\\?\\UNC\\") {
+    if let Some(raw_unc) = path_str.strip_prefix("\\\\?\\UNC\\") {
         // 截取掉 \\?\UNC\ (8个字符)，剩下 Server\Share...
-        let raw_unc = &path_str[8..];
-        return format!("\\\\{}", raw_unc.replace("/", "\\"));
+        return format!("\\\\{}", to_host_separators(raw_unc));
     }
 
     // 2. 处理 \\?\C:\Path 类型的路径 -> C:\Path
     // 移除可能存在的 UNC 路径前缀 (\\?\) 以避免某些工具兼容性问题
-    let clean_path = if path_str.starts_with("\\\\?\\") {
-        &path_str[4..]
-    } else {
-        &path_str
-    };
+    let clean_path = path_str.strip_prefix("\\\\?\\").unwrap_or(&path_str);
 
-    // 确保在所有平台上都使用Windows风格的路径分隔符
-    clean_path.replace("/", "\\")
+    // 统一为宿主平台的路径分隔符
+    to_host_separators(clean_path)
 }
 
 /// 新增辅助函数：直接标准化字符串类型的路径
 fn normalize_str(s: &str) -> String {
-    s.replace("/", "\\")
+    to_host_separators(s)
 }
 
 /// 新增核心函数：清洗构建参数（Flags）
 /// 这是一个系统性的解决方案，用于处理 "-Ipath/to", "-Lpath/to", "path/to/file.a" 等各种情况
 fn sanitize_flag(flag: &str) -> String {
-    // 简单直接的策略：在 Windows 环境生成场景下，将所有正斜杠替换为反斜杠
+    // 简单直接的策略：将所有路径分隔符统一为宿主平台风格
+    // (Windows 下正斜杠转反斜杠，POSIX 下反斜杠转正斜杠)
     // 这对于 GCC/Clang 的路径参数（-I, -L, -o, 纯文件名）都是安全的
     // 同时也统一了视觉风格
-    flag.replace("/", "\\")
+    to_host_separators(flag)
 }
 
 
@@ -56,17 +58,63 @@ fn find_common_ancestor(paths: &[PathBuf]) -> PathBuf {
     ancestor
 }
 
+/// .clangd 中 CompileFlags 的 Add/Remove 选项
+/// Add 的每一项是一组必须放在一起的参数 (如 `-target <triple>`)，便于在工作区的项目之间比较
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ClangdCompileFlags {
+    add: Vec<Vec<String>>,
+    remove: Vec<String>,
+}
+
+impl ClangdCompileFlags {
+    /// 按 CompileFlags 的子项格式 (两格缩进) 写出 Add/Remove，空的部分不输出
+    fn write_entries(&self, content: &mut String) {
+        if !self.add.is_empty() {
+            content.push_str("  Add:\n");
+            for flag in self.add.iter().flatten() {
+                let formatted_flag = format!("    - {}\n", flag.replace('\\', "\\\\"));
+                debug_println!("[DEBUG generator] Added flag: {}", formatted_flag.trim());
+                content.push_str(&formatted_flag);
+            }
+        }
+        if !self.remove.is_empty() {
+            content.push_str("  Remove:\n");
+            for flag in &self.remove {
+                debug_println!("[DEBUG generator] Added remove flag: {}", flag);
+                content.push_str(&format!("    - {}\n", flag));
+            }
+        }
+    }
+}
+
 /// 生成clangd配置文件内容
-/// 使用第一个target的配置（通常是Debug）
+/// 使用指定target的配置，如果不指定则使用第一个target（通常是Debug）
 pub fn generate_clangd_config(
     project_info: &ProjectInfo,
     toolchain: &ToolchainConfig,
     _no_header_insertion: bool,
+    target: Option<&crate::models::BuildTarget>,
 ) -> Result<String, Box<dyn std::error::Error>> {
     debug_println!("[DEBUG generator] Starting to generate .clangd config...");
 
-    // 使用第一个target，如果没有则使用默认值
-    let target = project_info.targets.first();
+    // 注意：.clangd 是 YAML，但 clangd 接受这种简写格式
+    debug_println!("[DEBUG generator] Formatting clangd config content...");
+    let mut content = "CompileFlags:\n".to_string();
+    clangd_compile_flags(project_info, toolchain, target).write_entries(&mut content);
+
+    debug_println!("[DEBUG generator] Successfully generated .clangd config content");
+    Ok(content)
+}
+
+/// 计算项目 (所选 target) 在 .clangd 中的 CompileFlags 选项
+fn clangd_compile_flags(
+    project_info: &ProjectInfo,
+    toolchain: &ToolchainConfig,
+    target: Option<&crate::models::BuildTarget>,
+) -> ClangdCompileFlags {
+
+    // 获取要使用的target，如果没有则只使用全局配置
+    let target = target.or_else(|| project_info.targets.first());
 
     debug_println!("[DEBUG generator] Getting include paths from toolchain...");
     let includes = toolchain
@@ -79,15 +127,36 @@ pub fn generate_clangd_config(
         })
         .collect::<Vec<_>>();
 
+    // 展开编译选项中的 Code::Blocks 宏 ($(PROJECT_DIR)、$(#global)、环境变量等)
+    let macros = match target {
+        Some(target) => project_info.target_macros(target),
+        None => project_info.macros.clone(),
+    };
+    // 指定 target 时按其 OptionsRelation 组合项目和 target 选项；default.conf 中编译器的全局选项位于最前面
+    let (project_cflags, include_dirs) = match target {
+        Some(t) => {
+            let flags = project_info.target_flags(t);
+            (flags.cflags, flags.include_dirs)
+        }
+        None => (project_info.global_cflags.clone(), Vec::new()),
+    };
+    let all_cflags = macros.expand_all(toolchain.cb_compiler_options.iter().chain(project_cflags.iter()));
+    let include_dirs = macros.expand_all(&include_dirs);
+
+    // 目标三元组：项目覆盖值优先，否则由工具链推断；-march/-mabi/-mcpu 转换为对应的 clang 选项
+    let triple = project_info.target_triple.clone().unwrap_or_else(|| toolchain.target_triple());
+    let clang_target = ClangTarget::new(&triple, target.map(|t| &t.march_info), &all_cflags);
+
     // 构建Add部分
     debug_println!("[DEBUG generator] Building Add flags section...");
-    let mut add_flags = vec!["-xc", "-target", "riscv32-unknown-elf"];
-    debug_println!("[DEBUG generator] Added base flags: -xc, -target, riscv32-unknown-elf");
+    // -xc 只写入 C 项目的片段 (见 generate_clangd_fragment)，公共部分由工作区内所有项目共享
+    let mut add_flags = vec![vec!["-target".to_string(), clang_target.triple.clone()]];
+    debug_println!("[DEBUG generator] Added base flags: -target, {}", clang_target.triple);
 
     // 添加include路径
     debug_println!("[DEBUG generator] Adding include paths to Add flags...");
-    for inc in &includes {
-        add_flags.push(&inc[..]);
+    for inc in includes {
+        add_flags.push(vec![inc]);
     }
 
     // 对 clangd 代码分析无用的编译选项（优化、debug、代码生成相关）
@@ -101,110 +170,85 @@ pub fn generate_clangd_config(
     .cloned()
     .collect();
 
-    // 添加全局编译选项（包括宏定义）
-    debug_println!("[DEBUG generator] Adding global_cflags to Add flags...");
-    for flag in &project_info.global_cflags {
-        // 跳过-march选项，因为我们会单独处理
-        if flag.starts_with("-march=") {
-            debug_println!("[DEBUG generator] Skipping march flag from global_cflags: {}", flag);
+    // 添加编译选项（包括宏定义）
+    debug_println!("[DEBUG generator] Adding compiler flags to Add flags...");
+    for flag in &all_cflags {
+        // 跳过-march/-mabi/-mcpu选项，因为我们会单独处理
+        if is_clang_target_flag(flag) {
+            debug_println!("[DEBUG generator] Skipping target flag: {}", flag);
             continue;
         }
         // 跳过对 clangd 无用的编译选项
         if skip_add_flags.contains(flag.as_str()) {
-            debug_println!("[DEBUG generator] Skipping unnecessary flag from global_cflags: {}", flag);
+            debug_println!("[DEBUG generator] Skipping unnecessary flag: {}", flag);
             continue;
         }
-        debug_println!("[DEBUG generator] Added global flag: {}", flag);
-        add_flags.push(flag.as_str());
+        debug_println!("[DEBUG generator] Added flag: {}", flag);
+        add_flags.push(vec![flag.clone()]);
     }
 
-    // 添加target特定的编译选项
-    if let Some(target) = target {
-        debug_println!("[DEBUG generator] Adding target-specific flags for '{}'...", target.name);
-        for flag in &target.cflags {
-            if flag.starts_with("-march=") {
-                debug_println!("[DEBUG generator] Skipping march flag from target: {}", flag);
-                continue;
-            }
-            if skip_add_flags.contains(flag.as_str()) {
-                debug_println!("[DEBUG generator] Skipping unnecessary flag from target: {}", flag);
-                continue;
-            }
-            debug_println!("[DEBUG generator] Added target flag: {}", flag);
-            add_flags.push(flag.as_str());
-        }
-
-        // 添加target特定的include路径
-        for inc in &target.include_dirs {
-            add_flags.push(inc.as_str());
-        }
+    // 添加项目和target的include路径
+    for inc in include_dirs {
+        add_flags.push(vec![inc]);
+    }
 
-        debug_println!("[DEBUG generator] Checking for custom march extensions...");
-        if target.march_info.has_custom_extension {
-            debug_println!("[DEBUG generator] Found custom extension, adding base march...");
-            if let Some(ref base_march) = target.march_info.base_march {
-                debug_println!("[DEBUG generator] Adding base march: {}", base_march);
-                add_flags.push(base_march.as_str());
-            }
-        } else if !target.march_info.full_march.is_empty() {
-            debug_println!("[DEBUG generator] Adding full march: {}", target.march_info.full_march);
-            add_flags.push(&target.march_info.full_march[..]);
-        }
+    // 添加转换后的 -march/-mabi/-mcpu (RISC-V 自定义扩展已去掉)
+    for flag in &clang_target.add {
+        debug_println!("[DEBUG generator] Adding target flag: {}", flag);
+        add_flags.push(vec![flag.clone()]);
     }
 
     // 构建Remove部分
     debug_println!("[DEBUG generator] Building Remove flags section...");
-    let mut remove_flags = Vec::new();
+    let mut remove_flags: Vec<String> = Vec::new();
 
-    // 如果有target且有-march指令，添加到Remove部分
-    if let Some(target) = target {
-        if !target.march_info.full_march.is_empty() {
-            debug_println!(
-                "[DEBUG generator] Adding full march to Remove: {}",
-                target.march_info.full_march
-            );
-            remove_flags.push(&target.march_info.full_march[..]);
-        }
+    // compile_commands.json 中的原始 -march (clang 可能不认识其中的自定义扩展)
+    for flag in &clang_target.remove {
+        debug_println!("[DEBUG generator] Adding to Remove: {}", flag);
+        remove_flags.push(flag.clone());
     }
     debug_println!("[DEBUG generator] Adding -mjump-tables-in-text to Remove");
-    remove_flags.push("-mjump-tables-in-text");
+    remove_flags.push("-mjump-tables-in-text".to_string());
 
-    // 注意：.clangd 是 YAML，但 clangd 接受这种简写格式
-    debug_println!("[DEBUG generator] Formatting clangd config content...");
-    let mut content = format!("CompileFlags:\n  Add:\n");
-
-    // 添加Add部分
-    debug_println!("[DEBUG generator] Adding Add flags to config...");
-    for flag in add_flags {
-        let formatted_flag = format!("    - {}\n", flag.replace('\\', "\\\\"));
-        debug_println!("[DEBUG generator] Added flag: {}", formatted_flag.trim());
-        content.push_str(&formatted_flag);
-    }
-
-    // 如果有Remove部分，添加
-    if !remove_flags.is_empty() {
-        debug_println!("[DEBUG generator] Adding Remove flags to config...");
-        content.push_str("  Remove:\n");
-        for flag in remove_flags {
-            debug_println!("[DEBUG generator] Added remove flag: {}", flag);
-            content.push_str(&format!("    - {}\n", flag));
-        }
-    }
+    ClangdCompileFlags { add: add_flags, remove: remove_flags }
+}
 
-    debug_println!("[DEBUG generator] Successfully generated .clangd config content");
-    Ok(content)
+/// 由 ClangTarget 统一输出的目标相关选项
+fn is_clang_target_flag(flag: &str) -> bool {
+    flag.starts_with("-march=") || flag.starts_with("-mabi=") || flag.starts_with("-mcpu=")
 }
 
 /// 包含 PathMatch 和 CompilationDatabase
-/// 使用第一个target的object_output作为数据库路径
+/// 使用指定target的object_output作为数据库路径，如果不指定则使用第一个target
 pub fn generate_clangd_fragment(
     project_info: &ProjectInfo,
     project_dir: &Path,     // CBP 目录
     workspace_root: &Path,  // .clangd 根目录
     _db_path: &Path,        // compile_commands.json 目录 (现在使用target特定的路径)
+    target: Option<&crate::models::BuildTarget>,
 ) -> Result<(String, String), Box<dyn std::error::Error>> {
     debug_println!("[DEBUG generator] Generating clangd fragment...");
 
+    // CompilationDatabase 使用所选target的object_output，转为正斜杠
+    let db_path = if let Some(target) = target.or_else(|| project_info.targets.first()) {
+        let obj_output_path = project_dir.join(&target.object_output);
+        obj_output_path.to_string_lossy().replace("\\", "/")
+    } else {
+        // 如果没有target，使用当前目录
+        ".".to_string()
+    };
+    Ok(clangd_fragment(project_info, project_dir, workspace_root, &db_path, &ClangdCompileFlags::default()))
+}
+
+/// 生成项目的 .clangd 片段：PathMatch 匹配项目源文件，CompileFlags 指向 db_path，
+/// 并添加项目专属的选项 (纯 C 项目追加 -xc)
+fn clangd_fragment(
+    project_info: &ProjectInfo,
+    project_dir: &Path,
+    workspace_root: &Path,
+    db_path: &str,
+    flags: &ClangdCompileFlags,
+) -> (String, String) {
     // 1. 计算 PathMatch (基于源文件共同祖先)
     let abs_source_paths: Vec<PathBuf> = project_info.source_files.iter()
         .map(|src| get_clean_absolute_path(project_dir, Path::new(&src.filename)))
@@ -233,22 +277,20 @@ pub fn generate_clangd_fragment(
         format!("{}/.*", rel_str)
     };
 
-    // 2. CompilationDatabase (使用第一个target的object_output，转为正斜杠)
-    let db_path = if let Some(target) = project_info.targets.first() {
-        let obj_output_path = project_dir.join(&target.object_output);
-        obj_output_path.to_string_lossy().replace("\\", "/")
-    } else {
-        // 如果没有target，使用当前目录
-        ".".to_string()
-    };
-
-    // 3. 生成片段内容
-    let fragment = format!(
-        "If:\n  PathMatch: {}\n\nCompileFlags:\n  CompilationDatabase: {}",
+    // 2. 生成片段内容
+    let mut fragment = format!(
+        "If:\n  PathMatch: {}\n\nCompileFlags:\n  CompilationDatabase: {}\n",
         path_match, db_path
     );
 
-    Ok((path_match, fragment))
+    // 只含 C 源文件的项目强制按 C 解析头文件；含 C++ 的项目由 clangd 按扩展名判断语言
+    let mut flags = flags.clone();
+    if project_info.is_c_only() {
+        flags.add.push(vec!["-xc".to_string()]);
+    }
+    flags.write_entries(&mut fragment);
+
+    (path_match, fragment.trim_end().to_string())
 }
 
 /// 生成编译命令列表
@@ -258,6 +300,17 @@ pub fn generate_compile_commands(
     project_dir: &Path,
     toolchain: &ToolchainConfig,
     target: Option<&crate::models::BuildTarget>,
+) -> Vec<CompileCommand> {
+    generate_compile_commands_with_format(project_info, project_dir, toolchain, target, CompileCommandsFormat::Command)
+}
+
+/// 生成编译命令列表，并指定 compile_commands.json 中使用 `command` 字符串还是 `arguments` 数组
+pub fn generate_compile_commands_with_format(
+    project_info: &crate::parser::ProjectInfo,
+    project_dir: &Path,
+    toolchain: &ToolchainConfig,
+    target: Option<&crate::models::BuildTarget>,
+    format: CompileCommandsFormat,
 ) -> Vec<CompileCommand> {
     debug_println!("[DEBUG generator] Starting to generate compile commands...");
     debug_println!(
@@ -271,6 +324,12 @@ pub fn generate_compile_commands(
 
     debug_println!("[DEBUG generator] Generating compile commands for target: {}", target.name);
 
+    // 仅执行命令的 target 不编译任何文件
+    if target.target_type == TargetType::CommandsOnly {
+        debug_println!("[DEBUG generator] Target {} is commands only, no compile commands", target.name);
+        return Vec::new();
+    }
+
     // 使用工具链中的编译器路径，但如果路径不存在，使用占位符
     debug_println!("[DEBUG generator] Getting compiler path from toolchain...");
     let compiler_path = toolchain.compiler_path();
@@ -309,16 +368,23 @@ pub fn generate_compile_commands(
             "[WARNING generator] Compiler path {} does not exist. Using placeholder.",
             compiler_path
         );
-        "riscv32-elf-gcc".to_string()
+        toolchain.tool_name(Tool::Cc)
     };
     debug_println!("[DEBUG generator] Final compiler path to use: {}", compiler);
 
+    // C++ / 汇编文件使用各自的编译器驱动
+    let languages = project_info.source_files.iter()
+        .filter(|src| src.belongs_to(&target.name) && src.compile)
+        .map(|src| src.language())
+        .collect();
+    let drivers = LanguageDrivers::resolve(toolchain, &compiler, &languages);
+
     debug_println!("[DEBUG generator] Building base compiler flags with absolute paths...");
 
     // 定义一个闭包来处理 flag，如果是 -I 开头，则转为绝对路径
     let resolve_include_path = |flag: &str| -> String {
-        if flag.starts_with("-I") {
-            let path_part = &flag[2..]; // 去掉 -I 前缀
+        if let Some(path_part) = flag.strip_prefix("-I") {
+            // 去掉 -I 前缀
             let path = Path::new(path_part);
 
             // 计算绝对路径时，优先使用逻辑路径计算，避免 canonicalize 展开网络驱动器
@@ -338,35 +404,48 @@ pub fn generate_compile_commands(
     };
 
     let mut base_flags: Vec<String> = Vec::new();
+    // 先展开 Code::Blocks 宏，再解析其中的 include 路径
+    let macros = project_info.target_macros(target);
 
-    // 1. 处理 global_cflags (防止里面包含手动写的 -I)
-    for flag in &project_info.global_cflags {
-        base_flags.push(resolve_include_path(flag));
-    }
+    // 项目和 target 的选项按 target 的 OptionsRelation 组合，与 Code::Blocks 一样选项在前、include 路径在后
+    let flags = project_info.target_flags(target);
 
-    // 2. 处理全局 include_dirs (parser 中已经加上了 -I 前缀)
-    for flag in &project_info.global_include_dirs {
-        base_flags.push(resolve_include_path(flag));
+    // 1. 处理 default.conf 的全局编译选项和组合后的编译选项 (防止里面包含手动写的 -I)
+    for flag in macros.expand_all(toolchain.cb_compiler_options.iter().chain(flags.cflags.iter())) {
+        base_flags.push(resolve_include_path(&flag));
     }
 
-    // 3. 处理target特定的编译选项
-    for flag in &target.cflags {
-        base_flags.push(resolve_include_path(flag));
-    }
-
-    // 4. 处理target特定的include路径
-    for flag in &target.include_dirs {
-        base_flags.push(resolve_include_path(flag));
+    // 2. 处理组合后的 include_dirs (parser 中已经加上了 -I 前缀)
+    for flag in macros.expand_all(&flags.include_dirs) {
+        base_flags.push(resolve_include_path(&flag));
     }
 
     debug_println!("[DEBUG generator] Base flags: {:?}", base_flags);
 
+    // 源文件的共同祖先目录，用于计算与 build.ninja 一致的对象文件路径
+    let abs_source_paths: Vec<PathBuf> = project_info.source_files.iter()
+        .map(|src| get_clean_absolute_path(project_dir, Path::new(&src.filename)))
+        .collect();
+    let common_ancestor = find_common_ancestor(&abs_source_paths);
+
     debug_println!(
         "[DEBUG generator] Starting to process {} source files...",
         project_info.source_files.len()
     );
     let mut compile_commands = Vec::new();
     for (index, src) in project_info.source_files.iter().enumerate() {
+        // 不属于当前 target 的文件不生成编译命令
+        if !src.belongs_to(&target.name) {
+            debug_println!(
+                "[DEBUG generator] Skipping file {}/{}: {} (not in target {})",
+                index + 1,
+                project_info.source_files.len(),
+                src.filename,
+                target.name
+            );
+            continue;
+        }
+
         // 只有compile为true的文件才生成编译命令
         if !src.compile {
             debug_println!(
@@ -415,22 +494,30 @@ pub fn generate_compile_commands(
         };
 
         debug_println!("[DEBUG generator] Building command parts for file...");
-        let mut cmd = vec![&compiler[..], "-c"];
-        cmd.extend(base_flags.iter().map(|s| s.as_str()));
+        let mut arguments = vec![drivers.get(src.language()).to_string(), "-c".to_string()];
+        arguments.extend(base_flags.iter().cloned());
         // 命令中使用处理过的路径（可能是短路径，也可能是绝对长路径）
-        cmd.push(&src_path_for_cmd);
-
-        // 对每个命令参数进行引号处理，防止空格导致命令解析错误
-        let quoted_cmd = cmd.iter().map(|&arg| quote_if_needed(arg)).collect::<Vec<_>>();
-        let command_str = quoted_cmd.join(" ");
-        debug_println!("[DEBUG generator] Generated command: {}", command_str);
+        arguments.push(src_path_for_cmd);
+        debug_println!("[DEBUG generator] Generated arguments: {:?}", arguments);
+
+        // 对象文件路径与 build.ninja 中的一致 (object_output + 相对共同祖先的目录结构)
+        let obj_path = object_file_path(
+            &target.object_output,
+            &abs_path_buf,
+            &common_ancestor,
+            Path::new(&src.filename),
+            &toolchain.def.switches.object_extension,
+        );
+        let output = normalize_path(&get_clean_absolute_path(project_dir, &obj_path));
 
         debug_println!("[DEBUG generator] Creating compile command entry...");
-        compile_commands.push(CompileCommand {
-            directory: project_dir.to_string_lossy().into_owned(),
-            command: command_str,
-            file: abs_path_str, // 这里使用绝对路径
-        });
+        compile_commands.push(CompileCommand::new(
+            project_dir.to_string_lossy().into_owned(),
+            arguments,
+            abs_path_str, // 这里使用绝对路径
+            Some(output),
+            format,
+        ));
     }
 
     debug_println!(
@@ -478,8 +565,7 @@ fn compute_relative_path(target: &Path, base: &Path) -> Option<PathBuf> {
 
 fn resolve_library_path(lib: &str, lib_dirs: &[String], root_dir: &Path) -> Option<String> {
     // 1. 处理库名称
-    let (search_names, is_flag) = if lib.starts_with("-l") {
-        let name = &lib[2..];
+    let (search_names, is_flag) = if let Some(name) = lib.strip_prefix("-l") {
         // 如果是 -lfoo，则搜索 libfoo.a
         (vec![format!("lib{}.a", name)], true)
     } else {
@@ -517,11 +603,7 @@ fn resolve_library_path(lib: &str, lib_dirs: &[String], root_dir: &Path) -> Opti
     // 3. 在库目录中搜索
     for dir_flag in lib_dirs {
         // 移除 -L 前缀
-        let raw_dir = if dir_flag.starts_with("-L") {
-            &dir_flag[2..]
-        } else {
-            dir_flag
-        };
+        let raw_dir = dir_flag.strip_prefix("-L").unwrap_or(dir_flag);
 
         let dir_path = Path::new(raw_dir);
         
@@ -545,36 +627,277 @@ fn resolve_library_path(lib: &str, lib_dirs: &[String], root_dir: &Path) -> Opti
     None
 }
 
+/// 生成 ninja 构建文件时各 target 共享的上下文
+struct NinjaContext<'a> {
+    project_info: &'a ProjectInfo,
+    project_dir: &'a Path,
+    toolchain: &'a ToolchainConfig,
+    compiler: String,
+    /// 各语言源文件使用的编译器驱动
+    drivers: LanguageDrivers,
+    linker: String,
+    /// 所有源文件的逻辑绝对路径（与 project_info.source_files 一一对应）
+    abs_source_paths: Vec<PathBuf>,
+    /// 源文件的共同祖先目录，用于在 object_output 下保持目录结构
+    common_ancestor: PathBuf,
+    /// 共享的 link/ar 规则只输出一次
+    link_rule_emitted: bool,
+    ar_rule_emitted: bool,
+    /// 每次构建都会执行的 phony 目标 (预构建步骤、after="always" 的后构建步骤依赖它)，只输出一次
+    always_emitted: bool,
+    /// 项目级预构建步骤的 stamp (转义后)，所有 target 的编译边以 order-only 依赖它
+    project_prebuild_stamp: Option<String>,
+    /// 已生成构建边的输出文件，避免多个 target 重复生成同一输出
+    emitted_outputs: HashSet<String>,
+    /// 工作区模式：项目目录相对于工作区根目录的路径，所有项目内路径都加上该前缀
+    path_prefix: Option<PathBuf>,
+    /// 工作区模式：工作区根目录 (顶层 build.ninja 所在目录)
+    workspace_root: Option<&'a Path>,
+    /// 工作区模式：当前项目依赖的其他项目 (<Depends>)
+    dependencies: &'a [NinjaDependency<'a>],
+}
+
+impl NinjaContext<'_> {
+    /// 工作区模式下为项目内的相对路径加上项目目录前缀，否则原样返回
+    fn prefixed(&self, path: &Path) -> PathBuf {
+        match &self.path_prefix {
+            Some(prefix) if !path.is_absolute() => prefix.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// 为编译/链接选项中项目内的相对路径加上项目目录前缀
+    /// 路径参数可以与选项连写 (-Tlink.ld)、在同一选项中以空格分隔 (-T link.ld) 或作为下一个选项给出
+    fn prefixed_flags(&self, flags: impl IntoIterator<Item = String>) -> Vec<String> {
+        if self.path_prefix.is_none() {
+            return flags.into_iter().collect();
+        }
+        let mut expect_path = false;
+        flags
+            .into_iter()
+            .map(|flag| {
+                flag.split(' ')
+                    .map(|word| self.prefixed_flag_word(word, &mut expect_path))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    fn prefixed_flag_word(&self, word: &str, expect_path: &mut bool) -> String {
+        if word.is_empty() {
+            return String::new();
+        }
+        if std::mem::take(expect_path) {
+            return self.prefixed_str(word);
+        }
+        if let Some(args) = word.strip_prefix("-Wl,") {
+            return format!("-Wl,{}", self.prefixed_linker_args(args));
+        }
+        if SEPARATE_PATH_OPTIONS.contains(&word) {
+            *expect_path = true;
+            return word.to_string();
+        }
+        for opt in JOINED_PATH_OPTIONS {
+            if let Some(path) = word.strip_prefix(opt)
+                && !is_section_address(opt, path)
+            {
+                return format!("{}{}", opt, self.prefixed_str(path));
+            }
+        }
+        word.to_string()
+    }
+
+    /// -Wl, 传给链接器的参数 (以逗号分隔)：-T/-L/-Map/--script 的路径参数
+    fn prefixed_linker_args(&self, args: &str) -> String {
+        let mut expect_path = false;
+        args.split(',')
+            .map(|arg| {
+                if std::mem::take(&mut expect_path) {
+                    return self.prefixed_str(arg);
+                }
+                if matches!(arg, "-T" | "-L" | "-Map" | "--Map" | "--script" | "--library-path") {
+                    expect_path = true;
+                    return arg.to_string();
+                }
+                for opt in ["--script=", "-Map=", "--Map=", "--library-path=", "-T", "-L"] {
+                    if let Some(path) = arg.strip_prefix(opt)
+                        && !is_section_address(opt, path)
+                    {
+                        return format!("{}{}", opt, self.prefixed_str(path));
+                    }
+                }
+                arg.to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn prefixed_str(&self, path: &str) -> String {
+        if path.is_empty() {
+            return String::new();
+        }
+        normalize_path(&self.prefixed(Path::new(path)))
+    }
+}
+
+/// 以下一个参数作为路径的选项 (-T link.ld、-include cfg.h)
+const SEPARATE_PATH_OPTIONS: &[&str] =
+    &["-I", "-L", "-T", "-include", "-imacros", "-isystem", "-iquote", "-idirafter"];
+/// 可以与路径连写的选项 (-Iinclude、-Tlink.ld)
+const JOINED_PATH_OPTIONS: &[&str] = &["-I", "-L", "-T", "-isystem", "-iquote", "-idirafter"];
+
+/// -Ttext=0x0、-Tbss 之类的段地址选项不带路径
+fn is_section_address(opt: &str, arg: &str) -> bool {
+    opt == "-T" && (arg.contains('=') || matches!(arg, "text" | "data" | "bss" | "text-segment"))
+}
+
+/// 工作区中当前项目所依赖的项目，其产物作为链接步骤的依赖
+pub struct NinjaDependency<'a> {
+    pub project_info: &'a ProjectInfo,
+    pub project_dir: &'a Path,
+}
+
+impl NinjaDependency<'_> {
+    /// 依赖项目中与 target_name 同名的 target 的产物及其类型，没有同名 target 时使用第一个 target
+    /// 仅执行命令的 target 没有产物，返回其 phony 目标
+    fn output_for(&self, target_name: &str, workspace_root: &Path) -> Option<(String, TargetType)> {
+        let target = self.project_info.targets.iter()
+            .find(|t| t.name == target_name)
+            .or_else(|| self.project_info.targets.first())?;
+        let prefix = workspace_path_prefix(self.project_dir, workspace_root);
+        let output = if target.target_type == TargetType::CommandsOnly {
+            normalize_path(&prefix.join(&target.name))
+        } else {
+            normalize_path(&prefix.join(target.artifact_output()))
+        };
+        Some((output, target.target_type))
+    }
+}
+
+/// 工作区中的单个项目，用于生成顶层 build.ninja
+pub struct WorkspaceNinjaProject<'a> {
+    pub project_dir: &'a Path,
+    /// 项目 ninja 文件的文件名 (位于 project_dir 中)
+    pub subninja_file: &'a str,
+    /// 默认构建的 target 名称
+    pub default_target: &'a str,
+}
+
+/// 计算项目目录相对于工作区根目录的路径 (ninja 在工作区根目录执行)
+fn workspace_path_prefix(project_dir: &Path, workspace_root: &Path) -> PathBuf {
+    compute_relative_path(project_dir, workspace_root).unwrap_or_else(|| project_dir.to_path_buf())
+}
+
 /// 生成ninja构建文件内容
-/// 使用第一个target的配置（通常是Debug）
+/// 每个 target 拥有独立的对象目录和以 target 名称命名的 phony 目标 (如 `ninja Debug`)，
+/// cc/link/ar 规则在各 target 之间共享，默认构建第一个 target
 pub fn generate_ninja_build(
     project_info: &ProjectInfo,
     project_dir: &Path,
     toolchain: &ToolchainConfig,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    debug_println!("[DEBUG generator] Starting to generate ninja build file...");
+    build_ninja_content(project_info, project_dir, toolchain, None, &[])
+}
 
-    // 获取要使用的target
-    let target = project_info.targets.first()
-        .expect("No target available");
+/// 生成供工作区顶层 build.ninja 通过 subninja 引用的项目 ninja 文件
+/// ninja 始终在工作区根目录执行命令，因此项目内的路径都加上项目目录相对于工作区根目录的前缀，
+/// 并为链接步骤添加对依赖项目产物的依赖；不输出 default，由顶层文件决定
+pub fn generate_subninja_build(
+    project_info: &ProjectInfo,
+    project_dir: &Path,
+    toolchain: &ToolchainConfig,
+    workspace_root: &Path,
+    dependencies: &[NinjaDependency],
+) -> Result<String, Box<dyn std::error::Error>> {
+    build_ninja_content(project_info, project_dir, toolchain, Some(workspace_root), dependencies)
+}
 
-    debug_println!("[DEBUG generator] Generating ninja build for target: {}", target.name);
+/// 计算源文件的对象文件路径：object_output + 相对于共同祖先的路径 + 对象文件扩展名 (编译器的 objectExtension，默认 o)
+/// 如果 strip_prefix 失败（例如跨盘符），回退到使用文件名
+fn object_file_path(
+    object_output: &str,
+    abs_src: &Path,
+    common_ancestor: &Path,
+    src_path: &Path,
+    object_extension: &str,
+) -> PathBuf {
+    let relative_structure: &Path = abs_src.strip_prefix(common_ancestor)
+        .unwrap_or_else(|_| match src_path.file_name() {
+            Some(name) => Path::new(name),
+            None => src_path,
+        });
+    Path::new(object_output)
+        .join(relative_structure)
+        .with_extension(object_extension)
+}
 
-    // 使用工具链中的编译器路径
-    let compiler_path = toolchain.compiler_path();
-    let compiler_exists = Path::new(&compiler_path).exists();
-    let compiler = if compiler_exists {
-        match get_short_path(&compiler_path) {
+/// 按源文件语言解析出的编译器驱动
+struct LanguageDrivers {
+    c: String,
+    cpp: String,
+    asm: String,
+}
+
+impl LanguageDrivers {
+    /// 只解析实际用到的语言，避免为不存在的文件类型输出路径警告；
+    /// 与 C 编译器路径相同的驱动直接复用已解析的 C 编译器
+    fn resolve(toolchain: &ToolchainConfig, c_compiler: &str, languages: &HashSet<SourceLanguage>) -> Self {
+        let resolve = |language: SourceLanguage, placeholder: &str, kind: &str| {
+            let driver_path = toolchain.driver_path(language);
+            if !languages.contains(&language) || driver_path == toolchain.compiler_path() {
+                c_compiler.to_string()
+            } else {
+                resolve_tool_path(&driver_path, placeholder, kind)
+            }
+        };
+        LanguageDrivers {
+            c: c_compiler.to_string(),
+            cpp: resolve(SourceLanguage::Cpp, &toolchain.tool_name(Tool::Cxx), "C++ compiler"),
+            asm: resolve(SourceLanguage::Asm, &toolchain.tool_name(Tool::As), "Assembler"),
+        }
+    }
+
+    fn get(&self, language: SourceLanguage) -> &str {
+        match language {
+            SourceLanguage::C => &self.c,
+            SourceLanguage::Cpp => &self.cpp,
+            SourceLanguage::Asm => &self.asm,
+        }
+    }
+}
+
+/// 解析工具路径：存在时使用短路径名，不存在时使用占位符 (kind 用于警告信息，如 "Compiler")
+fn resolve_tool_path(tool_path: &str, placeholder: &str, kind: &str) -> String {
+    if Path::new(tool_path).exists() {
+        match get_short_path(tool_path) {
             Ok(short_path) => short_path,
             Err(e) => {
-                println!("[WARNING generator] Failed to get short path for compiler: {}. Using original path.", e);
-                compiler_path.clone()
+                println!("[WARNING generator] Failed to get short path for {}: {}. Using original path.", kind.to_lowercase(), e);
+                tool_path.to_string()
             }
         }
     } else {
-        println!("[WARNING generator] Compiler path {} does not exist. Using placeholder.", compiler_path);
-        "riscv32-elf-gcc".to_string()
-    };
+        println!("[WARNING generator] {} path {} does not exist. Using placeholder.", kind, tool_path);
+        placeholder.to_string()
+    }
+}
+
+fn build_ninja_content(
+    project_info: &ProjectInfo,
+    project_dir: &Path,
+    toolchain: &ToolchainConfig,
+    workspace_root: Option<&Path>,
+    dependencies: &[NinjaDependency],
+) -> Result<String, Box<dyn std::error::Error>> {
+    debug_println!("[DEBUG generator] Starting to generate ninja build file...");
+
+    if project_info.targets.is_empty() {
+        return Err("No target available".into());
+    }
+
+    // 使用工具链中的编译器路径
+    let compiler = resolve_tool_path(&toolchain.compiler_path(), &toolchain.tool_name(Tool::Cc), "Compiler");
 
     // 获取链接器路径
     let linker_path = toolchain.linker_path(&project_info.linker_type);
@@ -590,58 +913,16 @@ pub fn generate_ninja_build(
     } else {
         println!("[WARNING generator] Linker path {} does not exist. Using placeholder.", linker_path);
         if project_info.linker_type == "ld" {
-            "riscv32-elf-ld".to_string()
+            toolchain.tool_name(Tool::Ld)
         } else {
-            "riscv32-elf-gcc".to_string()
+            toolchain.tool_name(Tool::Link)
         }
     };
 
-    // 提前计算常用的标准化路径，避免重复计算
-    let clean_obj_dir = {
-        let mut path = normalize_path(Path::new(&target.object_output));
-        // 确保路径以分隔符结尾，以便与文件名正确连接
-        if !path.ends_with('\\') && !path.ends_with('/') {
-            path.push('\\');
-        }
-        path
-    };
-
-    // [FIX] 新增：计算 TARGET_OUTPUT_DIR (基于 output 文件的父目录)
-    let output_path = Path::new(&target.output);
-    let target_output_dir = output_path.parent().unwrap_or(Path::new("."));
-    let clean_target_output_dir = {
-        let mut path = normalize_path(target_output_dir);
-        // 确保路径以分隔符结尾，以便与文件名正确连接
-        if !path.ends_with('\\') && !path.ends_with('/') {
-            path.push('\\');
-        }
-        path
-    };
-
-    // 构建基础编译器标志
-    let mut base_flags: Vec<String> = Vec::new();
-    for flag in &project_info.global_cflags {
-        base_flags.push(sanitize_flag(flag)); // 确保全局CFLAGS里的路径也被转换
-    }
-    for include in &project_info.global_include_dirs {
-        // -I 选项
-        let clean_path = normalize_path(Path::new(include));
-        base_flags.push(format!("{}", clean_path));
-    }
-
-    // 添加target特定的编译选项和include路径
-    for flag in &target.cflags {
-        base_flags.push(sanitize_flag(flag));
-    }
-    for include in &target.include_dirs {
-        let clean_path = normalize_path(Path::new(include));
-        base_flags.push(format!("{}", clean_path));
-    }
-
     // 规则部分
     let mut ninja_content = String::new();
     ninja_content.push_str("# Generated by cbp2clangd\n");
-    ninja_content.push_str("\n");
+    ninja_content.push('\n');
     
     // Rule: CC
     ninja_content.push_str("rule cc\n");
@@ -651,9 +932,29 @@ pub fn generate_ninja_build(
     ));
     ninja_content.push_str("  depfile = $out.d\n");
     ninja_content.push_str("  deps = gcc\n");
-    ninja_content.push_str("\n");
+    ninja_content.push('\n');
+
+    // Rule: CXX / AS (仅在存在使用默认构建命令的 C++ / 汇编文件时输出)
+    let compiled_sources: Vec<_> = project_info.source_files.iter().filter(|src| src.compile).collect();
+    let languages = compiled_sources.iter().map(|src| src.language()).collect();
+    let drivers = LanguageDrivers::resolve(toolchain, &compiler, &languages);
+    for language in [SourceLanguage::Cpp, SourceLanguage::Asm] {
+        let needs_rule = compiled_sources.iter()
+            .any(|src| src.language() == language && src.build_command.is_none());
+        if !needs_rule {
+            continue;
+        }
+        ninja_content.push_str(&format!("rule {}\n", language.ninja_rule()));
+        ninja_content.push_str(&format!(
+            "  command = {} $flags -MMD -MF $out.d -c $in -o $out\n",
+            drivers.get(language)
+        ));
+        ninja_content.push_str("  depfile = $out.d\n");
+        ninja_content.push_str("  deps = gcc\n");
+        ninja_content.push('\n');
+    }
 
-    // === 新增逻辑：计算所有源文件的共同祖先目录，以保持目录结构 ===
+    // === 计算所有源文件的共同祖先目录，以保持目录结构 ===
     // 1. 获取所有源文件的逻辑绝对路径
     let abs_source_paths: Vec<PathBuf> = project_info.source_files.iter()
         .map(|src| get_clean_absolute_path(project_dir, Path::new(&src.filename)))
@@ -663,31 +964,176 @@ pub fn generate_ninja_build(
     let common_ancestor = find_common_ancestor(&abs_source_paths);
     debug_println!("[DEBUG generator] Common source ancestor: {}", common_ancestor.display());
 
+    let mut ctx = NinjaContext {
+        project_info,
+        project_dir,
+        toolchain,
+        compiler,
+        drivers,
+        linker,
+        abs_source_paths,
+        common_ancestor,
+        link_rule_emitted: false,
+        ar_rule_emitted: false,
+        always_emitted: false,
+        project_prebuild_stamp: None,
+        emitted_outputs: HashSet::new(),
+        path_prefix: workspace_root.map(|root| workspace_path_prefix(project_dir, root)),
+        workspace_root,
+        dependencies,
+    };
+
+    // 项目级预构建步骤 (ExtraCommands before)：每次构建都执行一次，所有 target 的编译边以 order-only 依赖其 stamp
+    // 声明的生成文件只能由一条构建边输出，因此只生成一次，在默认 (第一个) target 的上下文中展开
+    // <cbp2clangd><prebuild_output file="..."/> 声明的生成文件作为该构建边的输出
+    if !project_info.prebuild_commands.is_empty() {
+        let first_target = &project_info.targets[0];
+        let stamp_name = format!("{}.project_prebuild.stamp", ninja_rule_suffix(&project_info.project_name));
+        let step = CommandStep {
+            rule_name: "project_prebuild".to_string(),
+            stamp_file: Path::new(&first_target.object_output).join(stamp_name),
+            outputs: &project_info.prebuild_outputs,
+            // 只执行一次，使用默认 (第一个) target 的环境和宏
+            environment: project_info.target_environment(first_target),
+            commands: project_info.command_macros(first_target, toolchain).expand_all(&project_info.prebuild_commands),
+            inputs: Vec::new(),
+            order_only: Vec::new(),
+            always: true,
+        };
+        ctx.project_prebuild_stamp = Some(write_command_step(&mut ninja_content, &mut ctx, step));
+    }
+
+    let mut target_outputs: Vec<(&str, String)> = Vec::new();
+    for target in &project_info.targets {
+        let outputs = write_ninja_target(&mut ninja_content, &mut ctx, target);
+        target_outputs.push((&target.name, outputs.join(" ")));
+    }
+    let default_output = target_outputs.first().map(|(_, output)| output.clone());
+
+    // 虚拟 target：以别名命名的 phony 目标，依赖各成员 target 的产物 (`ninja All`)
+    for alias in &project_info.virtual_targets {
+        let member_outputs: Vec<&str> = alias.targets.iter()
+            .filter_map(|member| target_outputs.iter().find(|(name, _)| name == member))
+            .map(|(_, output)| output.as_str())
+            .collect();
+        ninja_content.push_str(&format!("# Virtual target: {}\n", alias.name));
+        ninja_content.push_str(&format!(
+            "build {}: phony {}\n\n",
+            escape_ninja_path(&normalize_path(&ctx.prefixed(Path::new(&alias.name)))),
+            member_outputs.join(" ")
+        ));
+    }
+
+    // 工作区模式下由顶层 build.ninja 决定默认目标
+    if let Some(default_output) = default_output
+        && ctx.path_prefix.is_none()
+    {
+        ninja_content.push_str(&format!("default {}\n", default_output));
+    }
+
+    debug_println!("[DEBUG generator] Successfully generated ninja build file content");
+    Ok(ninja_content)
+}
+
+/// 输出单个 target 的全部构建边，返回转义后的最终产物路径 (及后构建步骤的 stamp)
+fn write_ninja_target(
+    ninja_content: &mut String,
+    ctx: &mut NinjaContext,
+    target: &crate::models::BuildTarget,
+) -> Vec<String> {
+    let project_info = ctx.project_info;
+    let project_dir = ctx.project_dir;
+    let compiler = ctx.compiler.clone();
+
+    debug_println!("[DEBUG generator] Generating ninja build for target: {}", target.name);
+    ninja_content.push_str(&format!("# Target: {}\n", target.name));
+    ninja_content.push('\n');
+
+    // 预构建步骤：项目级步骤之后执行 target 级步骤 (ExtraCommands before)，每次构建都执行，
+    // 编译和链接边以 order-only 依赖其 stamp
+    let mut prebuild_stamps: Vec<String> = ctx.project_prebuild_stamp.iter().cloned().collect();
+    if !target.extra_commands.before.is_empty() {
+        let step = CommandStep {
+            order_only: prebuild_stamps.clone(),
+            always: true,
+            ..CommandStep::for_target(project_info, ctx.toolchain, target, "prebuild", &target.extra_commands.before)
+        };
+        prebuild_stamps.push(write_command_step(ninja_content, ctx, step));
+    }
+
+    // 仅执行命令的 target (type 4) 没有编译和链接步骤，每次构建都依次执行预/后构建命令，
+    // 只生成以 target 名称命名的 phony 目标
+    let phony_name = escape_ninja_path(&normalize_path(&ctx.prefixed(Path::new(&target.name))));
+    if target.target_type == TargetType::CommandsOnly {
+        debug_println!("[DEBUG generator] Target {} is commands only, skipping compile and link", target.name);
+        let mut stamps = prebuild_stamps;
+        write_postbuild_steps(ninja_content, ctx, target, &mut stamps, true);
+        let deps: String = stamps.iter().map(|stamp| format!(" {}", stamp)).collect();
+        ninja_content.push_str(&format!("build {}: phony{}\n\n", phony_name, deps));
+        return vec![phony_name];
+    }
+
+    // 提前计算常用的标准化路径，避免重复计算
+    // 确保路径以分隔符结尾，以便与文件名正确连接
+    let clean_obj_dir = with_trailing_separator(normalize_path(&ctx.prefixed(Path::new(&target.object_output))));
+
+    // [FIX] 新增：计算 TARGET_OUTPUT_DIR (基于 output 文件的父目录)
+    let output_path = Path::new(&target.output);
+    let target_output_dir = output_path.parent().unwrap_or(Path::new("."));
+    let clean_target_output_dir = with_trailing_separator(normalize_path(&ctx.prefixed(target_output_dir)));
+
+    // 宏上下文：$(TARGET_*) 路径使用 ninja 中实际使用的 (工作区模式下带前缀的) 路径
+    let mut macros = project_info.target_macros(target);
+    macros.set("TARGET_OBJECT_DIR", &clean_obj_dir);
+    macros.set("TARGET_OUTPUT_DIR", &clean_target_output_dir);
+    macros.set(
+        "TARGET_OUTPUT_FILE",
+        &normalize_path(&ctx.prefixed(Path::new(&target.artifact_output()))),
+    );
+
+    // 选项中的 $(TARGET_*) 使用项目内的路径，展开后与选项中的其他路径一起由 prefixed_flags 加上前缀
+    let flag_macros = project_info.target_macros(target);
+
+    // 构建基础编译器标志：项目和 target 的选项按 target 的 OptionsRelation 组合 (与 compile_commands.json 一致)
+    let flags = project_info.target_flags(target);
+    let cflags = flag_macros.expand_all(ctx.toolchain.cb_compiler_options.iter().chain(flags.cflags.iter()));
+    // 确保全局CFLAGS里的路径也被转换
+    let mut base_flags = ctx.prefixed_flags(cflags.iter().map(|flag| sanitize_flag(flag)));
+    // 合并后的 include 路径 (-I 选项)
+    let all_includes = flag_macros.expand_all(&flags.include_dirs);
+    let clean_includes = ctx.prefixed_flags(all_includes.iter().map(|p| normalize_path(Path::new(p))));
+    base_flags.extend(clean_includes.iter().cloned());
+
     // 构建对象文件列表
     let mut regular_obj_files = Vec::new();
-    // 保存源文件路径和对应的对象文件路径的映射
-    let mut src_to_obj_map = Vec::new();
+    // 普通源文件的编译边，与特殊文件的编译边一起按 weight 排序后输出
+    let mut regular_edges = Vec::new();
+
+    // 构建命令模板中的 $options/$includes
+    let options_str = base_flags.join(" ");
+    let clean_includes = clean_includes.join(" ");
 
     // 处理普通源文件
     // 同时遍历 SourceFileInfo 和 计算出的绝对路径
-    for (src_info, abs_path) in project_info.source_files.iter().zip(abs_source_paths.iter()) {
+    for (src_info, abs_path) in project_info.source_files.iter().zip(ctx.abs_source_paths.iter()) {
+        // 只处理属于当前 target 的源文件
+        if !src_info.belongs_to(&target.name) {
+            continue;
+        }
+
         let src_path = Path::new(&src_info.filename);
 
-        // 3. 计算相对于共同祖先的路径
-        // 如果 strip_prefix 失败（例如跨盘符），回退到使用文件名
-        let relative_structure: &Path = abs_path.strip_prefix(&common_ancestor)
-            .unwrap_or_else(|_| match src_path.file_name() {
-                Some(name) => Path::new(name),
-                None => src_path,
-            });
-
-        // 4. 构建最终的对象文件路径：object_output + 相对结构 + .o
-        let obj_path_buf = Path::new(&target.object_output)
-            .join(relative_structure)
-            .with_extension("o");
-
-        let obj_name = normalize_path(&obj_path_buf);
-        let clean_src = normalize_path(src_path);
+        // 构建最终的对象文件路径：object_output + 相对共同祖先的结构 + 对象文件扩展名
+        let obj_path_buf = object_file_path(
+            &target.object_output,
+            abs_path,
+            &ctx.common_ancestor,
+            src_path,
+            &ctx.toolchain.def.switches.object_extension,
+        );
+
+        let obj_name = normalize_path(&ctx.prefixed(&obj_path_buf));
+        let clean_src = normalize_path(&ctx.prefixed(src_path));
         
         // 对 Ninja 构建文件中的路径进行转义，处理空格和冒号
         let escaped_obj_name = escape_ninja_path(&obj_name);
@@ -698,44 +1144,72 @@ pub fn generate_ninja_build(
             regular_obj_files.push(escaped_obj_name.clone());
         }
         
-        // 如果需要编译，添加到编译规则映射中
-        if src_info.compile {
-            src_to_obj_map.push((escaped_src, escaped_obj_name));
+        // 如果需要编译，添加到编译边列表中
+        if !src_info.compile {
+            continue;
         }
+
+        // 按语言 (扩展名或 compilerVar) 选择编译器驱动和共享规则
+        let language = src_info.language();
+        let driver = ctx.drivers.get(language).to_string();
+        let rule = match &src_info.build_command {
+            Some(build_command) => {
+                // 自定义构建命令替代 cc 规则，$object 为默认的对象文件路径
+                let mut command_macros = macros.clone();
+                command_macros.set("compiler", &driver);
+                command_macros.set("options", &options_str);
+                command_macros.set("includes", &clean_includes);
+                command_macros.set("file", &clean_src);
+                command_macros.set("object", &obj_name);
+                EdgeRule::Custom {
+                    name: unit_rule_name("custom", &src_info.filename, &target.name),
+                    command: command_macros.expand(build_command),
+                    compiler: driver,
+                }
+            }
+            None => EdgeRule::Builtin(language.ninja_rule()),
+        };
+        regular_edges.push(CompileEdge {
+            weight: src_info.weight,
+            output: escaped_obj_name,
+            input: escaped_src,
+            rule,
+            special: false,
+        });
     }
-    // === 结束新增逻辑 ===
 
     // 处理特殊文件
+    // 自定义构建命令在 <Environment> 变量设置后执行 ($ 需要按 ninja 语法转义)
+    let ninja_environment: Vec<(String, String)> = project_info
+        .target_environment(target)
+        .into_iter()
+        .map(|(name, value)| (name, value.replace('$', "$$")))
+        .collect();
     let mut special_output_files = Vec::new();
+    let mut compile_edges = Vec::new();
     for special_file in &project_info.special_files {
+        // 只处理属于当前 target 的特殊文件
+        if !special_file.belongs_to(&target.name) {
+            debug_println!("[DEBUG generator] Skipping special file {} (not in target {})", special_file.filename, target.name);
+            continue;
+        }
+
         // 只有compile为true的特殊文件才处理
         if !special_file.compile {
             debug_println!("[DEBUG generator] Skipping special file {} (compile is false)", special_file.filename);
             continue;
         }
 
-        // 解析构建命令中的目标文件名
-        let mut processed_cmd = special_file.build_command.clone();
-
         // 路径标准化处理
-        let clean_file_path = normalize_path(Path::new(&special_file.filename));
-        // 合并全局include和target特定include
-        let all_includes: Vec<_> = project_info.global_include_dirs.iter()
-            .chain(target.include_dirs.iter())
-            .collect();
-        let clean_includes = all_includes.iter()
-            .map(|p| normalize_path(Path::new(p)))
-            .collect::<Vec<_>>()
-            .join(" ");
+        let clean_file_path = normalize_path(&ctx.prefixed(Path::new(&special_file.filename)));
 
-        // 替换变量
-        processed_cmd = processed_cmd.replace("$compiler", &compiler);
-        processed_cmd = processed_cmd.replace("$options", &base_flags.join(" "));
-        processed_cmd = processed_cmd.replace("$includes", &clean_includes);
-        processed_cmd = processed_cmd.replace("$file", &clean_file_path);
-        // [FIX] 区分 OBJECT_DIR 和 OUTPUT_DIR
-        processed_cmd = processed_cmd.replace("$(TARGET_OBJECT_DIR)", &clean_obj_dir);
-        processed_cmd = processed_cmd.replace("$(TARGET_OUTPUT_DIR)", &clean_target_output_dir);
+        // 展开构建命令模板变量 ($compiler/$options/$includes/$file) 和 Code::Blocks 宏
+        let mut command_macros = macros.clone();
+        command_macros.set("compiler", &compiler);
+        command_macros.set("options", &options_str);
+        command_macros.set("includes", &clean_includes);
+        command_macros.set("file", &clean_file_path);
+        let processed_cmd = command_macros.expand(&special_file.build_command);
 
         // 提取输出文件名
         let output_file = if let Some(output_pos) = processed_cmd.find("-o ") {
@@ -750,14 +1224,14 @@ pub fn generate_ninja_build(
             // 对特殊文件也应用类似的逻辑，尝试保持结构，但因为它是自定义命令，
             // 通常由用户指定输出位置。这里只做简单的 fallback
             let abs_path = get_clean_absolute_path(project_dir, Path::new(&special_file.filename));
-            let relative_structure: &Path = abs_path.strip_prefix(&common_ancestor)
+            let relative_structure: &Path = abs_path.strip_prefix(&ctx.common_ancestor)
                 .unwrap_or_else(|_| Path::new(&special_file.filename));
             
             let full_path = Path::new(&target.object_output)
                 .join(relative_structure)
-                .with_extension("o");
+                .with_extension(&ctx.toolchain.def.switches.object_extension);
                     
-            normalize_path(&full_path)
+            normalize_path(&ctx.prefixed(&full_path))
         };
 
         // 对 Ninja 构建文件中的路径进行转义，处理空格和冒号
@@ -768,95 +1242,150 @@ pub fn generate_ninja_build(
         // 特殊文件的输出文件必须作为依赖，否则编译命令不会执行
         special_output_files.push(escaped_output_file.clone());
 
-            let rule_name = format!(
-                "special_{}",
-                special_file
-                    .filename
-                    .replace(".", "_")
-                    .replace("/", "_")
-                    .replace("\\", "_")
-                    .replace(":", "_")
-            );
-
-            // 如果构建命令为空，生成一个创建空.o文件的命令
-            let final_command = if processed_cmd.is_empty() {
-                // 在Windows上创建空文件的命令：先创建目录，再创建文件
-                let output_path = Path::new(&output_file);
-                let output_dir = output_path.parent().unwrap_or(Path::new("."));
-                let output_dir_str = normalize_path(output_dir);
+        // 如果构建命令为空，生成一个创建空.o文件的命令
+        let final_command = if processed_cmd.is_empty() {
+            // 创建空文件的命令：先创建目录，再创建文件
+            let output_path = Path::new(&output_file);
+            let output_dir = output_path.parent().unwrap_or(Path::new("."));
+            let output_dir_str = normalize_path(output_dir);
 
-                // 使用mkdir命令创建目录（如果不存在），然后创建空文件
-                // 注意：ninja在Windows上使用cmd.exe执行命令，所以需要用cmd /c来运行多个命令
-                format!("cmd /c (mkdir {} >nul 2>&1) & (type nul > {})", output_dir_str, output_file)
-            } else {
-                processed_cmd
-            };
+            touch_command(&output_dir_str, &output_file)
+        } else {
+            processed_cmd
+        };
 
-            // 检查是否是编译命令（包含编译器），如果是则添加依赖跟踪
-            let is_compile_command = final_command.contains(&compiler) ||
-                                   final_command.contains("gcc") ||
-                                   final_command.contains("g++") ||
-                                   final_command.contains("clang") ||
-                                   final_command.contains("clang++");
+        compile_edges.push(CompileEdge {
+            weight: special_file.weight,
+            output: escaped_output_file,
+            input: escaped_clean_file_path,
+            // 规则名包含 target 名称，因为命令中的 $(TARGET_*) 宏按 target 展开
+            rule: EdgeRule::Custom {
+                name: unit_rule_name("special", &special_file.filename, &target.name),
+                command: final_command,
+                compiler: compiler.clone(),
+            },
+            special: true,
+        });
+    }
+    compile_edges.extend(regular_edges);
+
+    // 按 weight 排序 (稳定排序，相同权重保持原有顺序)；存在多个权重时，
+    // 每条编译边以 order-only 依赖前一个权重组的全部输出，使权重小的 Unit 先构建
+    compile_edges.sort_by_key(|edge| edge.weight);
+    let mut weight_groups: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for edge in &compile_edges {
+        weight_groups.entry(edge.weight).or_default().push(edge.output.clone());
+    }
 
-            ninja_content.push_str(&format!("rule {}\n", rule_name));
+    ninja_content.reserve(compile_edges.len() * 100); // Pre-allocate space for build rules
+    for edge in compile_edges {
+        // 多个 target 共用 object_output 时同一对象文件只能有一条构建边，沿用先出现的 target
+        if !ctx.emitted_outputs.insert(edge.output.clone()) {
+            if edge.special {
+                // 不依赖 target 宏的命令在各 target 下产生相同的输出，只需生成一次构建边
+                debug_println!("[DEBUG generator] Special file output {} already generated by another target", edge.output);
+            } else {
+                println!(
+                    "[WARNING generator] {} is shared with another target (same object_output), target '{}' reuses it",
+                    edge.output, target.name
+                );
+            }
+            continue;
+        }
 
-            if is_compile_command {
-                // 为编译命令添加依赖跟踪
-                // 需要确保 -MMD -MF $out.d 是在编译器之后添加的，但要在输入文件之前
-                let modified_command = insert_dependency_flags(final_command, &compiler);
+        let mut order_only_deps: Vec<&str> = weight_groups
+            .range(..edge.weight)
+            .next_back()
+            .map(|(_, outputs)| outputs.iter().map(String::as_str).collect())
+            .unwrap_or_default();
+        order_only_deps.extend(prebuild_stamps.iter().map(String::as_str));
+        let order_only = if order_only_deps.is_empty() {
+            String::new()
+        } else {
+            format!(" || {}", order_only_deps.join(" "))
+        };
 
-                ninja_content.push_str(&format!("  command = {}\n", modified_command));
-                ninja_content.push_str("  depfile = $out.d\n");
-                ninja_content.push_str("  deps = gcc\n");
-            } else {
-                // 非编译命令，不添加依赖跟踪
-                ninja_content.push_str(&format!("  command = {}\n", final_command));
+        match edge.rule {
+            EdgeRule::Builtin(rule) => {
+                ninja_content.push_str(&format!("build {}: {} {}{}\n", edge.output, rule, edge.input, order_only));
+                ninja_content.push_str(&format!("  flags = {}\n", options_str));
             }
-            ninja_content.push_str("\n");
+            EdgeRule::Custom { name, command, compiler } => {
+                ninja_content.push_str(&format!("rule {}\n", name));
+
+                // 检查是否是编译命令（包含编译器），如果是则添加依赖跟踪
+                let is_compile_command = command.contains(&compiler) ||
+                                       command.contains("gcc") ||
+                                       command.contains("g++") ||
+                                       command.contains("clang") ||
+                                       command.contains("clang++");
+
+                if is_compile_command {
+                    // 为编译命令添加依赖跟踪
+                    // 需要确保 -MMD -MF $out.d 是在编译器之后添加的，但要在输入文件之前
+                    let modified_command = insert_dependency_flags(command, &compiler);
+
+                    ninja_content.push_str(&format!("  command = {}\n", with_environment(&ninja_environment, &modified_command)));
+                    ninja_content.push_str("  depfile = $out.d\n");
+                    ninja_content.push_str("  deps = gcc\n");
+                } else {
+                    // 非编译命令，不添加依赖跟踪
+                    ninja_content.push_str(&format!("  command = {}\n", with_environment(&ninja_environment, &command)));
+                }
+                ninja_content.push('\n');
 
-            ninja_content.push_str(&format!(
-                "build {}: {} {}\n",
-                escaped_output_file, rule_name, escaped_clean_file_path
-            ));
-            ninja_content.push_str("\n");
+                ninja_content.push_str(&format!("build {}: {} {}{}\n", edge.output, name, edge.input, order_only));
+            }
         }
-
-    // 构建部分 - 普通源文件
-    ninja_content.reserve(src_to_obj_map.len() * 100); // Pre-allocate space for build rules
-    for (src, obj) in src_to_obj_map {
-        ninja_content.push_str(&format!("build {}: cc {}\n", obj, src));
-        ninja_content.push_str(&format!("  flags = {}\n", base_flags.join(" ")));
-        ninja_content.push_str("\n");
+        ninja_content.push('\n');
     }
 
     // 链接目标
-    let mut target_name = normalize_path(Path::new(&target.output));
-
-    // 检查是否为静态库目标（.a结尾）
-    let is_static_lib = target_name.ends_with(".a");
-
-    // 处理静态库文件名
-    if is_static_lib {
-        let target_path = Path::new(&target_name);
-        if let Some(file_name) = target_path.file_name() {
-            let file_name_str = file_name.to_string_lossy().to_string();
-            if !file_name_str.starts_with("lib") {
-                let dir = target_path.parent().unwrap_or_else(|| Path::new("."));
-                let stem = file_name_str.strip_suffix(".a").unwrap_or(&file_name_str);
-                let new_file_name = format!("lib{}.a", stem);
-                target_name = normalize_path(&dir.join(new_file_name));
+    let target_name = normalize_path(&ctx.prefixed(Path::new(&target.artifact_output())));
+
+    // 静态库目标 (type 2) 使用 ar 归档
+    let is_static_lib = target.target_type == TargetType::StaticLib;
+
+    // 工作区依赖项目的产物：库作为隐式依赖 (变化时重新链接)，其余只保证构建顺序
+    let mut dependency_libs = Vec::new();
+    let mut dependency_order = Vec::new();
+    for dependency in ctx.dependencies {
+        let root = ctx.workspace_root.unwrap_or(ctx.project_dir);
+        if let Some((output, dep_type)) = dependency.output_for(&target.name, root) {
+            if dep_type.is_library() && !is_static_lib {
+                dependency_libs.push(escape_ninja_path(&output));
+            } else {
+                dependency_order.push(escape_ninja_path(&output));
             }
         }
     }
+    dependency_order.extend(prebuild_stamps.iter().cloned());
+    let order_only_str = if dependency_order.is_empty() {
+        String::new()
+    } else {
+        format!(" || {}", dependency_order.join(" "))
+    };
     
     // 对目标文件名进行 Ninja 路径转义处理
     let escaped_target_name = escape_ninja_path(&target_name);
 
+    if !ctx.emitted_outputs.insert(escaped_target_name.clone()) {
+        println!(
+            "[WARNING generator] Output {} of target '{}' is already produced by another target",
+            escaped_target_name, target.name
+        );
+        ninja_content.push_str(&format!(
+            "build {}: phony {}\n\n",
+            phony_name,
+            escaped_target_name
+        ));
+        return vec![escaped_target_name];
+    }
+
     // 生成主目标的构建规则
     if is_static_lib {
         // 静态库目标
-        let ar_path = toolchain.ar_path();
+        let ar_path = ctx.toolchain.ar_path();
         let ar_exists = Path::new(&ar_path).exists();
         let ar = if ar_exists {
             match get_short_path(&ar_path) {
@@ -868,15 +1397,15 @@ pub fn generate_ninja_build(
             }
         } else {
             println!("[WARNING generator] Ar path {} does not exist. Using placeholder.", ar_path);
-            "riscv32-elf-ar".to_string()
+            ctx.toolchain.tool_name(Tool::Ar)
         };
 
-        ninja_content.push_str("rule ar\n");
-        ninja_content.push_str(&format!(
-            "  command = cmd /c (if exist \"$out\" del /q \"$out\") & {} crs $out $in\n",
-            ar
-        ));
-        ninja_content.push_str("\n");
+        if !ctx.ar_rule_emitted {
+            ninja_content.push_str("rule ar\n");
+            ninja_content.push_str(&format!("  command = {}\n", archive_command(&ar)));
+            ninja_content.push('\n');
+            ctx.ar_rule_emitted = true;
+        }
 
         // 特殊文件的输出作为隐式依赖，放到 | 符号后面
         let deps_str = if special_output_files.is_empty() {
@@ -886,15 +1415,19 @@ pub fn generate_ninja_build(
         };
 
         ninja_content.push_str(&format!(
-            "build {}: ar {}{}\n",
+            "build {}: ar {}{}{}\n",
             escaped_target_name,
             regular_obj_files.join(" "),
-            deps_str
+            deps_str,
+            order_only_str
         ));
 
     } else {
-        // 可执行文件目标
+        // 可执行文件 / 动态库目标
         let mut pre_link_flags: Vec<String> = Vec::new();
+        if target.target_type == TargetType::DynamicLib {
+            pre_link_flags.push("-shared".to_string());
+        }
         let mut lib_flags: Vec<String> = Vec::new();
         
         // 1. 解析库依赖
@@ -902,77 +1435,62 @@ pub fn generate_ninja_build(
 
         debug_println!("[DEBUG generator] Resolving library dependencies...");
 
-        // 合并全局链接库和target特定的链接库
-        let all_libs: Vec<_> = project_info.global_linker_libs.iter()
-            .chain(target.linker_libs.iter())
-            .collect();
-        let all_lib_dirs: Vec<String> = project_info.global_linker_lib_dirs.iter()
-            .chain(target.linker_lib_dirs.iter())
-            .cloned()
-            .collect();
+        // 组合后的链接库 (全局 + target特定)
+        let all_libs = flag_macros.expand_all(&flags.linker_libs);
+        // default.conf 中编译器的库目录排在项目库目录之后
+        let cb_lib_dirs: Vec<String> = ctx.toolchain.cb_library_dirs.iter().map(|dir| format!("-L{}", dir)).collect();
+        let all_lib_dirs = flag_macros.expand_all(flags.lib_dirs.iter().chain(cb_lib_dirs.iter()));
 
         for lib in &all_libs {
             // 在这里应用 sanitize_flag
             // 这样无论是 "-lmath", "libs/libmath.a", 还是 "../libs/libfoo.a"
             // 都会变成 Windows 风格 (../libs/libfoo.a -> ..\libs\libfoo.a)
-            lib_flags.push(sanitize_flag(lib));
+            let lib_flag = sanitize_flag(lib);
+            if lib_flag.starts_with('-') {
+                lib_flags.push(lib_flag);
+            } else {
+                // 直接给出的库文件路径
+                lib_flags.push(normalize_path(&ctx.prefixed(Path::new(&lib_flag))));
+            }
 
             // 依赖解析逻辑（用于 ninja 的 implicit deps）
             if let Some(resolved_path) = resolve_library_path(lib, &all_lib_dirs, project_dir) {
                 debug_println!("[DEBUG generator] Resolved library {} to {}", lib, resolved_path);
-                resolved_lib_dependencies.push(resolved_path);
+                resolved_lib_dependencies.push(normalize_path(&ctx.prefixed(Path::new(&resolved_path))));
             } else {
                 debug_println!("[DEBUG generator] Could not resolve library path for {}", lib);
             }
         }
 
-        // 添加链接器选项 (全局 + target特定)
-        for opt in &project_info.global_linker_options {
-            let replaced_opt = opt.replace("$(TARGET_OBJECT_DIR)", &clean_obj_dir);
-            let replaced_opt = replaced_opt.replace("$(TARGET_OUTPUT_DIR)", &clean_target_output_dir);
-            pre_link_flags.push(sanitize_flag(&replaced_opt));
-        }
-        for opt in &target.linker_options {
-            let replaced_opt = opt.replace("$(TARGET_OBJECT_DIR)", &clean_obj_dir);
-            let replaced_opt = replaced_opt.replace("$(TARGET_OUTPUT_DIR)", &clean_target_output_dir);
-            // Linker options 可能包含 -Map=output/path.map 之类的，需要转换路径分隔符
-            pre_link_flags.push(sanitize_flag(&replaced_opt));
-        }
+        // 添加链接器选项 (default.conf 的全局链接选项 + 按 OptionsRelation 组合的项目和 target 选项)
+        // Linker options 可能包含 -Map=$(TARGET_OUTPUT_DIR)app.map 之类的，展开宏后需要转换路径分隔符
+        let linker_options = ctx.toolchain.cb_linker_options.iter().chain(flags.linker_options.iter());
+        // 工作区模式下 -T link.ld、-Wl,-Map=out.map 等项目内的相对路径需要加上项目目录前缀
+        let linker_options = flag_macros.expand_all(linker_options);
+        pre_link_flags.extend(ctx.prefixed_flags(linker_options.iter().map(|opt| sanitize_flag(opt))));
         // 添加链接库目录 (全局 + target特定)
-        for lib_dir in &project_info.global_linker_lib_dirs {
-            // 统一处理 -L 标志
-            if lib_dir.starts_with("-L") {
-                let path_part = &lib_dir[2..];
-                // normalize_str 替换斜杠
-                let clean_part = normalize_str(path_part);
-                pre_link_flags.push(format!("-L{}", clean_part));
-            } else {
-                pre_link_flags.push(normalize_str(lib_dir));
-            }
-        }
-        for lib_dir in &target.linker_lib_dirs {
-            // 统一处理 -L 标志
-            if lib_dir.starts_with("-L") {
-                let path_part = &lib_dir[2..];
-                // normalize_str 替换斜杠
-                let clean_part = normalize_str(path_part);
-                pre_link_flags.push(format!("-L{}", clean_part));
-            } else {
-                pre_link_flags.push(normalize_str(lib_dir));
-            }
-        }
+        // 与编译侧的 -I 一样，工作区模式下相对的 -L 目录需要加上项目目录前缀
+        pre_link_flags.extend(ctx.prefixed_flags(all_lib_dirs.iter().map(|dir| normalize_str(dir))));
 
-        ninja_content.push_str("rule link\n");
-        ninja_content.push_str(&format!(
-            "  command = {} $in $pre_flags $lib_flags -o $out\n",
-            linker
-        ));
-        ninja_content.push_str("\n");
+        if !ctx.link_rule_emitted {
+            ninja_content.push_str("rule link\n");
+            ninja_content.push_str(&format!(
+                "  command = {} $in $pre_flags $lib_flags -o $out\n",
+                ctx.linker
+            ));
+            ninja_content.push('\n');
+            ctx.link_rule_emitted = true;
+        }
 
         // 特殊文件的输出和库依赖都作为隐式依赖，放到 | 符号后面
         let mut implicit_deps = Vec::new();
         implicit_deps.extend(special_output_files.iter().cloned());
         implicit_deps.extend(resolved_lib_dependencies.iter().cloned());
+        for lib in dependency_libs {
+            if !implicit_deps.contains(&lib) {
+                implicit_deps.push(lib);
+            }
+        }
 
         let implicit_deps_str = if implicit_deps.is_empty() {
             String::new()
@@ -981,10 +1499,11 @@ pub fn generate_ninja_build(
         };
 
         ninja_content.push_str(&format!(
-            "build {}: link {}{}\n",
+            "build {}: link {}{}{}\n",
             escaped_target_name,
             regular_obj_files.join(" "),
-            implicit_deps_str
+            implicit_deps_str,
+            order_only_str
         ));
 
         if !pre_link_flags.is_empty() {
@@ -995,12 +1514,202 @@ pub fn generate_ninja_build(
             ninja_content.push_str(&format!("  lib_flags = {}\n", lib_flags.join(" ")));
         }
     }
-    ninja_content.push_str("\n");
+    ninja_content.push('\n');
 
-    ninja_content.push_str(&format!("default {}\n", escaped_target_name));
+    // 后构建步骤：产物重新生成后执行，after="always" 时每次构建都执行
+    let mut outputs = vec![escaped_target_name];
+    write_postbuild_steps(ninja_content, ctx, target, &mut outputs, false);
 
-    debug_println!("[DEBUG generator] Successfully generated ninja build file content");
-    Ok(ninja_content)
+    // 以 target 名称命名的 phony 目标，便于 `ninja Debug` / `ninja Release`
+    // 工作区模式下加上项目目录前缀 (如 `ninja app/Debug`)，避免与其他项目冲突
+    ninja_content.push_str(&format!(
+        "build {}: phony {}\n",
+        phony_name,
+        outputs.join(" ")
+    ));
+    ninja_content.push('\n');
+
+    outputs
+}
+
+/// 执行一组 ExtraCommands 的构建步骤，命令成功后写入 stamp 文件
+struct CommandStep<'a> {
+    rule_name: String,
+    /// stamp 文件 (相对项目目录)
+    stamp_file: PathBuf,
+    /// 命令生成的其他文件 (相对项目目录)，作为构建边的输出
+    outputs: &'a [String],
+    environment: Vec<(String, String)>,
+    /// 宏已展开的命令
+    commands: Vec<String>,
+    /// 显式输入 (已转义)，比 stamp 新时重新执行
+    inputs: Vec<String>,
+    /// order-only 依赖 (已转义)
+    order_only: Vec<String>,
+    /// 依赖一个不存在的 phony 目标，每次构建都执行
+    always: bool,
+}
+
+impl<'a> CommandStep<'a> {
+    /// target 的构建步骤：stamp 为 `<object_output>/<target>.<kind>.stamp`，使用 target 的 <Environment> 变量，
+    /// 命令按该 target 的宏和构建所用的工具链 ($compiler) 展开 (与 Code::Blocks 一致，项目级命令同样按正在构建的 target 展开)
+    fn for_target(
+        project_info: &ProjectInfo,
+        toolchain: &ToolchainConfig,
+        target: &crate::models::BuildTarget,
+        kind: &str,
+        commands: &[String],
+    ) -> Self {
+        let suffix = ninja_rule_suffix(&target.name);
+        CommandStep {
+            rule_name: format!("{}_{}", kind, suffix),
+            stamp_file: Path::new(&target.object_output).join(format!("{}.{}.stamp", suffix, kind)),
+            outputs: &[],
+            environment: project_info.target_environment(target),
+            commands: project_info.command_macros(target, toolchain).expand_all(commands),
+            inputs: Vec::new(),
+            order_only: Vec::new(),
+            always: false,
+        }
+    }
+}
+
+/// 输出构建步骤的规则和构建边，返回转义后的 stamp 路径
+/// 命令在项目目录中执行 (与 Code::Blocks 一致)；声明了生成文件时使用 restat，文件内容未变时不触发重新编译
+fn write_command_step(ninja_content: &mut String, ctx: &mut NinjaContext, step: CommandStep) -> String {
+    let stamp = normalize_path(&ctx.prefixed(&step.stamp_file));
+    let escaped_stamp = escape_ninja_path(&stamp);
+    let mut outputs = vec![escaped_stamp.clone()];
+    for output in step.outputs {
+        outputs.push(escape_ninja_path(&normalize_path(&ctx.prefixed(Path::new(output)))));
+    }
+    ctx.emitted_outputs.extend(outputs.iter().cloned());
+
+    let always_name = escape_ninja_path(&normalize_path(&ctx.prefixed(Path::new("cbp2clangd_always"))));
+    if step.always && !ctx.always_emitted {
+        ninja_content.push_str(&format!("build {}: phony\n\n", always_name));
+        ctx.always_emitted = true;
+    }
+
+    // 工作区模式下 ninja 在工作区根目录运行，命令需要切换到项目目录
+    let dir = ctx.path_prefix.is_some().then(|| normalize_path(ctx.project_dir));
+    let command = command_sequence(&step.environment, dir.as_deref(), &step.commands, &stamp);
+    ninja_content.push_str(&format!("rule {}\n", step.rule_name));
+    ninja_content.push_str(&format!("  command = {}\n", command.replace('$', "$$")));
+    if !step.outputs.is_empty() {
+        ninja_content.push_str("  restat = 1\n");
+    }
+    ninja_content.push('\n');
+
+    let mut edge = format!("build {}: {}", outputs.join(" "), step.rule_name);
+    for input in &step.inputs {
+        edge.push_str(&format!(" {}", input));
+    }
+    if step.always {
+        edge.push_str(&format!(" | {}", always_name));
+    }
+    if !step.order_only.is_empty() {
+        edge.push_str(&format!(" || {}", step.order_only.join(" ")));
+    }
+    ninja_content.push_str(&edge);
+    ninja_content.push_str("\n\n");
+    escaped_stamp
+}
+
+/// 依次输出 target 级 (ExtraCommands after) 和项目级后构建步骤 (均按当前 target 展开宏)，每一步以 outputs 中已有的产物和 stamp 为输入，
+/// 并把自己的 stamp 追加到 outputs；always 为 true 或设置了 after="always" 时每次构建都执行
+fn write_postbuild_steps(
+    ninja_content: &mut String,
+    ctx: &mut NinjaContext,
+    target: &crate::models::BuildTarget,
+    outputs: &mut Vec<String>,
+    always: bool,
+) {
+    let project_info = ctx.project_info;
+    let steps = [
+        ("postbuild", &target.extra_commands.after, target.extra_commands.after_always),
+        ("project_postbuild", &project_info.postbuild_commands, project_info.postbuild_always),
+    ];
+    for (kind, commands, after_always) in steps {
+        if commands.is_empty() {
+            continue;
+        }
+        let step = CommandStep {
+            inputs: outputs.clone(),
+            always: always || after_always,
+            ..CommandStep::for_target(project_info, ctx.toolchain, target, kind, commands)
+        };
+        outputs.push(write_command_step(ninja_content, ctx, step));
+    }
+}
+
+/// 生成工作区顶层 build.ninja：通过 subninja 引用各项目的 ninja 文件，
+/// 默认构建每个项目的 `<项目目录>/<target>` phony 目标
+pub fn generate_workspace_ninja(
+    workspace_title: &str,
+    workspace_root: &Path,
+    projects: &[WorkspaceNinjaProject],
+) -> String {
+    let mut content = String::new();
+    content.push_str(&format!("# Generated by cbp2clangd (workspace: {})\n", workspace_title));
+    content.push('\n');
+
+    let mut default_outputs = Vec::new();
+    for project in projects {
+        let prefix = workspace_path_prefix(project.project_dir, workspace_root);
+        content.push_str(&format!(
+            "subninja {}\n",
+            escape_ninja_path(&normalize_path(&prefix.join(project.subninja_file)))
+        ));
+        default_outputs.push(escape_ninja_path(&normalize_path(&prefix.join(project.default_target))));
+    }
+
+    if !default_outputs.is_empty() {
+        content.push('\n');
+        content.push_str(&format!("default {}\n", default_outputs.join(" ")));
+    }
+
+    content
+}
+
+/// 单个编译单元的构建边 (普通源文件或特殊文件)，收集后按 weight 排序输出
+struct CompileEdge {
+    weight: u32,
+    output: String,
+    input: String,
+    rule: EdgeRule,
+    /// 特殊文件 (自定义构建命令的非 C 源文件)
+    special: bool,
+}
+
+/// 构建边使用的规则
+enum EdgeRule {
+    /// 共享的 cc / cxx 规则
+    Builtin(&'static str),
+    /// 为该文件单独生成的规则 (Unit 的自定义构建命令)
+    Custom { name: String, command: String, compiler: String },
+}
+
+/// 为单个文件的自定义规则生成规则名
+/// 规则名包含 target 名称，因为命令中的 $(TARGET_*) 宏按 target 展开
+fn unit_rule_name(kind: &str, filename: &str, target_name: &str) -> String {
+    format!(
+        "{}_{}_{}",
+        kind,
+        filename
+            .replace(".", "_")
+            .replace("/", "_")
+            .replace("\\", "_")
+            .replace(":", "_"),
+        ninja_rule_suffix(target_name)
+    )
+}
+
+/// 将 target 名称转换为可用于 ninja 规则名的后缀
+fn ninja_rule_suffix(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect()
 }
 
 /// 辅助函数：查找编译器在命令字符串中的位置
@@ -1010,10 +1719,10 @@ fn find_compiler_position(command: &str, compiler: &str) -> Option<usize> {
         return Some(pos);
     }
 
-    // 如果精确匹配失败，尝试匹配编译器名称（如 gcc, g++, clang 等）
+    // 如果精确匹配失败，尝试匹配编译器名称（如 gcc, g++, clang 等），包括去掉路径和工具前缀后的名称
     let compiler_variants = [
         "gcc", "g++", "clang", "clang++",
-        &compiler.replace("riscv32-elf-", "")
+        compiler.rsplit(['/', '\\', '-']).next().unwrap_or(compiler)
     ];
 
     for variant in &compiler_variants {
@@ -1212,7 +1921,40 @@ pub fn merge_clangd_config(existing_content: &str, new_compile_flags: &str) -> S
 }
 
 /// 生成构建脚本文件内容
+/// Windows 上生成 build.bat，其他平台生成 POSIX sh 脚本 (build.sh)
 pub fn generate_build_script(
+    project_info: &ProjectInfo,
+    toolchain: &ToolchainConfig,
+    project_dir: &Path,
+    ninja_path: Option<&str>,
+) -> String {
+    if cfg!(windows) {
+        generate_batch_script(project_info, toolchain, project_dir, ninja_path)
+    } else {
+        generate_shell_script(project_info, toolchain, project_dir, ninja_path)
+    }
+}
+
+/// 在构建脚本中导出 <Environment> 变量，使 ninja (及其中的预/后构建步骤) 看到与 IDE 中相同的环境
+/// 使用默认 (第一个) target 的环境，与项目级预构建步骤的宏展开保持一致
+fn write_environment(script_content: &mut String, project_info: &ProjectInfo, comment: &str) {
+    let environment = match project_info.targets.first() {
+        Some(target) => project_info.target_environment(target),
+        None => project_info.environment.clone(),
+    };
+    if environment.is_empty() {
+        return;
+    }
+    script_content.push_str(&format!("{} Set project environment\n", comment));
+    for (name, value) in &environment {
+        script_content.push_str(&env_assignment(name, value));
+        script_content.push('\n');
+    }
+    script_content.push('\n');
+}
+
+/// 生成 Windows 批处理构建脚本 (build.bat)
+fn generate_batch_script(
     project_info: &ProjectInfo,
     toolchain: &ToolchainConfig,
     _project_dir: &Path,
@@ -1226,26 +1968,15 @@ pub fn generate_build_script(
     let toolchain_bin = format!(r"{}\bin", toolchain.get_base_path());
     script_content.push_str("@echo off\n");
     script_content.push_str("rem Generated by cbp2clangd\n");
-    script_content.push_str("\n");
+    script_content.push('\n');
     script_content.push_str("cd /d \"%~dp0\"\n\n");
+    write_environment(&mut script_content, project_info, "rem");
     script_content.push_str("rem Set toolchain path\n");
     script_content.push_str(&format!("set PATH={};%PATH%\n", toolchain_bin));
 
-    script_content.push_str("\n");
+    script_content.push('\n');
 
-    // 2. 添加预构建命令
-    if !project_info.prebuild_commands.is_empty() {
-        script_content.push_str("rem Prebuild commands\n");
-        for cmd in &project_info.prebuild_commands {
-            script_content.push_str("pushd %~dp0\n");
-            let processed_cmd = cmd.replace("$(PROJECT_NAME)", &project_info.project_name);
-            script_content.push_str(&format!("call {}\n", processed_cmd));
-            script_content.push_str("popd\n");
-        }
-        script_content.push_str("\n");
-    }
-
-    // 3. 添加ninja构建命令
+    // 2. 添加ninja构建命令 (预/后构建命令由 build.ninja 中的构建步骤执行)
     script_content.push_str("rem Build project with ninja\n");
     if let Some(ninja_path) = ninja_path {
         script_content.push_str(&format!("{} -f build.ninja\n", ninja_path));
@@ -1253,29 +1984,53 @@ pub fn generate_build_script(
         script_content.push_str("ninja -f build.ninja\n");
     }
     script_content.push_str("if %errorlevel% neq 0 exit /b %errorlevel%\n");
-    script_content.push_str("\n");
-
-    // 4. 添加后构建命令
-    if !project_info.postbuild_commands.is_empty() {
-        script_content.push_str("rem Postbuild commands\n");
-        for cmd in &project_info.postbuild_commands {
-            script_content.push_str("pushd %~dp0\n");
-            let processed_cmd = cmd.replace("$(PROJECT_NAME)", &project_info.project_name);
-            script_content.push_str(&format!("call {}\n", processed_cmd));
-            script_content.push_str("popd\n");
-        }
-        script_content.push_str("\n");
-    }
+    script_content.push('\n');
 
-    // 5. 添加完成信息
+    // 3. 添加完成信息
     script_content.push_str("rem Build completed successfully\n");
     script_content.push_str("echo Build completed successfully\n");
-    script_content.push_str("\n");
+    script_content.push('\n');
 
     debug_println!("[DEBUG generator] Successfully generated build script content");
     script_content
 }
 
+/// 生成 POSIX sh 构建脚本 (build.sh)，结构与 build.bat 保持一致
+fn generate_shell_script(
+    project_info: &ProjectInfo,
+    toolchain: &ToolchainConfig,
+    _project_dir: &Path,
+    ninja_path: Option<&str>,
+) -> String {
+    debug_println!("[DEBUG generator] Starting to generate shell build script...");
+
+    let mut script_content = String::new();
+
+    // 1. 添加工具链路径到PATH环境变量
+    let toolchain_bin = format!("{}/bin", toolchain.get_base_path());
+    script_content.push_str("#!/bin/sh\n");
+    script_content.push_str("# Generated by cbp2clangd\n");
+    script_content.push('\n');
+    script_content.push_str("cd \"$(dirname \"$0\")\" || exit 1\n\n");
+    write_environment(&mut script_content, project_info, "#");
+    script_content.push_str("# Set toolchain path\n");
+    script_content.push_str(&format!("export PATH=\"{}:$PATH\"\n", toolchain_bin));
+
+    script_content.push('\n');
+
+    // 2. 添加ninja构建命令 (预/后构建命令由 build.ninja 中的构建步骤执行)
+    script_content.push_str("# Build project with ninja\n");
+    let ninja = ninja_path.unwrap_or("ninja");
+    script_content.push_str(&format!("{} -f build.ninja || exit $?\n", ninja));
+    script_content.push('\n');
+
+    // 3. 添加完成信息
+    script_content.push_str("echo Build completed successfully\n");
+
+    debug_println!("[DEBUG generator] Successfully generated shell build script content");
+    script_content
+}
+
 /// 合并多个 compile_commands.json 文件到第一个文件中
 /// 
 /// # 参数
@@ -1288,6 +2043,21 @@ pub fn generate_build_script(
 pub fn merge_compile_commands(
     json_paths: &[PathBuf],
     workspace_root: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let first_json_path = json_paths.first().ok_or("At least one JSON file is required")?;
+    merge_compile_commands_into(json_paths, first_json_path)?;
+    point_clangd_at_database(first_json_path, workspace_root)
+}
+
+/// 合并多个 compile_commands.json 文件并写入指定的输出文件，不修改 .clangd
+/// (workspace 命令随后用 generate_workspace_clangd 为每个项目生成指向合并结果的片段)
+///
+/// # 参数
+/// * `json_paths` - JSON 文件路径列表，第一个文件必须存在
+/// * `output_path` - 合并结果的写入位置
+pub fn merge_compile_commands_into(
+    json_paths: &[PathBuf],
+    output_path: &Path,
 ) -> Result<(), Box<dyn std::error::Error>> {
     debug_println!("[DEBUG generator] Starting to merge compile_commands.json files...");
     
@@ -1333,24 +2103,34 @@ pub fn merge_compile_commands(
         merged_commands.append(&mut commands);
     }
     
-    // 3. 写回第一个 JSON 文件
+    // 3. 写入输出文件
     debug_println!(
         "[DEBUG generator] Writing merged commands ({} total) to: {}",
         merged_commands.len(),
-        first_json_path.display()
+        output_path.display()
     );
     
     let json_content = serde_json::to_string_pretty(&merged_commands)?;
-    fs::write(first_json_path, json_content)?;
-    
-    // 4. 更新 .clangd 配置文件
+    fs::write(output_path, json_content)?;
+
+    debug_println!(
+        "[DEBUG generator] Successfully merged {} compile_commands.json files",
+        json_paths.len()
+    );
+
+    Ok(())
+}
+
+/// 将 .clangd 的 CompilationDatabase 指向合并后的数据库：只保留主配置 (移除所有 If 片段)
+fn point_clangd_at_database(output_path: &Path, workspace_root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    // 更新 .clangd 配置文件
     // 目标格式：将 CompilationDatabase 添加到现有 CompileFlags 块内部
     let clangd_path = workspace_root.join(".clangd");
-    let first_json_dir = first_json_path
+    let output_dir = output_path
         .parent()
         .unwrap_or_else(|| Path::new("."))
         .to_path_buf();
-    let db_path_str = first_json_dir.to_string_lossy().replace("\\", "/");
+    let db_path_str = output_dir.to_string_lossy().replace("\\", "/");
 
     debug_println!(
         "[DEBUG generator] Updating .clangd at: {}",
@@ -1424,21 +2204,98 @@ pub fn merge_compile_commands(
         clangd_path.display(),
         db_path_str
     );
-    
-    debug_println!(
-        "[DEBUG generator] Successfully merged {} compile_commands.json files",
-        json_paths.len()
-    );
-    
+
     Ok(())
 }
 
+/// 工作区 .clangd 中的单个项目 (使用其所选 target 的选项)
+pub struct WorkspaceClangdProject<'a> {
+    pub project_info: &'a ProjectInfo,
+    pub project_dir: &'a Path,
+    pub toolchain: &'a ToolchainConfig,
+    pub target: &'a crate::models::BuildTarget,
+}
+
+/// 生成工作区根目录的 .clangd
+/// 主配置只包含所有项目共有的 Add/Remove 选项；每个项目保留自己的 PathMatch 片段
+/// (generate_clangd_fragment 的格式)，写入其专属选项 (-target、include、-D、-xc 等)，
+/// 所有 CompilationDatabase 都指向合并后的数据库目录 db_dir。
+/// existing_content 中主配置的其他设置和不属于这些项目的片段原样保留
+pub fn generate_workspace_clangd(
+    existing_content: &str,
+    projects: &[WorkspaceClangdProject],
+    workspace_root: &Path,
+    db_dir: &Path,
+    no_header_insertion: bool,
+) -> String {
+    let db_path = db_dir.to_string_lossy().replace("\\", "/");
+    let project_flags: Vec<ClangdCompileFlags> = projects.iter()
+        .map(|p| clangd_compile_flags(p.project_info, p.toolchain, Some(p.target)))
+        .collect();
+
+    // 所有项目都包含的选项放入主配置
+    let shared = match project_flags.split_first() {
+        Some((first, rest)) => ClangdCompileFlags {
+            add: first.add.iter()
+                .filter(|group| rest.iter().all(|flags| flags.add.contains(group)))
+                .cloned()
+                .collect(),
+            remove: first.remove.iter()
+                .filter(|flag| rest.iter().all(|flags| flags.remove.contains(flag)))
+                .cloned()
+                .collect(),
+        },
+        None => ClangdCompileFlags::default(),
+    };
+    let mut base_config = format!("CompileFlags:\n  CompilationDatabase: {}\n", db_path);
+    shared.write_entries(&mut base_config);
+
+    let mut parts: Vec<&str> = existing_content.split("\n---").collect();
+    let existing_base = if existing_content.trim().is_empty() { "" } else { parts.remove(0) };
+    let mut base = if existing_base.trim().is_empty() {
+        base_config
+    } else {
+        merge_clangd_config(existing_base, &base_config)
+    };
+    if no_header_insertion && !base.contains("Completion:") {
+        if !base.ends_with('\n') {
+            base.push('\n');
+        }
+        base.push_str("\nCompletion:\n  HeaderInsertion: Never");
+    }
+
+    let fragments: Vec<(String, String)> = projects.iter().zip(&project_flags)
+        .map(|(project, flags)| {
+            let specific = ClangdCompileFlags {
+                add: flags.add.iter().filter(|group| !shared.add.contains(group)).cloned().collect(),
+                remove: flags.remove.iter().filter(|flag| !shared.remove.contains(flag)).cloned().collect(),
+            };
+            clangd_fragment(project.project_info, project.project_dir, workspace_root, &db_path, &specific)
+        })
+        .collect();
+
+    let mut final_parts = vec![base.trim_end().to_string()];
+    // 保留用户自己的片段，替换这些项目之前生成的片段
+    for part in parts {
+        let trimmed_part = part.trim();
+        let replaced = fragments.iter().any(|(path_match, _)| {
+            trimmed_part.lines().any(|line| line.trim() == format!("PathMatch: {}", path_match))
+        });
+        if !trimmed_part.is_empty() && !replaced {
+            final_parts.push(trimmed_part.to_string());
+        }
+    }
+    final_parts.extend(fragments.into_iter().map(|(_, fragment)| fragment));
+    final_parts.join("\n\n---\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
 
     #[test]
+    #[cfg(windows)]
     fn test_normalize_str() {
         // 测试斜杠替换
         assert_eq!(normalize_str("path/to/file"), "path\\to\\file");
@@ -1451,6 +2308,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(windows)]
     fn test_sanitize_flag() {
         // 测试 Include 路径清洗
         assert_eq!(sanitize_flag("-Ipath/to/include"), "-Ipath\\to\\include");
@@ -1466,6 +2324,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(windows)]
     fn test_get_clean_absolute_path() {
         let base = PathBuf::from("C:\\Project");
         let rel = Path::new("..\\Libs\\test.c");
@@ -1493,6 +2352,7 @@ mod tests {
     }
     
     #[test]
+    #[cfg(windows)]
     fn test_find_common_ancestor() {
         let paths = vec![
             PathBuf::from("C:\\Proj\\src\\main.c"),
@@ -1523,6 +2383,7 @@ mod tests {
     }
     
     #[test]
+    #[cfg(windows)]
     fn test_normalize_path() {
         // 测试 UNC 路径修复
         let p = Path::new("\\\\?\\UNC\\Server\\Share\\File.c");
@@ -1542,6 +2403,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(windows)]
     fn test_get_clean_absolute_path_extended() {
         // 测试多级相对路径
         let base = PathBuf::from("C:\\Project\\src");
@@ -1559,6 +2421,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(windows)]
     fn test_find_common_ancestor_edge_cases() {
         // 测试根目录
         let paths = vec![
@@ -1608,6 +2471,31 @@ mod tests {
         assert!(result.contains("-c"));
     }
 
+    #[test]
+    #[cfg(not(windows))]
+    fn test_posix_path_helpers() {
+        // POSIX 上统一为正斜杠，Windows 风格的分隔符会被转换
+        assert_eq!(normalize_str("path\\to\\file"), "path/to/file");
+        assert_eq!(normalize_str("path/to/file"), "path/to/file");
+        assert_eq!(sanitize_flag("-Ipath\\to\\include"), "-Ipath/to/include");
+        assert_eq!(sanitize_flag("-L..\\libs"), "-L../libs");
+        assert_eq!(sanitize_flag("-O2"), "-O2");
+        assert_eq!(normalize_path(Path::new("obj\\Debug/main.o")), "obj/Debug/main.o");
+
+        let base = PathBuf::from("/home/user/project/src");
+        let abs = get_clean_absolute_path(&base, Path::new("../../libs/test.c"));
+        assert_eq!(abs, PathBuf::from("/home/user/libs/test.c"));
+        let abs = get_clean_absolute_path(&base, Path::new("/abs/path/file.c"));
+        assert_eq!(abs, PathBuf::from("/abs/path/file.c"));
+
+        let paths = vec![
+            PathBuf::from("/proj/src/main.c"),
+            PathBuf::from("/proj/src/utils/helper.c"),
+            PathBuf::from("/proj/drivers/gpio.c"),
+        ];
+        assert_eq!(find_common_ancestor(&paths), PathBuf::from("/proj"));
+    }
+
     #[test]
     fn test_variable_substitution_logic() {
         use crate::models::SpecialFileBuildInfo;
@@ -1640,12 +2528,21 @@ mod tests {
                     build_command: "cp $file $(TARGET_OUTPUT_DIR)\\ && echo $(TARGET_OBJECT_DIR)".to_string(),
                     compile: true,
                     link: false,
+                    targets: vec![],
+                    weight: crate::models::DEFAULT_UNIT_WEIGHT,
                 }
             ],
             prebuild_commands: vec![],
             postbuild_commands: vec![],
+            postbuild_always: false,
+            prebuild_outputs: vec![],
             targets: vec![target],
+            virtual_targets: vec![],
             linker_type: "gcc".to_string(),
+            environment: vec![],
+            macros: Default::default(),
+            target_triple: None,
+            model: Default::default(),
         };
 
         let toolchain = crate::ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
@@ -1656,9 +2553,47 @@ mod tests {
 
         // 断言验证
         // 1. OUTPUT_DIR 应该是 bin\Debug (app.elf 的父目录)
-        assert!(ninja_content.contains("bin\\Debug"));
+        assert!(ninja_content.contains(&normalize_str("bin/Debug")));
         // 2. OBJECT_DIR 应该是 obj\Debug
-        assert!(ninja_content.contains("obj\\Debug"));
+        assert!(ninja_content.contains(&normalize_str("obj/Debug")));
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_generate_shell_script() {
+        use crate::parser::ProjectInfo;
+
+        let project = ProjectInfo {
+            compiler_id: "riscv32-v2".to_string(),
+            project_name: "Test".to_string(),
+            global_cflags: vec![],
+            global_include_dirs: vec![],
+            global_linker_libs: vec![],
+            global_linker_options: vec![],
+            global_linker_lib_dirs: vec![],
+            source_files: vec![],
+            special_files: vec![],
+            prebuild_commands: vec!["./gen.sh $(PROJECT_NAME)".to_string()],
+            postbuild_commands: vec![],
+            postbuild_always: false,
+            prebuild_outputs: vec![],
+            targets: vec![],
+            virtual_targets: vec![],
+            linker_type: "gcc".to_string(),
+            environment: vec![],
+            macros: Default::default(),
+            target_triple: None,
+            model: Default::default(),
+        };
+        let toolchain = crate::ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+        let script = generate_build_script(&project, &toolchain, Path::new("."), None);
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("export PATH=\"/opt/RV32-Toolchain/RV32-V2/bin:$PATH\""));
+        // 预构建命令由 build.ninja 执行，不再出现在构建脚本中
+        assert!(!script.contains("gen.sh"));
+        assert!(script.contains("ninja -f build.ninja || exit $?"));
+        assert!(!script.contains("call "));
     }
 
     #[test]
@@ -1,22 +1,47 @@
 // 公共API暴露
 mod cb_config;
+mod cbp_model;
+mod clang_target;
 mod cli;
 mod config;
 mod generator;
+mod macros;
 mod models;
 mod parser;
+mod platform;
+mod probe;
+mod toolchains;
 mod utils;
+mod workspace;
 
 // 暴露需要访问的函数
-pub use cb_config::{CbCompilerConfig, CbCompilerEntry, load_cb_compiler_config};
-pub use cli::{parse_args, Command, ConvertArgs, MergeCompileCommandsArgs};
+pub use clang_target::{ClangTarget, infer_target_triple};
+pub use cb_config::{
+    CB_CONFIG_ENV, CbCompilerConfig, CbCompilerDefinition, CbCompilerEntry, CbCompilerOption, CbGlobalVariable,
+    DefaultConfLocation, DefaultConfSource, compiler_definition_dirs, find_default_conf, load_cb_compiler_config,
+    load_compiler_definitions, parse_compiler_definition,
+};
+pub use cbp_model::{CbpElement, CbpProject};
+pub use cli::{parse_args, Command, ConvertArgs, MergeCompileCommandsArgs, WorkspaceArgs};
 pub use config::{ToolchainConfig, ToolchainResolveError};
 pub use generator::{
-    generate_build_script, generate_clangd_config, generate_clangd_fragment, generate_compile_commands, generate_ninja_build,
-    merge_clangd_config, merge_compile_commands,
+    generate_build_script, generate_clangd_config, generate_clangd_fragment, generate_compile_commands, generate_compile_commands_with_format,
+    generate_ninja_build,
+    generate_subninja_build, generate_workspace_clangd, generate_workspace_ninja, NinjaDependency, WorkspaceClangdProject,
+    WorkspaceNinjaProject,
+    merge_clangd_config, merge_compile_commands, merge_compile_commands_into,
+};
+pub use macros::MacroContext;
+pub use models::{
+    CompileCommandsFormat, CompilerVar, DEFAULT_UNIT_WEIGHT, ExtraCommands, OptionsRelation, OptionsRelations, TargetFlags, TargetType,
+    VirtualTarget,
 };
-pub use parser::parse_cbp_file;
+pub use parser::{parse_cbp_file, parse_cbp_file_with_path, ProjectInfo, TargetSelectError};
+pub use platform::{BUILD_SCRIPT_NAME, make_executable};
+pub use probe::{CompilerProbe, parse_probe_output, probe_compiler};
+pub use toolchains::{BUILTIN_TOOLCHAIN_IDS, CompilerSwitches, DefaultRoot, Tool, ToolNames, ToolchainDef};
 pub use utils::is_debug_mode;
 pub use utils::set_debug_mode;
 pub use utils::compute_absolute_path;
 pub use utils::get_clean_absolute_path;
+pub use workspace::{WorkspaceInfo, WorkspaceProject, parse_workspace_file};
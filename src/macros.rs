@@ -0,0 +1,335 @@
+use crate::cb_config::CbGlobalVariable;
+use crate::debug_println;
+use crate::models::BuildTarget;
+use crate::platform::with_trailing_separator;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 宏值中再次引用宏时的最大展开深度，防止循环引用导致无限递归
+const MAX_EXPANSION_DEPTH: usize = 8;
+
+/// 全局变量未指定成员时使用的默认成员 ($(#name) 等价于 $(#name.base))
+const DEFAULT_GLOBAL_MEMBER: &str = "base";
+
+/// Code::Blocks 宏展开上下文
+///
+/// 支持的写法：
+/// - `$(NAME)` / `${NAME}`：内置宏 (项目、target、日期)，其次是 <Environment> 变量，找不到时查找环境变量，仍找不到则展开为空
+/// - `$(#name)` / `$(#name.member)`：全局变量
+/// - `$NAME`：仅展开已定义的内置宏，未定义时保持原样 (避免破坏 ninja 的 $in/$out 等变量)；
+///   <Environment> 变量不参与裸名称查找，以免 OBJECT/FILE 之类的变量替换构建命令中的 $object/$file 占位符
+/// - `$$`：转义为单个 `$`
+#[derive(Debug, Default, Clone)]
+pub struct MacroContext {
+    vars: HashMap<String, String>,                       // 内置宏，键为大写名称
+    env: HashMap<String, String>,                        // <Environment> 变量，键为大写名称
+    globals: HashMap<String, HashMap<String, String>>, // 全局变量：名称 -> 成员 -> 值 (均为小写键)
+}
+
+impl MacroContext {
+    /// 创建项目级宏上下文
+    /// project_file 为 .cbp 文件路径，未知时 (例如直接解析 XML 字符串) 传入 None
+    pub fn for_project(project_name: &str, project_file: Option<&Path>) -> Self {
+        let mut ctx = Self::default();
+        for name in ["PROJECT_NAME", "PROJECTNAME"] {
+            ctx.set(name, project_name);
+        }
+
+        // Code::Blocks 中 $(PROJECT_DIR) 指 .cbp 文件所在目录
+        // 生成的构建脚本和 ninja 都在项目根目录运行，所以替换为宿主平台风格的当前目录
+        let project_dir = format!(".{}", crate::platform::PATH_SEPARATOR);
+        for name in ["PROJECT_DIR", "PROJECTDIR", "PROJECT_DIRECTORY"] {
+            ctx.set(name, &project_dir);
+        }
+
+        if let Some(file_name) = project_file.and_then(|p| p.file_name()) {
+            let file_name = file_name.to_string_lossy();
+            for name in ["PROJECT_FILENAME", "PROJECT_FILE_NAME", "PROJECT_FILE", "PROJECTFILE"] {
+                ctx.set(name, &file_name);
+            }
+        }
+        ctx
+    }
+
+    /// 基于当前上下文派生出某个 Build Target 的上下文，补充 $(TARGET_*) 宏
+    pub fn with_target(&self, target: &BuildTarget) -> Self {
+        let mut ctx = self.clone();
+        let output = target.artifact_output();
+        let output_path = Path::new(&output);
+
+        ctx.set("TARGET_NAME", &target.name);
+        ctx.set("TARGET_OUTPUT_FILE", &output);
+        ctx.set(
+            "TARGET_OUTPUT_BASENAME",
+            &output_path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default(),
+        );
+        ctx.set(
+            "TARGET_OUTPUT_DIR",
+            &dir_with_separator(&output_path.parent().map(|p| p.to_string_lossy()).unwrap_or_default()),
+        );
+        ctx.set("TARGET_OBJECT_DIR", &dir_with_separator(&target.object_output));
+
+        // target 级 <Environment> 变量 (值在解析时已展开)
+        for (name, value) in &target.environment {
+            ctx.set_env(name, value);
+        }
+        ctx
+    }
+
+    /// 定义 (或覆盖) 一个内置宏，名称不区分大小写
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.vars.insert(name.to_uppercase(), value.to_string());
+    }
+
+    /// 定义 (或覆盖) 一个 <Environment> 变量，名称不区分大小写，只能以 $(NAME) / ${NAME} 引用
+    pub fn set_env(&mut self, name: &str, value: &str) {
+        self.env.insert(name.to_uppercase(), value.to_string());
+    }
+
+    /// 定义一个全局变量成员，名称和成员均不区分大小写
+    pub fn set_global(&mut self, name: &str, member: &str, value: &str) {
+        self.globals
+            .entry(name.to_lowercase())
+            .or_default()
+            .insert(member.to_lowercase(), value.to_string());
+    }
+
+    /// 导入一组全局变量 (default.conf 中激活集合的 <gcv>)
+    pub fn set_global_variables(&mut self, variables: &HashMap<String, CbGlobalVariable>) {
+        for (name, variable) in variables {
+            for (member, value) in variable.resolved_members() {
+                self.set_global(name, &member, &value);
+            }
+        }
+    }
+
+    /// 展开字符串中的全部宏
+    pub fn expand(&self, input: &str) -> String {
+        self.expand_depth(input, 0)
+    }
+
+    /// 展开一组字符串 (编译选项、include 路径、链接选项等)
+    pub fn expand_all<'a>(&self, items: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+        items.into_iter().map(|item| self.expand(item)).collect()
+    }
+
+    fn expand_depth(&self, input: &str, depth: usize) -> String {
+        if depth > MAX_EXPANSION_DEPTH || !input.contains('$') {
+            return input.to_string();
+        }
+
+        let mut result = String::with_capacity(input.len());
+        let mut rest = input;
+        while let Some(pos) = rest.find('$') {
+            result.push_str(&rest[..pos]);
+            let after = &rest[pos + 1..];
+
+            // $$ 转义为单个 $
+            if let Some(stripped) = after.strip_prefix('$') {
+                result.push('$');
+                rest = stripped;
+                continue;
+            }
+
+            // 解析宏名称：(name)、{name} 或裸名称
+            let (name, consumed, bare) = match after.chars().next() {
+                Some(open @ ('(' | '{')) => {
+                    let close = if open == '(' { ')' } else { '}' };
+                    match after[1..].find(close) {
+                        Some(end) => (&after[1..1 + end], end + 2, false),
+                        None => {
+                            // 括号未闭合，按普通字符处理
+                            result.push('$');
+                            rest = after;
+                            continue;
+                        }
+                    }
+                }
+                _ => {
+                    let len = after
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                        .unwrap_or(after.len());
+                    (&after[..len], len, true)
+                }
+            };
+
+            if name.is_empty() {
+                result.push('$');
+                rest = after;
+                continue;
+            }
+
+            match self.lookup(name.trim(), bare) {
+                Some(value) => result.push_str(&self.expand_depth(&value, depth + 1)),
+                // 未定义的裸名称保持原样
+                None if bare => result.push_str(&rest[pos..pos + 1 + consumed]),
+                None => debug_println!("[DEBUG macros] Unknown macro '{}', expanding to empty", name),
+            }
+            rest = &after[consumed..];
+        }
+        result.push_str(rest);
+        result
+    }
+
+    fn lookup(&self, name: &str, bare: bool) -> Option<String> {
+        if let Some(global) = name.strip_prefix('#') {
+            return if bare { None } else { self.lookup_global(global) };
+        }
+        if let Some(value) = self.vars.get(&name.to_uppercase()) {
+            return Some(value.clone());
+        }
+        if let Some(value) = date_macro(name, unix_now()) {
+            return Some(value);
+        }
+        if bare {
+            return None;
+        }
+        if let Some(value) = self.env.get(&name.to_uppercase()) {
+            return Some(value.clone());
+        }
+        std::env::var(name).ok()
+    }
+
+    fn lookup_global(&self, spec: &str) -> Option<String> {
+        let (name, member) = spec.split_once('.').unwrap_or((spec, DEFAULT_GLOBAL_MEMBER));
+        let value = self
+            .globals
+            .get(&name.to_lowercase())
+            .and_then(|members| members.get(&member.to_lowercase()))
+            .cloned();
+        if value.is_none() {
+            eprintln!("Warning: Global variable '#{}' is not defined in Code::Blocks default.conf", spec);
+        }
+        value
+    }
+}
+
+/// 目录非空时确保以分隔符结尾，便于与文件名直接拼接 (如 $(TARGET_OUTPUT_DIR)app.map)
+fn dir_with_separator(dir: &str) -> String {
+    if dir.is_empty() {
+        String::new()
+    } else {
+        with_trailing_separator(dir.to_string())
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// 日期时间宏 (TDAY/TODAY/NOW/NOW_L/WEEKDAY 及其 _UTC 变体)
+/// 没有时区数据库可用，统一按 UTC 计算
+fn date_macro(name: &str, unix_secs: i64) -> Option<String> {
+    let upper = name.to_uppercase();
+    let base = upper.strip_suffix("_UTC").unwrap_or(&upper);
+
+    let days = unix_secs.div_euclid(86400);
+    let secs_of_day = unix_secs.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    let (hour, minute, second) = (secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60);
+
+    let value = match base {
+        "TDAY" => format!("{:04}{:02}{:02}", year, month, day),
+        "TODAY" => format!("{:04}-{:02}-{:02}", year, month, day),
+        "NOW" => format!("{:04}-{:02}-{:02}-{:02}.{:02}", year, month, day, hour, minute),
+        "NOW_L" => format!(
+            "{:04}-{:02}-{:02}-{:02}.{:02}.{:02}",
+            year, month, day, hour, minute, second
+        ),
+        "WEEKDAY" => {
+            const WEEKDAYS: [&str; 7] =
+                ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+            // 1970-01-01 是星期四
+            WEEKDAYS[(days + 4).rem_euclid(7) as usize].to_string()
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// 将 1970-01-01 起的天数转换为 (年, 月, 日)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_macro_forms() {
+        let target = BuildTarget {
+            name: "Release".to_string(),
+            output: "bin/Release/app".to_string(),
+            object_output: "obj/Release".to_string(),
+            extension_auto: true,
+            ..Default::default()
+        };
+        let mut ctx = MacroContext::for_project("demo", Some(Path::new("proj/demo.cbp"))).with_target(&target);
+        ctx.set("compiler", "gcc");
+        ctx.set_global("SDK", "base", "/opt/sdk");
+        ctx.set_global("sdk", "include", "/opt/sdk/inc");
+
+        assert_eq!(ctx.expand("$(PROJECT_NAME)-${TARGET_NAME}"), "demo-Release");
+        assert_eq!(ctx.expand("$(PROJECT_FILENAME)"), "demo.cbp");
+        assert_eq!(ctx.expand("$(TARGET_OUTPUT_FILE)"), "bin/Release/app.elf");
+        assert_eq!(ctx.expand("$(TARGET_OUTPUT_BASENAME)"), "app");
+        assert_eq!(ctx.expand("-Wl,-Map=$(TARGET_OUTPUT_DIR)app.map"), "-Wl,-Map=bin/Release/app.map");
+        assert_eq!(ctx.expand("$(TARGET_OBJECT_DIR)gen"), "obj/Release/gen");
+        assert_eq!(ctx.expand("-I$(#sdk)/lib -I$(#Sdk.include)"), "-I/opt/sdk/lib -I/opt/sdk/inc");
+
+        // 裸名称：已定义的展开，未定义的保持原样
+        assert_eq!(ctx.expand("$compiler -c $in -o $out"), "gcc -c $in -o $out");
+        // $$ 转义、未闭合括号
+        assert_eq!(ctx.expand("echo $$(PROJECT_NAME) $(oops"), "echo $(PROJECT_NAME) $(oops");
+        // 未知宏展开为空
+        assert_eq!(ctx.expand("a$(CBP2CLANGD_SURELY_UNDEFINED_VAR)b"), "ab");
+    }
+
+    #[test]
+    fn test_expand_environment_and_nested() {
+        let mut ctx = MacroContext::default();
+        let path = std::env::var("PATH").unwrap_or_default();
+        assert_eq!(ctx.expand("$(PATH)"), path);
+        assert_eq!(ctx.expand("${PATH}"), path);
+
+        // 宏的值中引用其他宏时递归展开，循环引用在达到深度上限后停止
+        ctx.set("OUT", "$(BASE)/out");
+        ctx.set("BASE", "build");
+        ctx.set("LOOP", "$(LOOP)");
+        assert_eq!(ctx.expand("$(OUT)"), "build/out");
+        assert!(ctx.expand("$(LOOP)").contains("$(LOOP)"));
+
+        // <Environment> 变量只能以 $(NAME) / ${NAME} 引用，不替换构建命令中的裸名称占位符
+        ctx.set_env("OBJECT", "env_object");
+        ctx.set("file", "src/a.c");
+        assert_eq!(ctx.expand("$file -o $object"), "src/a.c -o $object");
+        assert_eq!(ctx.expand("$(OBJECT) ${object}"), "env_object env_object");
+    }
+
+    #[test]
+    fn test_date_macros() {
+        // 2024-02-29 12:34:56 UTC (星期四)
+        let secs = 1_709_210_096;
+        assert_eq!(date_macro("TDAY", secs).unwrap(), "20240229");
+        assert_eq!(date_macro("TODAY", secs).unwrap(), "2024-02-29");
+        assert_eq!(date_macro("NOW_UTC", secs).unwrap(), "2024-02-29-12.34");
+        assert_eq!(date_macro("NOW_L", secs).unwrap(), "2024-02-29-12.34.56");
+        assert_eq!(date_macro("WEEKDAY", secs).unwrap(), "Thursday");
+        assert!(date_macro("TOMORROW", secs).is_none());
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+    }
+}
@@ -1,17 +1,35 @@
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
 
 use cbp2clangd::{
-    Command, ToolchainConfig, ToolchainResolveError, compute_absolute_path, debug_println,
-    generate_build_script, generate_compile_commands, generate_ninja_build,
-    merge_compile_commands, parse_args, parse_cbp_file, set_debug_mode,
-    load_cb_compiler_config,
+    Command, NinjaDependency, ProjectInfo, ToolchainConfig, ToolchainResolveError,
+    WorkspaceNinjaProject, compute_absolute_path, debug_println,
+    generate_build_script, generate_compile_commands_with_format, generate_ninja_build,
+    generate_subninja_build, generate_workspace_clangd, generate_workspace_ninja, WorkspaceClangdProject,
+    merge_compile_commands, merge_compile_commands_into, parse_args, parse_cbp_file_with_path,
+    parse_workspace_file, set_debug_mode,
+    compiler_definition_dirs, load_cb_compiler_config, load_compiler_definitions, make_executable, TargetSelectError, BUILD_SCRIPT_NAME,
     // 引入两个生成函数
     generate_clangd_config, generate_clangd_fragment,
 };
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// 工作区模式下每个项目额外生成的 ninja 文件，由工作区顶层 build.ninja 通过 subninja 引用
+const SUBNINJA_FILE_NAME: &str = "build.workspace.ninja";
+
+/// 单个项目的转换结果，供工作区流程继续使用
+struct ConvertedProject {
+    project_info: ProjectInfo,
+    project_dir: PathBuf,
+    toolchain: ToolchainConfig,
+    /// --target 所选 target (虚拟 target 为其全部成员) 的 compile_commands.json 路径，第一个为 .clangd 所引用的
+    compile_commands_paths: Vec<PathBuf>,
+    /// --target 所选的 target 名称 (可以是虚拟 target)
+    selected_target: String,
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 解析命令行参数
     debug_println!("[DEBUG] Parsing command line arguments...");
@@ -23,7 +41,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Ok(())
         }
         Command::Convert(args) => {
-            run_convert(args)
+            run_convert(args).map(|_| ())
+        }
+        Command::Workspace(args) => {
+            run_workspace(args)
         }
         Command::MergeCompileCommands(args) => {
             // 设置调试模式
@@ -44,7 +65,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
-fn run_convert(args: cbp2clangd::ConvertArgs) -> Result<(), Box<dyn std::error::Error>> {
+/// 转换单个 CBP 项目
+fn run_convert(args: cbp2clangd::ConvertArgs) -> Result<ConvertedProject, Box<dyn std::error::Error>> {
     // 设置调试模式
     set_debug_mode(args.debug);
 
@@ -123,28 +145,47 @@ fn run_convert(args: cbp2clangd::ConvertArgs) -> Result<(), Box<dyn std::error::
         fs::read_to_string(cbp_path)?
     };
 
+    // 加载 Code::Blocks 编译器配置 (如果存在)，其中的全局变量用于展开 CBP 中的 $(#name)
+    let mut cb_config = load_cb_compiler_config(args.cb_config_path.as_deref());
+    if cb_config.is_some() {
+        debug_println!("[DEBUG] Loaded Code::Blocks compiler config from default.conf");
+    } else {
+        debug_println!("[DEBUG] default.conf not found or unreadable, using built-in toolchains");
+    }
+
+    // 加载 Code::Blocks 安装 / 用户目录 (及 --cb-compilers) 中的编译器定义
+    let definitions = load_compiler_definitions(&compiler_definition_dirs(args.cb_compilers_dir.as_deref()));
+    debug_println!("[DEBUG] Loaded {} compiler definition(s)", definitions.len());
+    if !definitions.is_empty() {
+        cb_config.get_or_insert_with(Default::default).definitions = definitions;
+    }
+
     debug_println!("[DEBUG] Parsing CBP file...");
-    let mut project_info = parse_cbp_file(&xml_content)?;
+    let mut project_info = parse_cbp_file_with_path(&xml_content, cbp_path, cb_config.as_ref())?;
 
     // 使用命令行参数中的 linker_type 覆盖解析结果
     project_info.linker_type = args.linker_type;
 
+    // 确定 .clangd 使用的 target (--target 指定，默认第一个；虚拟 target 使用其第一个成员)
+    let selected_targets = match project_info.resolve_targets(args.target.as_deref()) {
+        Ok(targets) => targets,
+        Err(TargetSelectError::UnknownTarget { name, available }) => {
+            eprintln!("Error: CBP 文件中不存在 Build Target '{}'", name);
+            eprintln!("可用的 target: {}", available.join(", "));
+            return Err(format!("Unknown target: {}", name).into());
+        }
+    };
+    let selected_target = selected_targets[0];
+    debug_println!("[DEBUG] Selected target for .clangd: {}", selected_target.name);
+
     // 确定工具链配置
     debug_println!(
         "[DEBUG] Determining toolchain configuration for compiler: {}",
         project_info.compiler_id
     );
 
-    // 加载 Code::Blocks 编译器配置 (如果存在)
-    let cb_config = load_cb_compiler_config();
-    if cb_config.is_some() {
-        debug_println!("[DEBUG] Loaded Code::Blocks compiler config from default.conf");
-    } else {
-        debug_println!("[DEBUG] default.conf not found or unreadable, using hardcoded defaults");
-    }
-
     // 解析工具链配置
-    let toolchain = match ToolchainConfig::resolve_toolchain(&project_info.compiler_id, cb_config.as_ref()) {
+    let mut toolchain = match ToolchainConfig::resolve_toolchain(&project_info.compiler_id, cb_config.as_ref()) {
         Ok(config) => config,
         Err(ToolchainResolveError::UnknownCompiler { compiler_id, available }) => {
             eprintln!("Error: CBP 文件引用了未知的编译器 '{}'", compiler_id);
@@ -169,6 +210,11 @@ fn run_convert(args: cbp2clangd::ConvertArgs) -> Result<(), Box<dyn std::error::
         );
     }
 
+    // 运行编译器探测系统 include 路径 (--probe-compiler)，失败时保留默认路径
+    if args.probe_compiler && toolchain.probe_system_includes() {
+        println!("Probed system include dirs from {}", toolchain.compiler_path());
+    }
+
     // 项目根目录
     let project_dir = if args.test_mode {
         // 测试模式：直接使用当前目录
@@ -183,69 +229,56 @@ fn run_convert(args: cbp2clangd::ConvertArgs) -> Result<(), Box<dyn std::error::
     };
     debug_println!("[DEBUG] Project directory: {}", project_dir.display());
 
-    // 生成编译命令列表 - 使用第一个target
-    debug_println!("[DEBUG] Generating compile commands...");
-    let first_target = project_info.targets.first()
-        .expect("No target available in project");
-    debug_println!("[DEBUG] Using target: {}", first_target.name);
-
-    let compile_commands = generate_compile_commands(&project_info, &project_dir, &toolchain, Some(first_target));
-    debug_println!(
-        "[DEBUG] Compile commands generated: {}",
-        compile_commands.len()
-    );
-
-    // 1. 处理 Object Output (存放 CDB 和 bat) - 使用第一个target的object_output
-    let raw_obj_out = &first_target.object_output;
-    let abs_object_output = project_dir.join(raw_obj_out);
-    fs::create_dir_all(&abs_object_output)?;
-    // 修改：使用 compute_absolute_path 替代 canonicalize
-    let abs_object_output = compute_absolute_path(&abs_object_output)?;
-
-    debug_println!("[DEBUG] Object Output: {}", abs_object_output.display());
-
-    // 2. 生成 compile_commands.json
-    debug_println!("[DEBUG] Preparing compile_commands.json path...");
-
-    // 修改：使用 compute_absolute_path 替代 canonicalize
-    let normalized_output_dir = compute_absolute_path(&abs_object_output)?;
-    debug_println!(
-        "[DEBUG] Normalized output directory: {}",
-        normalized_output_dir.display()
-    );
-
-    // 确保输出目录存在
-    debug_println!("[DEBUG] Ensuring output directory exists...");
-    std::fs::create_dir_all(&normalized_output_dir)?;
-    debug_println!("[DEBUG] Output directory ensured");
+    // 为每个 target 生成 compile_commands.json，写入各自的 object_output 目录
+    debug_println!("[DEBUG] Generating compile commands for {} target(s)...", project_info.targets.len());
+    let mut written_databases: Vec<PathBuf> = Vec::new();
+    for target in &project_info.targets {
+        debug_println!("[DEBUG] Using target: {}", target.name);
+
+        let compile_commands = generate_compile_commands_with_format(
+            &project_info,
+            &project_dir,
+            &toolchain,
+            Some(target),
+            args.compile_commands_format,
+        );
+        debug_println!(
+            "[DEBUG] Compile commands generated: {}",
+            compile_commands.len()
+        );
 
-    // 使用规范化后的目录创建 compile_commands.json 路径
-    let compile_commands_path = normalized_output_dir.join("compile_commands.json");
-    debug_println!(
-        "[DEBUG] Final compile_commands.json path: {}",
-        compile_commands_path.display()
-    );
-    debug_println!(
-        "[DEBUG] After canonicalize: {}",
-        compile_commands_path.display()
-    );
+        // 1. 处理 Object Output (存放 CDB)
+        // 修改：使用 compute_absolute_path 替代 canonicalize
+        let abs_object_output = compute_absolute_path(&project_dir.join(&target.object_output))?;
+        debug_println!("[DEBUG] Object Output: {}", abs_object_output.display());
+
+        // 2. 生成 compile_commands.json
+        let compile_commands_path = abs_object_output.join("compile_commands.json");
+        if written_databases.contains(&compile_commands_path) {
+            // 多个 target 共用 object_output 时保留先出现的 target (与 .clangd 引用的保持一致)
+            eprintln!(
+                "Warning: Target '{}' shares object_output with another target, skipping {}",
+                target.name,
+                compile_commands_path.display()
+            );
+            continue;
+        }
+        fs::create_dir_all(&abs_object_output)?;
 
-    debug_println!("[DEBUG] Creating parent directory if needed...");
-    let parent_dir = compile_commands_path
-        .parent()
-        .unwrap_or_else(|| std::path::Path::new(""));
-    debug_println!("[DEBUG] Parent directory: {}", parent_dir.display());
-    fs::create_dir_all(parent_dir)?;
+        debug_println!("[DEBUG] Serializing compile commands to JSON...");
+        let json_content = serde_json::to_string_pretty(&compile_commands)?;
 
-    debug_println!("[DEBUG] Serializing compile commands to JSON...");
-    let json_content = serde_json::to_string_pretty(&compile_commands)?;
+        debug_println!(
+            "[DEBUG] Writing compile_commands.json to: {}",
+            compile_commands_path.display()
+        );
+        fs::write(&compile_commands_path, json_content)?;
+        println!("Generated {} (target: {})", compile_commands_path.display(), target.name);
+        written_databases.push(compile_commands_path);
+    }
 
-    debug_println!(
-        "[DEBUG] Writing compile_commands.json to: {}",
-        compile_commands_path.display()
-    );
-    fs::write(&compile_commands_path, json_content)?;
-    println!("Generated {}", compile_commands_path.display());
+    // .clangd 引用所选 target 的数据库
+    let abs_object_output = compute_absolute_path(&project_dir.join(&selected_target.object_output))?;
 
     // 3. 生成 build.ninja (放在 Project Dir)
     debug_println!("[DEBUG] Generating ninja build content...");
@@ -274,19 +307,25 @@ fn run_convert(args: cbp2clangd::ConvertArgs) -> Result<(), Box<dyn std::error::
         &project_dir,
         args.ninja_path.as_deref(),
     );
-    let build_script_path = project_dir.join("build.bat");
+    let build_script_path = project_dir.join(BUILD_SCRIPT_NAME);
     debug_println!(
         "[DEBUG] Writing build script to: {}",
         build_script_path.display()
     );
     fs::write(&build_script_path, build_script_content)?;
+    make_executable(&build_script_path)?;
     println!("Generated {}", build_script_path.display());
 
     // 5. 处理 .clangd (在 Workspace Root)
     let clangd_path = workspace_root.join(".clangd");
 
     // A. 生成公共头部 (Base Config) - 只包含 CompileFlags
-    let base_config = generate_clangd_config(&project_info, &toolchain, args.no_header_insertion)?;
+    let base_config = generate_clangd_config(
+        &project_info,
+        &toolchain,
+        args.no_header_insertion,
+        Some(selected_target),
+    )?;
 
     // B. 生成项目专属片段 (Fragment)
     // 注意：现在使用target特定的object_output路径
@@ -294,7 +333,8 @@ fn run_convert(args: cbp2clangd::ConvertArgs) -> Result<(), Box<dyn std::error::
         &project_info,
         &project_dir,
         &workspace_root,
-        &abs_object_output
+        &abs_object_output,
+        Some(selected_target),
     )?;
 
     // C. 读取并合并
@@ -338,7 +378,7 @@ fn run_convert(args: cbp2clangd::ConvertArgs) -> Result<(), Box<dyn std::error::
         if args.no_header_insertion && !has_completion {
             // 确保在适当的位置添加（在 CompileFlags 之后）
             if !merged_base_config.ends_with('\n') {
-                base_with_completion.push_str("\n");
+                base_with_completion.push('\n');
             }
             base_with_completion.push_str("\nCompletion:\n  HeaderInsertion: Never");
         }
@@ -366,5 +406,166 @@ fn run_convert(args: cbp2clangd::ConvertArgs) -> Result<(), Box<dyn std::error::
 
     debug_println!("[DEBUG] Program completed successfully");
 
+    let mut compile_commands_paths = Vec::new();
+    for target in &selected_targets {
+        let path = compute_absolute_path(&project_dir.join(&target.object_output))?.join("compile_commands.json");
+        if !compile_commands_paths.contains(&path) {
+            compile_commands_paths.push(path);
+        }
+    }
+    let selected_target = args.target.clone().unwrap_or_else(|| selected_target.name.clone());
+    Ok(ConvertedProject {
+        project_info,
+        project_dir,
+        toolchain,
+        compile_commands_paths,
+        selected_target,
+    })
+}
+
+/// 转换工作区中的所有项目，并在工作区根目录生成合并后的 compile_commands.json 和 .clangd
+fn run_workspace(args: cbp2clangd::WorkspaceArgs) -> Result<(), Box<dyn std::error::Error>> {
+    set_debug_mode(args.debug);
+    debug_println!("[DEBUG] Starting workspace conversion: {}", args.workspace_path.display());
+
+    let xml_content = fs::read_to_string(&args.workspace_path)?;
+    let workspace = parse_workspace_file(&xml_content)?;
+
+    // 成员项目路径相对于 .workspace 所在目录
+    let workspace_dir = compute_absolute_path(
+        args.workspace_path.parent().unwrap_or_else(|| Path::new(".")),
+    )?;
+    let workspace_root = compute_absolute_path(&args.output_dir)?;
+    if !workspace_root.exists() {
+        fs::create_dir_all(&workspace_root)?;
+    }
+    debug_println!("[DEBUG] Workspace Root: {}", workspace_root.display());
+
+    // 逐个运行完整的 convert 流程
+    let mut converted: Vec<(&cbp2clangd::WorkspaceProject, ConvertedProject)> = Vec::new();
+    for project in workspace.projects_in_convert_order() {
+        let cbp_path = project.path(&workspace_dir);
+        if !cbp_path.is_file() {
+            eprintln!("Warning: Workspace project not found, skipping: {}", cbp_path.display());
+            continue;
+        }
+
+        println!("\n=== Converting {} ===", cbp_path.display());
+        let result = run_convert(cbp2clangd::ConvertArgs {
+            cbp_path,
+            output_dir: workspace_root.clone(),
+            debug: args.debug,
+            linker_type: args.linker_type.clone(),
+            test_mode: false,
+            ninja_path: args.ninja_path.clone(),
+            no_header_insertion: args.no_header_insertion,
+            target: args.target.clone(),
+            compile_commands_format: args.compile_commands_format,
+            probe_compiler: args.probe_compiler,
+            cb_compilers_dir: args.cb_compilers_dir.clone(),
+            cb_config_path: args.cb_config_path.clone(),
+        })?;
+        converted.push((project, result));
+    }
+
+    if converted.is_empty() {
+        return Err(format!("No project of workspace '{}' could be converted", workspace.title).into());
+    }
+
+    // 为每个项目生成 subninja 文件，<Depends> 中的项目产物作为链接步骤的依赖
+    for (project, result) in &converted {
+        let mut dependencies = Vec::new();
+        for dep_filename in &project.depends {
+            let dep_result = workspace
+                .find_project(dep_filename, &workspace_dir)
+                .and_then(|dep| converted.iter().find(|(p, _)| std::ptr::eq(*p, dep)));
+            match dep_result {
+                Some((_, dep)) => dependencies.push(NinjaDependency {
+                    project_info: &dep.project_info,
+                    project_dir: &dep.project_dir,
+                }),
+                None => eprintln!(
+                    "Warning: Dependency '{}' of '{}' is not a converted workspace project, ignoring",
+                    dep_filename, project.filename
+                ),
+            }
+        }
+
+        let subninja_content = generate_subninja_build(
+            &result.project_info,
+            &result.project_dir,
+            &result.toolchain,
+            &workspace_root,
+            &dependencies,
+        )?;
+        let subninja_path = result.project_dir.join(SUBNINJA_FILE_NAME);
+        fs::write(&subninja_path, subninja_content)?;
+        println!("Generated {}", subninja_path.display());
+    }
+
+    // 顶层 build.ninja：按工作区文件中的顺序引用各项目
+    let ninja_projects: Vec<WorkspaceNinjaProject> = workspace.projects.iter()
+        .filter_map(|p| converted.iter().find(|(c, _)| std::ptr::eq(*c, p)))
+        .map(|(_, result)| WorkspaceNinjaProject {
+            project_dir: &result.project_dir,
+            subninja_file: SUBNINJA_FILE_NAME,
+            default_target: &result.selected_target,
+        })
+        .collect();
+    // 若有项目位于工作区根目录，保留其自身的 build.ninja
+    let workspace_ninja_name = if converted.iter().any(|(_, r)| r.project_dir == workspace_root) {
+        "workspace.ninja"
+    } else {
+        "build.ninja"
+    };
+    let workspace_ninja_path = workspace_root.join(workspace_ninja_name);
+    fs::write(
+        &workspace_ninja_path,
+        generate_workspace_ninja(&workspace.title, &workspace_root, &ninja_projects),
+    )?;
+    println!("Generated {}", workspace_ninja_path.display());
+    if workspace_ninja_name != "build.ninja" {
+        println!("Note: A project lives in the workspace root, build the workspace with `ninja -f {}`", workspace_ninja_name);
+    }
+
+    // 合并各项目的 compile_commands.json
+    let json_paths: Vec<PathBuf> = converted.iter()
+        .flat_map(|(_, result)| result.compile_commands_paths.iter().cloned())
+        .collect();
+
+    // 合并到工作区根目录
+    let merged_path = workspace_root.join("compile_commands.json");
+    merge_compile_commands_into(&json_paths, &merged_path)?;
+
+    // .clangd：每个项目保留自己的片段 (改为引用合并后的数据库)，主配置只包含共有的选项
+    let mut clangd_projects = Vec::new();
+    for (_, result) in &converted {
+        clangd_projects.push(WorkspaceClangdProject {
+            project_info: &result.project_info,
+            project_dir: &result.project_dir,
+            toolchain: &result.toolchain,
+            target: result.project_info.select_target(Some(&result.selected_target))?,
+        });
+    }
+    let clangd_path = workspace_root.join(".clangd");
+    let existing_clangd = if clangd_path.exists() { fs::read_to_string(&clangd_path)? } else { String::new() };
+    fs::write(
+        &clangd_path,
+        generate_workspace_clangd(
+            &existing_clangd,
+            &clangd_projects,
+            &workspace_root,
+            &workspace_root,
+            args.no_header_insertion,
+        ),
+    )?;
+    println!("Updated {} ({} project fragment(s))", clangd_path.display(), clangd_projects.len());
+    println!(
+        "Generated {} ({} project(s) of workspace '{}')",
+        merged_path.display(),
+        converted.len(),
+        workspace.title
+    );
+
     Ok(())
 }
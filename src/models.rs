@@ -1,4 +1,28 @@
 use serde::{Serialize, Deserialize};
+use crate::utils::quote_if_needed;
+
+/// Code::Blocks 中 Unit 未指定 weight 时的默认构建权重
+pub const DEFAULT_UNIT_WEIGHT: u32 = 50;
+
+/// Unit 使用的编译器驱动 (<Option compilerVar="..."/>)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompilerVar {
+    Cc,  // CC: C 编译器 (gcc)
+    Cpp, // CPP: C++ 编译器 (g++)
+    Asm, // ASM: 汇编器驱动
+}
+
+impl CompilerVar {
+    /// 从 CBP 中的 compilerVar 属性值解析
+    pub fn from_cb_value(value: &str) -> Option<Self> {
+        match value.trim() {
+            "CC" => Some(CompilerVar::Cc),
+            "CPP" => Some(CompilerVar::Cpp),
+            "ASM" => Some(CompilerVar::Asm),
+            _ => None,
+        }
+    }
+}
 
 /// 普通源文件信息，包含编译和链接标志
 #[derive(Debug, Default, PartialEq)]
@@ -6,14 +30,103 @@ pub struct SourceFileInfo {
     pub filename: String,    // 文件名
     pub compile: bool,       // 是否编译
     pub link: bool,          // 是否链接
+    pub targets: Vec<String>, // 所属的 Build Target (<Option target="..."/>)，为空表示属于所有 target
+    pub compiler_var: Option<CompilerVar>, // 指定的编译器驱动 (<Option compilerVar="..."/>)
+    pub weight: u32,         // 构建权重，权重小的先构建 (<Option weight="..."/>)
+    pub build_command: Option<String>, // 当前编译器的自定义构建命令，替代默认的 cc 规则
+}
+
+impl SourceFileInfo {
+    /// 判断该源文件是否属于指定的 Build Target
+    pub fn belongs_to(&self, target_name: &str) -> bool {
+        unit_belongs_to(&self.targets, target_name)
+    }
+
+    /// 源文件的语言：compilerVar 优先，未指定时按扩展名判断
+    pub fn language(&self) -> SourceLanguage {
+        let by_extension = SourceLanguage::from_filename(&self.filename);
+        match self.compiler_var {
+            Some(CompilerVar::Cpp) => SourceLanguage::Cpp,
+            Some(CompilerVar::Asm) => SourceLanguage::Asm,
+            // CC 也用于汇编文件 (gcc 驱动)，只有 C++ 扩展名被强制按 C 编译
+            Some(CompilerVar::Cc) if by_extension == SourceLanguage::Asm => SourceLanguage::Asm,
+            Some(CompilerVar::Cc) => SourceLanguage::C,
+            None => by_extension,
+        }
+    }
+}
+
+/// 源文件语言，决定使用的编译器驱动和 ninja 规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SourceLanguage {
+    C,
+    Cpp,
+    Asm,
+}
+
+impl SourceLanguage {
+    /// 按扩展名判断语言 (.cpp/.cc/.cxx/.C 为 C++，.s/.S 为汇编，其余按 C 处理)
+    pub fn from_filename(filename: &str) -> Self {
+        match std::path::Path::new(filename).extension().and_then(|e| e.to_str()) {
+            Some("C") => SourceLanguage::Cpp,
+            Some("s") | Some("S") => SourceLanguage::Asm,
+            Some(ext) if matches!(ext.to_ascii_lowercase().as_str(), "cpp" | "cc" | "cxx" | "c++") => SourceLanguage::Cpp,
+            _ => SourceLanguage::C,
+        }
+    }
+
+    /// 该语言在 build.ninja 中使用的共享编译规则
+    pub fn ninja_rule(self) -> &'static str {
+        match self {
+            SourceLanguage::C => "cc",
+            SourceLanguage::Cpp => "cxx",
+            SourceLanguage::Asm => "as",
+        }
+    }
+}
+
+/// compile_commands.json 中编译命令的写法
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CompileCommandsFormat {
+    /// `command`：参数拼接为单个命令字符串 (默认)
+    #[default]
+    Command,
+    /// `arguments`：参数数组，不需要引号转义，包含引号或反斜杠的选项不会失真
+    Arguments,
 }
 
 /// 编译命令结构，用于生成compile_commands.json
+/// `command` 和 `arguments` 只会写出其中之一；读取时两种写法都接受 (第三方工具可能使用任一种)
 #[derive(Serialize, Deserialize)]
 pub struct CompileCommand {
     pub directory: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<String>,
     pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>, // 该编译步骤生成的对象文件
+}
+
+impl CompileCommand {
+    /// 按指定写法从参数列表创建编译命令
+    pub fn new(
+        directory: String,
+        arguments: Vec<String>,
+        file: String,
+        output: Option<String>,
+        format: CompileCommandsFormat,
+    ) -> Self {
+        let (command, arguments) = match format {
+            CompileCommandsFormat::Command => (
+                arguments.iter().map(|arg| quote_if_needed(arg)).collect::<Vec<_>>().join(" "),
+                Vec::new(),
+            ),
+            CompileCommandsFormat::Arguments => (String::new(), arguments),
+        };
+        CompileCommand { directory, command, arguments, file, output }
+    }
 }
 
 /// 特殊文件构建信息
@@ -25,20 +138,146 @@ pub struct SpecialFileBuildInfo {
     pub build_command: String, // 构建命令模板
     pub compile: bool,      // 是否编译
     pub link: bool,         // 是否链接
+    pub targets: Vec<String>, // 所属的 Build Target，为空表示属于所有 target
+    pub weight: u32,        // 构建权重，权重小的先构建
+}
+
+impl SpecialFileBuildInfo {
+    /// 判断该特殊文件是否属于指定的 Build Target
+    pub fn belongs_to(&self, target_name: &str) -> bool {
+        unit_belongs_to(&self.targets, target_name)
+    }
+}
+
+/// Code::Blocks 中未列出任何 target 的 Unit 属于所有 target
+fn unit_belongs_to(targets: &[String], target_name: &str) -> bool {
+    targets.is_empty() || targets.iter().any(|t| t == target_name)
 }
 
 /// RISC-V架构特性信息
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct MarchInfo {
     pub full_march: String,         // 完整的-march参数值
     pub base_march: Option<String>, // 基础部分（不带自定义扩展）
     pub has_custom_extension: bool, // 是否包含自定义扩展
 }
 
+/// Code::Blocks Build Target 类型 (<Option type="..."/>)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TargetType {
+    GuiApp,       // 0: GUI 应用程序
+    #[default]
+    ConsoleApp,   // 1: 控制台应用程序
+    StaticLib,    // 2: 静态库
+    DynamicLib,   // 3: 动态库
+    CommandsOnly, // 4: 仅执行命令 (不编译)
+    Native,       // 5: 本地程序 (如驱动)
+}
+
+impl TargetType {
+    /// 从 CBP 中的 type 属性值解析
+    pub fn from_cb_value(value: &str) -> Option<Self> {
+        match value.trim() {
+            "0" => Some(TargetType::GuiApp),
+            "1" => Some(TargetType::ConsoleApp),
+            "2" => Some(TargetType::StaticLib),
+            "3" => Some(TargetType::DynamicLib),
+            "4" => Some(TargetType::CommandsOnly),
+            "5" => Some(TargetType::Native),
+            _ => None,
+        }
+    }
+
+    /// 是否为库 (链接时作为其他 target 的输入)
+    pub fn is_library(self) -> bool {
+        matches!(self, TargetType::StaticLib | TargetType::DynamicLib)
+    }
+}
+
+/// target 选项与项目选项的组合方式 (Code::Blocks 的 OptionsRelation，<Option projectXxxRelation="..."/>)
+/// 取值与 Code::Blocks sdk/compileoptionsbase.h 中的枚举一致：
+/// orUseParentOptionsOnly = 0、orUseTargetOptionsOnly = 1、orPrependToParentOptions = 2、orAppendToParentOptions = 3
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OptionsRelation {
+    ProjectOnly,   // 0: 只使用项目选项 (orUseParentOptionsOnly)
+    TargetOnly,    // 1: 只使用 target 选项 (orUseTargetOptionsOnly)
+    PrependTarget, // 2: target 选项位于项目选项之前 (orPrependToParentOptions)
+    #[default]
+    AppendTarget,  // 3: target 选项位于项目选项之后 (orAppendToParentOptions，默认)
+}
+
+impl OptionsRelation {
+    /// 从 CBP 中的 projectXxxRelation 属性值解析
+    pub fn from_cb_value(value: &str) -> Option<Self> {
+        match value.trim() {
+            "0" => Some(OptionsRelation::ProjectOnly),
+            "1" => Some(OptionsRelation::TargetOnly),
+            "2" => Some(OptionsRelation::PrependTarget),
+            "3" => Some(OptionsRelation::AppendTarget),
+            _ => None,
+        }
+    }
+
+    /// 按组合方式合并项目选项和 target 选项
+    pub fn combine(self, project: &[String], target: &[String]) -> Vec<String> {
+        let (first, second): (&[String], &[String]) = match self {
+            OptionsRelation::ProjectOnly => (project, &[]),
+            OptionsRelation::TargetOnly => (&[], target),
+            OptionsRelation::PrependTarget => (target, project),
+            OptionsRelation::AppendTarget => (project, target),
+        };
+        first.iter().chain(second).cloned().collect()
+    }
+}
+
+/// Build Target 各类选项与项目选项的组合方式
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OptionsRelations {
+    pub compiler_options: OptionsRelation,      // projectCompilerOptionsRelation (包括 -D 宏定义)
+    pub linker_options: OptionsRelation,        // projectLinkerOptionsRelation (包括链接库)
+    pub include_dirs: OptionsRelation,          // projectIncludeDirsRelation
+    pub lib_dirs: OptionsRelation,              // projectLibDirsRelation
+    pub resource_include_dirs: OptionsRelation, // projectResourceIncludeDirsRelation (不生成资源编译步骤，仅记录)
+}
+
+/// 按 OptionsRelation 组合后的 target 选项 (宏未展开)，compile_commands.json、build.ninja 和 .clangd 共用
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TargetFlags {
+    pub cflags: Vec<String>,         // 编译选项
+    pub include_dirs: Vec<String>,   // 头文件目录 (-I)
+    pub linker_options: Vec<String>, // 链接器选项
+    pub linker_libs: Vec<String>,    // 链接库
+    pub lib_dirs: Vec<String>,       // 库搜索路径 (-L)
+}
+
+/// <ExtraCommands> 中的预/后构建命令 (宏未展开，生成 build.ninja 时用 ProjectInfo::command_macros 展开)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExtraCommands {
+    pub before: Vec<String>, // 预构建命令 (<Add before="..."/>)，在编译之前执行
+    pub after: Vec<String>,  // 后构建命令 (<Add after="..."/>)，在链接之后执行
+    pub after_always: bool,  // <Mode after="always"/>：产物没有重新生成时也执行后构建命令
+}
+
+impl ExtraCommands {
+    pub fn is_empty(&self) -> bool {
+        self.before.is_empty() && self.after.is_empty()
+    }
+}
+
+/// 虚拟 target (<VirtualTargets><Add alias="All" targets="Debug;Test;" />)：一组 Build Target 的别名
+#[derive(Debug, Clone, PartialEq)]
+pub struct VirtualTarget {
+    pub name: String,         // 别名 (alias)
+    pub targets: Vec<String>, // 成员 target 名称 (按原始顺序，只包含 CBP 中存在的 target)
+}
+
 /// 单个Build Target的配置信息
 #[derive(Debug, Default)]
 pub struct BuildTarget {
     pub name: String,                   // Target名称 (如 "Debug", "Release")
+    pub target_type: TargetType,        // Target类型
+    pub prefix_auto: bool,              // 库自动添加 lib 前缀 (prefix_auto="1")
+    pub extension_auto: bool,           // 自动设置产物扩展名 (extension_auto="1")
     pub output: String,                 // 输出文件路径
     pub object_output: String,          // 对象文件输出目录
     pub cflags: Vec<String>,            // 编译选项
@@ -48,4 +287,59 @@ pub struct BuildTarget {
     pub linker_libs: Vec<String>,       // 链接库
     pub linker_lib_dirs: Vec<String>,   // 库搜索路径
     pub march_info: MarchInfo,          // 架构信息
+    pub environment: Vec<(String, String)>, // <Environment> 变量 (值已展开)
+    pub relations: OptionsRelations,    // 与项目选项的组合方式
+    pub extra_commands: ExtraCommands,  // target 级 <ExtraCommands>
+}
+
+impl BuildTarget {
+    /// 按 Code::Blocks 的规则计算最终产物路径：
+    /// prefix_auto 为库添加 lib 前缀，extension_auto 按 target 类型设置扩展名
+    /// (静态库 .a，动态库 .so，可执行程序在没有扩展名时使用 .elf，本地程序 .sys)
+    pub fn artifact_output(&self) -> String {
+        // Windows 上保存的 CBP 使用反斜杠 (bin\Debug\drv)，在任何宿主上都按两种分隔符拆分出文件名
+        let (dir, file_name) = match self.output.rfind(['/', '\\']) {
+            Some(pos) => self.output.split_at(pos + 1),
+            None => ("", self.output.as_str()),
+        };
+        if file_name.is_empty() || file_name == "." || file_name == ".." {
+            return self.output.clone();
+        }
+        let (mut stem, mut extension) = match file_name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => (stem.to_string(), Some(ext.to_string())),
+            _ => (file_name.to_string(), None),
+        };
+
+        match self.target_type {
+            TargetType::StaticLib | TargetType::DynamicLib => {
+                if self.prefix_auto && !stem.starts_with("lib") {
+                    stem = format!("lib{}", stem);
+                }
+                if self.extension_auto {
+                    let ext = if self.target_type == TargetType::StaticLib { "a" } else { "so" };
+                    extension = Some(ext.to_string());
+                }
+            }
+            TargetType::GuiApp | TargetType::ConsoleApp => {
+                if self.extension_auto && extension.is_none() {
+                    extension = Some("elf".to_string());
+                }
+            }
+            TargetType::Native => {
+                if self.extension_auto {
+                    extension = Some("sys".to_string());
+                }
+            }
+            TargetType::CommandsOnly => return self.output.clone(),
+        }
+
+        let new_name = match extension {
+            Some(ext) => format!("{}.{}", stem, ext),
+            None => stem,
+        };
+        if new_name == file_name {
+            return self.output.clone();
+        }
+        format!("{}{}", dir, new_name)
+    }
 }
@@ -1,9 +1,44 @@
 use crate::ToolchainConfig;
-use crate::models::{BuildTarget, SpecialFileBuildInfo, SourceFileInfo};
+use crate::cb_config::CbCompilerConfig;
+use crate::cbp_model::CbpProject;
+use crate::debug_println;
+use crate::macros::MacroContext;
+use crate::models::{
+    BuildTarget, CompilerVar, DEFAULT_UNIT_WEIGHT, ExtraCommands, OptionsRelation, SourceLanguage, SpecialFileBuildInfo, SourceFileInfo,
+    TargetFlags, TargetType, VirtualTarget,
+};
 use roxmltree::Document;
 use std::collections::HashSet;
+use std::fmt;
 use std::path::Path;
 
+/// Build Target 选择失败错误类型
+#[derive(Debug)]
+pub enum TargetSelectError {
+    /// --target 指定的名称不存在于 CBP 中
+    UnknownTarget {
+        name: String,
+        available: Vec<String>,
+    },
+}
+
+impl fmt::Display for TargetSelectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetSelectError::UnknownTarget { name, available } => {
+                write!(
+                    f,
+                    "Unknown target '{}'. Available targets: {}",
+                    name,
+                    available.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TargetSelectError {}
+
 /// 项目信息结构
 pub struct ProjectInfo {
     pub compiler_id: String,
@@ -15,14 +50,131 @@ pub struct ProjectInfo {
     pub global_linker_lib_dirs: Vec<String>, // 全局库搜索路径 (Project/Linker)
     pub source_files: Vec<SourceFileInfo>,
     pub special_files: Vec<SpecialFileBuildInfo>,
-    pub prebuild_commands: Vec<String>,
-    pub postbuild_commands: Vec<String>,
+    pub prebuild_commands: Vec<String>,      // 项目级预构建命令 (宏未展开，见 command_macros)
+    pub postbuild_commands: Vec<String>,     // 项目级后构建命令 (宏未展开)
+    pub postbuild_always: bool,              // 项目级 <Mode after="always"/>
+    pub prebuild_outputs: Vec<String>,       // 预构建命令生成的文件 (<Extensions><cbp2clangd><prebuild_output file="..."/>)
     pub targets: Vec<BuildTarget>,           // 各个Build Target的配置
+    pub virtual_targets: Vec<VirtualTarget>, // 虚拟 target (<VirtualTargets>)，一组 target 的别名
     pub linker_type: String,
+    pub environment: Vec<(String, String)>,  // 项目级 <Environment> 变量 (值已展开)
+    pub macros: MacroContext,                // 项目级宏上下文 ($(PROJECT_*)、全局变量等)
+    pub target_triple: Option<String>,       // 覆盖 clangd 的目标三元组 (<Extensions><cbp2clangd target_triple="..."/>)
+    pub model: CbpProject,                   // 无损的原始结构 (全部元素和属性，保留原始顺序)
+}
+
+impl ProjectInfo {
+    /// 按名称选择 Build Target，未指定名称时使用第一个 target
+    /// 名称为虚拟 target 时返回其第一个成员
+    pub fn select_target(&self, name: Option<&str>) -> Result<&BuildTarget, TargetSelectError> {
+        Ok(self.resolve_targets(name)?[0])
+    }
+
+    /// 按名称解析出对应的 Build Target 列表：普通 target 对应自身，虚拟 target 对应其全部成员 (按别名中的顺序)
+    /// 未指定名称时使用第一个 target；返回的列表非空
+    pub fn resolve_targets(&self, name: Option<&str>) -> Result<Vec<&BuildTarget>, TargetSelectError> {
+        let find = |name: &str| self.targets.iter().find(|t| t.name == name);
+        let found: Vec<&BuildTarget> = match name {
+            Some(name) => match find(name) {
+                Some(target) => vec![target],
+                None => self
+                    .virtual_target(name)
+                    .map(|alias| alias.targets.iter().filter_map(|member| find(member)).collect())
+                    .unwrap_or_default(),
+            },
+            None => self.targets.first().into_iter().collect(),
+        };
+        if found.is_empty() {
+            return Err(TargetSelectError::UnknownTarget {
+                name: name.unwrap_or_default().to_string(),
+                available: self
+                    .targets
+                    .iter()
+                    .map(|t| t.name.clone())
+                    .chain(self.virtual_targets.iter().map(|v| v.name.clone()))
+                    .collect(),
+            });
+        }
+        Ok(found)
+    }
+
+    /// 按别名查找虚拟 target
+    pub fn virtual_target(&self, name: &str) -> Option<&VirtualTarget> {
+        self.virtual_targets.iter().find(|v| v.name == name)
+    }
+
+    /// 获取某个 Build Target 的宏上下文 (项目级宏 + $(TARGET_*))
+    pub fn target_macros(&self, target: &BuildTarget) -> MacroContext {
+        self.macros.with_target(target)
+    }
+
+    /// 展开某个 Build Target 的 ExtraCommands 所用的宏上下文：target 宏加上 Code::Blocks 构建命令模板中的
+    /// $compiler (构建所用工具链的 C 编译器)、$options / $includes (项目级编译选项和 include 路径)
+    pub fn command_macros(&self, target: &BuildTarget, toolchain: &ToolchainConfig) -> MacroContext {
+        let mut macros = self.target_macros(target);
+
+        // 对每个编译选项和include路径进行引号处理，防止空格导致命令解析错误
+        let quote = |opt: String| if opt.contains(' ') { format!("\"{}\"", opt) } else { opt };
+        let options: Vec<String> = macros.expand_all(&self.global_cflags).into_iter().map(quote).collect();
+        let includes: Vec<String> = macros.expand_all(&self.global_include_dirs).into_iter().map(quote).collect();
+
+        // 使用编译器的执行路径 (例如: C:\Program Files\...\riscv32-elf-gcc.exe)，避免依赖 PATH 环境变量
+        macros.set("compiler", &format!("\"{}\"", toolchain.compiler_path()));
+        macros.set("options", &options.join(" "));
+        macros.set("includes", &includes.join(" "));
+        macros
+    }
+
+    /// 项目是否只包含 C 源文件 (汇编文件不影响判断)，用于决定 .clangd 是否强制 -xc
+    pub fn is_c_only(&self) -> bool {
+        !self.source_files.iter()
+            .any(|src| src.compile && src.language() == SourceLanguage::Cpp)
+    }
+
+    /// 获取某个 Build Target 构建时的环境变量：项目级变量 + target 级变量 (同名时 target 优先)
+    pub fn target_environment(&self, target: &BuildTarget) -> Vec<(String, String)> {
+        self.environment
+            .iter()
+            .filter(|(name, _)| !target.environment.iter().any(|(n, _)| n == name))
+            .chain(target.environment.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// 组合某个 Build Target 构建时使用的项目选项和 target 选项 (按 target 的 projectXxxRelation)
+    /// default.conf 中编译器的全局选项不在其中，由调用方放在最前面
+    pub fn target_flags(&self, target: &BuildTarget) -> TargetFlags {
+        let relations = &target.relations;
+        TargetFlags {
+            cflags: relations.compiler_options.combine(&self.global_cflags, &target.cflags),
+            include_dirs: relations.include_dirs.combine(&self.global_include_dirs, &target.include_dirs),
+            linker_options: relations.linker_options.combine(&self.global_linker_options, &target.linker_options),
+            linker_libs: relations.linker_options.combine(&self.global_linker_libs, &target.linker_libs),
+            lib_dirs: relations.lib_dirs.combine(&self.global_linker_lib_dirs, &target.linker_lib_dirs),
+        }
+    }
 }
 
 /// 解析Code::Blocks项目文件
 pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::error::Error>> {
+    parse_cbp_project(xml_content, None, None)
+}
+
+/// 解析Code::Blocks项目文件
+/// 使用 .cbp 文件路径提供 $(PROJECT_FILENAME) 等宏，使用 default.conf 中的全局变量展开 $(#name)
+pub fn parse_cbp_file_with_path(
+    xml_content: &str,
+    cbp_path: &Path,
+    cb_config: Option<&CbCompilerConfig>,
+) -> Result<ProjectInfo, Box<dyn std::error::Error>> {
+    parse_cbp_project(xml_content, Some(cbp_path), cb_config)
+}
+
+fn parse_cbp_project(
+    xml_content: &str,
+    cbp_path: Option<&Path>,
+    cb_config: Option<&CbCompilerConfig>,
+) -> Result<ProjectInfo, Box<dyn std::error::Error>> {
     let doc = Document::parse(xml_content)?;
     let root = doc.root_element();
 
@@ -62,6 +214,13 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
         }
     }
     println!("Project name: {}", project_name);
+    let mut macros = MacroContext::for_project(&project_name, cbp_path);
+    if let Some(variables) = cb_config.and_then(CbCompilerConfig::active_global_variables) {
+        macros.set_global_variables(variables);
+    }
+
+    // === 项目级环境变量 (Project/Environment) ===
+    let environment = parse_environment(&project, &mut macros);
 
     // === 提取 compiler ID ===
     let mut compiler_id = "riscv32-v2".to_string(); // default
@@ -94,7 +253,7 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
             if let Some(opt) = add.attribute("option") {
                 global_cflags.push(opt.to_string());
                 // 检测全局 -march= 指令
-                parse_march_flag(&opt, &mut global_march_info);
+                parse_march_flag(opt, &mut global_march_info);
             }
             if let Some(dir) = add.attribute("directory") {
                 global_include_dirs.push(format!("-I{}", dir));
@@ -125,8 +284,6 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
 
     // === 解析Build Targets ===
     let mut targets = Vec::new();
-    let mut prebuild_commands = Vec::new();
-    let mut postbuild_commands = Vec::new();
 
     for build_node in project
         .children()
@@ -139,21 +296,61 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
             let target_name = target_node.attribute("title").unwrap_or("Default").to_string();
             println!("Found target: {}", target_name);
 
+            // prefix_auto/extension_auto 未指定时 Code::Blocks 默认开启
             let mut target = BuildTarget {
                 name: target_name,
+                prefix_auto: true,
+                extension_auto: true,
                 ..Default::default()
             };
 
-            // 解析Target级别的Option (output, object_output等)
+            // 解析Target级别的Option (output, object_output, type等)
+            let mut target_type = None;
             for option_node in target_node.children().filter(|n| n.tag_name().name() == "Option") {
+                if let Some(type_attr) = option_node.attribute("type") {
+                    target_type = TargetType::from_cb_value(type_attr);
+                    if target_type.is_none() {
+                        eprintln!("Warning: Unknown type '{}' for target {}", type_attr, target.name);
+                    }
+                }
                 if let Some(output) = option_node.attribute("output") {
                     target.output = output.to_string();
                 }
                 if let Some(obj_output) = option_node.attribute("object_output") {
                     target.object_output = obj_output.to_string();
                 }
+                if let Some(prefix_auto) = option_node.attribute("prefix_auto") {
+                    target.prefix_auto = prefix_auto == "1";
+                }
+                if let Some(extension_auto) = option_node.attribute("extension_auto") {
+                    target.extension_auto = extension_auto == "1";
+                }
+                // target 选项与项目选项的组合方式
+                let relations = [
+                    ("projectCompilerOptionsRelation", &mut target.relations.compiler_options),
+                    ("projectLinkerOptionsRelation", &mut target.relations.linker_options),
+                    ("projectIncludeDirsRelation", &mut target.relations.include_dirs),
+                    ("projectLibDirsRelation", &mut target.relations.lib_dirs),
+                    ("projectResourceIncludeDirsRelation", &mut target.relations.resource_include_dirs),
+                ];
+                for (attr, relation) in relations {
+                    if let Some(value) = option_node.attribute(attr) {
+                        match OptionsRelation::from_cb_value(value) {
+                            Some(parsed) => *relation = parsed,
+                            None => eprintln!("Warning: Unknown {} '{}' for target {}", attr, value, target.name),
+                        }
+                    }
+                }
             }
 
+            // 输出路径中可能引用项目宏、全局变量或环境变量 (例如 bin/$(TARGET_NAME)/app.elf)
+            let mut output_macros = macros.clone();
+            output_macros.set("TARGET_NAME", &target.name);
+            // target 级环境变量 (Target/Environment) 覆盖同名的项目级变量
+            target.environment = parse_environment(&target_node, &mut output_macros);
+            target.output = output_macros.expand(&target.output);
+            target.object_output = output_macros.expand(&target.object_output);
+
             // 解析Target级别Compiler
             if let Some(compiler_node) = target_node.children().find(|n| n.tag_name().name() == "Compiler") {
                 for add in compiler_node.children().filter(|n| n.tag_name().name() == "Add") {
@@ -190,11 +387,39 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
                 }
             }
 
+            // 0 表示只使用项目选项：target 自己的选项不会生效，提示用户确认 (只使用 target 选项应为 1)
+            let relations = &target.relations;
+            // -D 宏定义单独记录在 defines 中，同样跟随 projectCompilerOptionsRelation
+            let has_compiler_options = !target.cflags.is_empty() || !target.defines.is_empty();
+            let has_linker_options = !target.linker_options.is_empty() || !target.linker_libs.is_empty();
+            let ignored = [
+                ("projectCompilerOptionsRelation", relations.compiler_options, has_compiler_options),
+                ("projectLinkerOptionsRelation", relations.linker_options, has_linker_options),
+                ("projectIncludeDirsRelation", relations.include_dirs, !target.include_dirs.is_empty()),
+                ("projectLibDirsRelation", relations.lib_dirs, !target.linker_lib_dirs.is_empty()),
+            ];
+            for (attr, relation, has_target_options) in ignored {
+                if relation == OptionsRelation::ProjectOnly && has_target_options {
+                    eprintln!(
+                        "Warning: Target {} sets {}=0 (project options only), its own options are ignored; \
+                         use 1 for target options only",
+                        target.name, attr
+                    );
+                }
+            }
+
             // 如果没有找到output，使用默认值
             if target.output.is_empty() {
                 target.output = format!("{}.elf", project_name);
             }
 
+            // 未指定type时按输出文件推断：.a 为静态库，其余为可执行程序
+            target.target_type = target_type.unwrap_or(if target.output.ends_with(".a") {
+                TargetType::StaticLib
+            } else {
+                TargetType::ConsoleApp
+            });
+
             // 如果没有找到object_output，从output推导
             if target.object_output.is_empty() {
                 let output_path = Path::new(&target.output);
@@ -213,6 +438,9 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
                 }
             }
 
+            // target 级 ExtraCommands 在该 target 的上下文中展开
+            target.extra_commands = parse_extra_commands(&target_node);
+
             targets.push(target);
         }
     }
@@ -227,106 +455,35 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
         });
     }
 
-    // 将全局 march_info 传播到未设置 march 的 target
-    if !global_march_info.full_march.is_empty() {
-        for target in &mut targets {
-            if target.march_info.full_march.is_empty() {
-                target.march_info = crate::models::MarchInfo {
-                    full_march: global_march_info.full_march.clone(),
-                    base_march: global_march_info.base_march.clone(),
-                    has_custom_extension: global_march_info.has_custom_extension,
-                };
+    // 将全局 march_info 传播到 target：按编译选项的组合方式，命令行中最后出现的 -march 生效
+    for target in &mut targets {
+        let use_global = match target.relations.compiler_options {
+            OptionsRelation::ProjectOnly => true,
+            OptionsRelation::TargetOnly => false,
+            OptionsRelation::PrependTarget => !global_march_info.full_march.is_empty(),
+            OptionsRelation::AppendTarget => {
+                !global_march_info.full_march.is_empty() && target.march_info.full_march.is_empty()
             }
+        };
+        if use_global {
+            target.march_info = global_march_info.clone();
         }
     }
 
-    // 对每个编译选项和include路径进行引号处理，防止空格导致命令解析错误
-    let quoted_global_cflags: Vec<_> = global_cflags.iter().map(|opt| {
-        if opt.contains(' ') {
-            format!("\"{}\"", opt)
-        } else {
-            opt.clone()
-        }
-    }).collect();
-    let quoted_include_dirs: Vec<_> = global_include_dirs.iter().map(|dir| {
-        if dir.contains(' ') {
-            format!("\"{}\"", dir)
-        } else {
-            dir.clone()
-        }
-    }).collect();
-
-    let options_str = quoted_global_cflags.join(" ");
-    let includes_str = quoted_include_dirs.join(" ");
-
-    let toolchain = ToolchainConfig::from_compiler_id(&compiler_id)
-        .unwrap_or_else(|| {
-            // 如果找不到对应的编译器ID，回退到默认值，这里保持与 main.rs 一致的逻辑
-            ToolchainConfig::from_compiler_id("riscv32-v2").unwrap()
-        });
-
-    // 获取编译器的执行路径 (例如: C:\Program Files\...\riscv32-elf-gcc.exe)
-    // 这样生成的 bat 文件中可以直接调用绝对路径，避免依赖 PATH 环境变量
-    let compiler_cmd = format!("\"{}\"", toolchain.compiler_path());
-
-    // 定义宏替换闭包
-    let replace_cb_macros = |cmd: &str| -> String {
-        let mut processed = cmd.to_string();
-
-        // 1. 替换编译器变量 (现在使用的是 config.rs 中定义的真实路径)
-        processed = processed.replace("$compiler", &compiler_cmd);
-
-        // 2. 替换编译选项和头文件路径
-        processed = processed.replace("$options", &options_str);
-        processed = processed.replace("$includes", &includes_str);
-
-        // 3. 替换项目信息
-        processed = processed.replace("$(PROJECT_NAME)", &project_name);
-
-        // 4. 替换项目路径 $(PROJECT_DIR)
-        // Code::Blocks 中 $(PROJECT_DIR) 通常指 .cbp 文件所在目录
-        // 在生成的批处理中，我们通常在项目根目录运行，所以替换为当前目录
-        if processed.contains("$(PROJECT_DIR)") {
-            // 替换为 Windows 风格的当前目录引用，或者根据 cmd 上下文调整
-            // 这里简单的替换为 .\\ 即可，因为后续通常接相对路径
-            processed = processed.replace("$(PROJECT_DIR)", ".\\");
-        }
-
-        processed
-    };
-
-    // 解析ExtraCommands节点
-    if let Some(extra_commands_node) = project
-        .children()
-        .find(|n| n.tag_name().name() == "ExtraCommands")
-    {
-        for add in extra_commands_node
-            .children()
-            .filter(|n| n.tag_name().name() == "Add")
-        {
-            if let Some(before) = add.attribute("before") {
-                let trimmed_before = before.trim();
-                if !trimmed_before.is_empty() {
-                    // 应用宏替换
-                    let final_cmd = replace_cb_macros(trimmed_before);
-                    prebuild_commands.push(final_cmd);
-                }
-            }
-            if let Some(after) = add.attribute("after") {
-                let trimmed_after = after.trim();
-                if !trimmed_after.is_empty() {
-                    // 应用宏替换
-                    let final_cmd = replace_cb_macros(trimmed_after);
-                    postbuild_commands.push(final_cmd);
-                }
-            }
-        }
-    }
+    // 解析项目级 ExtraCommands 节点
+    // 命令中的宏 ($compiler、$(TARGET_*) 等) 在生成 build.ninja 时按构建所用的工具链和 target 展开
+    let ExtraCommands {
+        before: prebuild_commands,
+        after: postbuild_commands,
+        after_always: postbuild_always,
+    } = parse_extra_commands(&project);
+    // 预构建步骤的生成文件在默认 (第一个) target 的上下文中展开
+    let prebuild_outputs = parse_prebuild_outputs(&project, &macros.with_target(&targets[0]));
 
     // === 源文件和特殊文件 ===
     let mut source_files = Vec::new();
     let mut special_files = Vec::new();
-    let valid_exts: HashSet<&str> = ["c", "cpp", "C", "CPP", "S", "s"].iter().cloned().collect();
+    let valid_exts: HashSet<&str> = ["c", "cpp", "cc", "cxx", "C", "CPP", "S", "s"].iter().cloned().collect();
 
     for unit in project.children().filter(|n| n.tag_name().name() == "Unit") {
         if let Some(filename) = unit.attribute("filename") {
@@ -342,6 +499,9 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
             let mut compile = is_regular_source;
             let mut link = is_regular_source;
             let mut build_commands = Vec::new();
+            let mut unit_targets = Vec::new();
+            let mut compiler_var = None;
+            let mut weight = DEFAULT_UNIT_WEIGHT;
 
             for option in unit.children().filter(|n| n.tag_name().name() == "Option") {
                 // 检查compile属性：0关闭，1开启
@@ -354,6 +514,27 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
                     link = link_attr == "1";
                 }
 
+                // 检查target属性：Unit 所属的 Build Target，可出现多次
+                if let Some(target_attr) = option.attribute("target") {
+                    unit_targets.push(target_attr.to_string());
+                }
+
+                // 检查compilerVar属性：CC 使用 C 编译器，CPP 使用 C++ 编译器
+                if let Some(var_attr) = option.attribute("compilerVar") {
+                    compiler_var = CompilerVar::from_cb_value(var_attr);
+                    if compiler_var.is_none() {
+                        eprintln!("Warning: Unsupported compilerVar '{}' for {}", var_attr, filename);
+                    }
+                }
+
+                // 检查weight属性：权重小的 Unit 先构建
+                if let Some(weight_attr) = option.attribute("weight") {
+                    match weight_attr.trim().parse::<u32>() {
+                        Ok(value) => weight = value,
+                        Err(_) => eprintln!("Warning: Invalid weight '{}' for {}", weight_attr, filename),
+                    }
+                }
+
                 // 检查是否有buildCommand属性和compiler属性
                 if let (Some(compiler), Some(build_cmd)) = (
                     option.attribute("compiler"),
@@ -373,10 +554,19 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
             // 处理普通源文件
             if is_regular_source {
                 // 普通源文件：根据compile和link属性决定是否编译和链接
+                // 只使用为当前编译器设置的自定义构建命令
+                let build_command = build_commands
+                    .into_iter()
+                    .find(|(compiler, _)| compiler == &compiler_id)
+                    .map(|(_, build_cmd)| build_cmd);
                 source_files.push(SourceFileInfo {
                     filename: filename.to_string(),
                     compile,
                     link,
+                    targets: unit_targets,
+                    compiler_var,
+                    weight,
+                    build_command,
                 });
             } else {
                 // 处理特殊文件
@@ -402,6 +592,8 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
                         build_command,
                         compile,
                         link,
+                        targets: unit_targets,
+                        weight,
                     });
                 }
             }
@@ -424,11 +616,136 @@ pub fn parse_cbp_file(xml_content: &str) -> Result<ProjectInfo, Box<dyn std::err
         special_files,
         prebuild_commands,
         postbuild_commands,
+        postbuild_always,
+        prebuild_outputs,
+        virtual_targets: parse_virtual_targets(&project, &targets),
         targets,
         linker_type: "gcc".to_string(),
+        environment,
+        macros,
+        target_triple: parse_target_triple_override(&project),
+        model: CbpProject::from_document(&doc),
     })
 }
 
+/// 解析 <VirtualTargets><Add alias="All" targets="Debug;Test;" /></VirtualTargets>
+/// 不存在的成员 target 被忽略；与普通 target 同名或没有有效成员的别名被丢弃
+fn parse_virtual_targets(project: &roxmltree::Node, targets: &[BuildTarget]) -> Vec<VirtualTarget> {
+    let mut virtual_targets: Vec<VirtualTarget> = Vec::new();
+    let Some(node) = project.children().find(|n| n.tag_name().name() == "VirtualTargets") else {
+        return virtual_targets;
+    };
+    for add in node.children().filter(|n| n.tag_name().name() == "Add") {
+        let Some(alias) = add.attribute("alias").map(str::trim).filter(|a| !a.is_empty()) else {
+            continue;
+        };
+        if targets.iter().any(|t| t.name == alias) || virtual_targets.iter().any(|v| v.name == alias) {
+            eprintln!("Warning: Virtual target '{}' conflicts with another target, ignoring", alias);
+            continue;
+        }
+
+        let mut members: Vec<String> = Vec::new();
+        for member in add.attribute("targets").unwrap_or_default().split(';').map(str::trim) {
+            if member.is_empty() || members.iter().any(|m| m == member) {
+                continue;
+            }
+            if targets.iter().any(|t| t.name == member) {
+                members.push(member.to_string());
+            } else {
+                eprintln!("Warning: Virtual target '{}' references unknown target '{}', ignoring", alias, member);
+            }
+        }
+        if members.is_empty() {
+            eprintln!("Warning: Virtual target '{}' has no valid targets, ignoring", alias);
+            continue;
+        }
+        debug_println!("[DEBUG] Virtual target {}: {}", alias, members.join(", "));
+        virtual_targets.push(VirtualTarget {
+            name: alias.to_string(),
+            targets: members,
+        });
+    }
+    virtual_targets
+}
+
+/// 解析 <Extensions><cbp2clangd target_triple="..."/></Extensions>，为项目指定 clangd 的目标三元组
+fn parse_target_triple_override(project: &roxmltree::Node) -> Option<String> {
+    let triple = project
+        .children()
+        .filter(|n| n.tag_name().name() == "Extensions")
+        .flat_map(|n| n.children())
+        .filter(|n| n.tag_name().name() == "cbp2clangd")
+        .find_map(|n| n.attribute("target_triple"))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())?;
+    debug_println!("[DEBUG parser] Target triple override: {}", triple);
+    Some(triple.to_string())
+}
+
+/// 解析 <Extensions><cbp2clangd><prebuild_output file="..."/></cbp2clangd></Extensions>，
+/// 声明项目级预构建命令生成的文件 (如生成的头文件)，build.ninja 中作为预构建步骤的输出
+fn parse_prebuild_outputs(project: &roxmltree::Node, macros: &MacroContext) -> Vec<String> {
+    project
+        .children()
+        .filter(|n| n.tag_name().name() == "Extensions")
+        .flat_map(|n| n.children())
+        .filter(|n| n.tag_name().name() == "cbp2clangd")
+        .flat_map(|n| n.children())
+        .filter(|n| n.tag_name().name() == "prebuild_output")
+        .filter_map(|n| n.attribute("file").map(str::trim).filter(|f| !f.is_empty()))
+        .map(|file| {
+            debug_println!("[DEBUG parser] Pre-build output: {}", file);
+            macros.expand(file)
+        })
+        .collect()
+}
+
+/// 解析节点下的 <Environment><Variable name="..." value="..."/></Environment>
+/// 变量值按定义顺序展开 (可引用已有的宏和环境变量，例如 PATH=$(PATH);tools)，并注册为宏
+fn parse_environment(node: &roxmltree::Node, macros: &mut MacroContext) -> Vec<(String, String)> {
+    let mut environment = Vec::new();
+    for env_node in node.children().filter(|n| n.tag_name().name() == "Environment") {
+        for var in env_node.children().filter(|n| n.tag_name().name() == "Variable") {
+            let Some(name) = var.attribute("name").map(str::trim).filter(|n| !n.is_empty()) else {
+                eprintln!("Warning: <Environment> variable without name, skipping");
+                continue;
+            };
+            let value = macros.expand(var.attribute("value").unwrap_or_default());
+            debug_println!("[DEBUG parser] Environment variable: {}={}", name, value);
+            macros.set_env(name, &value);
+            environment.retain(|(n, _): &(String, String)| n != name);
+            environment.push((name.to_string(), value));
+        }
+    }
+    environment
+}
+
+/// 解析节点下的 <ExtraCommands>：<Add before/after="..."/> 按出现顺序保存 (宏未展开)，<Mode after="always"/> 设置 after_always
+fn parse_extra_commands(node: &roxmltree::Node) -> ExtraCommands {
+    let mut commands = ExtraCommands::default();
+    let Some(extra_commands_node) = node.children().find(|n| n.tag_name().name() == "ExtraCommands") else {
+        return commands;
+    };
+    for child in extra_commands_node.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "Add" => {
+                for (attr, list) in [("before", &mut commands.before), ("after", &mut commands.after)] {
+                    if let Some(cmd) = child.attribute(attr).map(str::trim).filter(|c| !c.is_empty()) {
+                        list.push(cmd.to_string());
+                    }
+                }
+            }
+            "Mode" => {
+                if let Some(mode) = child.attribute("after") {
+                    commands.after_always = mode == "always";
+                }
+            }
+            _ => {}
+        }
+    }
+    commands
+}
+
 /// 从编译器 flag 中解析 -march= 指令，填充 MarchInfo
 fn parse_march_flag(flag: &str, march_info: &mut crate::models::MarchInfo) {
     if !flag.starts_with("-march=") {
@@ -455,9 +772,9 @@ fn process_library_path(lib: &str) -> String {
         lib.to_string()
     } else {
         // 不带路径的库，处理前缀
-        if lib.starts_with("lib") {
+        if let Some(name) = lib.strip_prefix("lib") {
             // 去掉lib前缀，添加-l
-            format!("-l{}", &lib[3..])
+            format!("-l{}", name)
         } else {
             // 直接添加-l
             format!("-l{}", lib)
@@ -534,6 +851,34 @@ mod tests {
         assert!(debug_target.linker_libs.iter().any(|l| l.contains("m")));
     }
 
+    #[test]
+    fn test_select_target() {
+        let xml = r#"
+        <CodeBlocks_project_file>
+            <Project>
+                <Build>
+                    <Target title="Debug" />
+                    <Target title="Release" />
+                </Build>
+                <Unit filename="main.c" />
+            </Project>
+        </CodeBlocks_project_file>
+        "#;
+        let project = parse_cbp_file(xml).unwrap();
+
+        assert_eq!(project.select_target(None).unwrap().name, "Debug");
+        assert_eq!(project.select_target(Some("Release")).unwrap().name, "Release");
+
+        let err = project.select_target(Some("Test")).unwrap_err();
+        let TargetSelectError::UnknownTarget { name, available } = &err;
+        assert_eq!(name, "Test");
+        assert_eq!(available, &vec!["Debug".to_string(), "Release".to_string()]);
+        assert_eq!(
+            err.to_string(),
+            "Unknown target 'Test'. Available targets: Debug, Release"
+        );
+    }
+
     #[test]
     fn test_parse_march_extension() {
         // 这里的 XML 必须包含至少一个 Unit，否则 parse_cbp_file 会报错 "No source files..."
@@ -0,0 +1,249 @@
+use std::path::{Path, PathBuf};
+
+/// 宿主平台的路径分隔符（生成的 ninja / 脚本 / 命令行中统一使用）
+#[cfg(windows)]
+pub const PATH_SEPARATOR: char = '\\';
+#[cfg(not(windows))]
+pub const PATH_SEPARATOR: char = '/';
+
+/// 宿主平台的空设备，用于探测编译器时作为空输入文件
+#[cfg(windows)]
+pub const NULL_DEVICE: &str = "NUL";
+#[cfg(not(windows))]
+pub const NULL_DEVICE: &str = "/dev/null";
+
+/// 宿主平台的构建脚本文件名
+#[cfg(windows)]
+pub const BUILD_SCRIPT_NAME: &str = "build.bat";
+#[cfg(not(windows))]
+pub const BUILD_SCRIPT_NAME: &str = "build.sh";
+
+/// 为工具名追加宿主平台的可执行文件后缀 (Windows 上为 .exe)
+pub fn exe_name(tool: &str) -> String {
+    format!("{}{}", tool, std::env::consts::EXE_SUFFIX)
+}
+
+/// 将字符串中的路径分隔符统一为宿主平台风格
+/// Windows: `/` -> `\`；POSIX: `\` -> `/`
+pub fn to_host_separators(s: &str) -> String {
+    if cfg!(windows) {
+        s.replace('/', "\\")
+    } else {
+        s.replace('\\', "/")
+    }
+}
+
+/// 确保路径字符串以分隔符结尾，以便与文件名直接拼接
+pub fn with_trailing_separator(mut path: String) -> String {
+    if !path.ends_with('\\') && !path.ends_with('/') {
+        path.push(PATH_SEPARATOR);
+    }
+    path
+}
+
+/// 使用宿主平台分隔符拼接多段路径
+pub fn join_host(parts: &[&str]) -> String {
+    parts.join(&PATH_SEPARATOR.to_string())
+}
+
+/// 未在 default.conf 中配置 MASTER_PATH 时使用的 RV32 工具链默认安装位置
+pub fn default_toolchain_root(version_name: &str) -> String {
+    if cfg!(windows) {
+        format!("C:\\Program Files (x86)\\RV32-Toolchain\\RV32-{}", version_name)
+    } else {
+        format!("/opt/RV32-Toolchain/RV32-{}", version_name)
+    }
+}
+
+/// 便携版 Code::Blocks 的安装目录 (default.conf 与可执行文件放在一起)：
+/// cbp2clangd 自身所在目录 (与 Code::Blocks 一起分发时)，以及 PATH 中包含 codeblocks 可执行文件的目录
+pub fn codeblocks_portable_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .into_iter()
+        .collect();
+    if let Some(path) = std::env::var_os("PATH") {
+        let codeblocks = exe_name("codeblocks");
+        for dir in std::env::split_paths(&path) {
+            if dir.join(&codeblocks).is_file() && !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs
+}
+
+/// Code::Blocks 用户配置目录 (default.conf 所在目录)
+/// Windows: %APPDATA%\CodeBlocks；macOS: ~/Library/Application Support/codeblocks；
+/// 其他: $XDG_CONFIG_HOME/codeblocks (默认 ~/.config/codeblocks)，以及旧版本使用的 ~/.codeblocks
+pub fn codeblocks_user_config_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(windows) {
+        if let Ok(appdata) = std::env::var("APPDATA") {
+            dirs.push(PathBuf::from(appdata).join("CodeBlocks"));
+        }
+        return dirs;
+    }
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    if cfg!(target_os = "macos")
+        && let Some(home) = &home
+    {
+        dirs.push(home.join("Library/Application Support/codeblocks"));
+    }
+    match std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+        Some(config_home) => dirs.push(PathBuf::from(config_home).join("codeblocks")),
+        None => dirs.extend(home.iter().map(|home| home.join(".config/codeblocks"))),
+    }
+    dirs.extend(home.iter().map(|home| home.join(".codeblocks")));
+    dirs
+}
+
+/// Code::Blocks 编译器定义 (compilers/*.xml) 的默认搜索目录：用户数据目录优先，其次是安装目录
+pub fn codeblocks_compiler_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(windows) {
+        if let Ok(appdata) = std::env::var("APPDATA") {
+            dirs.push(PathBuf::from(appdata).join("CodeBlocks").join("share").join("codeblocks").join("compilers"));
+        }
+        for var in ["ProgramFiles", "ProgramFiles(x86)"] {
+            if let Ok(program_files) = std::env::var(var) {
+                dirs.push(PathBuf::from(program_files).join("CodeBlocks").join("share").join("CodeBlocks").join("compilers"));
+            }
+        }
+    } else {
+        if let Ok(home) = std::env::var("HOME") {
+            dirs.push(PathBuf::from(home).join(".local/share/codeblocks/compilers"));
+        }
+        dirs.push(PathBuf::from("/usr/local/share/codeblocks/compilers"));
+        dirs.push(PathBuf::from("/usr/share/codeblocks/compilers"));
+    }
+    dirs
+}
+
+/// 生成"删除旧文件后归档"的 ar 命令
+/// ar crs 会向已存在的归档追加成员，因此需要先删除旧文件
+pub fn archive_command(ar: &str) -> String {
+    if cfg!(windows) {
+        format!("cmd /c (if exist \"$out\" del /q \"$out\") & {} crs $out $in", ar)
+    } else {
+        format!("rm -f $out && {} crs $out $in", ar)
+    }
+}
+
+/// 生成"创建目录后写入空文件"的命令，用于没有构建命令的特殊文件
+pub fn touch_command(output_dir: &str, output_file: &str) -> String {
+    if cfg!(windows) {
+        // ninja 在 Windows 上直接 CreateProcess，需要用 cmd /c 来运行多个命令
+        format!("cmd /c (mkdir {} >nul 2>&1) & (type nul > {})", output_dir, output_file)
+    } else {
+        format!("mkdir -p {} && touch {}", output_dir, output_file)
+    }
+}
+
+/// 构建脚本中设置环境变量的语句 (build.bat: set "A=1"，build.sh: export A='1')
+pub fn env_assignment(name: &str, value: &str) -> String {
+    if cfg!(windows) {
+        format!("set \"{}={}\"", name, value)
+    } else {
+        format!("export {}={}", name, shell_quote(value))
+    }
+}
+
+/// 在设置环境变量后执行命令，用于 ninja 规则 (ninja 不支持按构建边设置环境变量)
+pub fn with_environment(vars: &[(String, String)], command: &str) -> String {
+    if vars.is_empty() {
+        return command.to_string();
+    }
+    let assignments: Vec<String> = vars.iter().map(|(name, value)| env_assignment(name, value)).collect();
+    if cfg!(windows) {
+        // ninja 在 Windows 上直接 CreateProcess，需要用 cmd /c 来串联 set 和命令
+        format!("cmd /c {} && {}", assignments.join(" && "), command)
+    } else {
+        format!("{} && {}", assignments.join(" && "), command)
+    }
+}
+
+/// 依次执行多条命令 (任一失败即停止) 后写入 stamp 文件，用于 ninja 中的预/后构建步骤
+/// 每条命令在 dir (未指定时为 ninja 的工作目录) 中单独运行 (Windows: pushd/call/popd，POSIX: 子 shell)，
+/// 命令内的 cd 不影响后续命令和 stamp 文件的位置
+pub fn command_sequence(vars: &[(String, String)], dir: Option<&str>, commands: &[String], stamp: &str) -> String {
+    let mut steps: Vec<String> = vars.iter().map(|(name, value)| env_assignment(name, value)).collect();
+    if cfg!(windows) {
+        let dir = dir.unwrap_or(".");
+        for command in commands {
+            steps.push(format!("pushd \"{}\" && call {} && popd", dir, command));
+        }
+        steps.push(format!("type nul > \"{}\"", stamp));
+        // ninja 在 Windows 上直接 CreateProcess，需要用 cmd /c 来串联多个命令
+        format!("cmd /c {}", steps.join(" && "))
+    } else {
+        let cd = dir.map(|dir| format!("cd {} && ", shell_quote(dir))).unwrap_or_default();
+        for command in commands {
+            steps.push(format!("( {}{} )", cd, command));
+        }
+        steps.push(format!("touch {}", shell_quote(stamp)));
+        steps.join(" && ")
+    }
+}
+
+/// POSIX shell 单引号转义
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// 将生成的构建脚本标记为可执行（仅 POSIX 需要）
+pub fn make_executable(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = std::fs::metadata(path)?.permissions();
+        perms.set_mode(perms.mode() | 0o755);
+        std::fs::set_permissions(path, perms)?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(windows)]
+    fn test_windows_host() {
+        assert_eq!(exe_name("riscv32-elf-gcc"), "riscv32-elf-gcc.exe");
+        assert_eq!(to_host_separators("a/b\\c"), "a\\b\\c");
+        assert_eq!(with_trailing_separator("obj/Debug".to_string()), "obj/Debug\\");
+        assert_eq!(
+            default_toolchain_root("V2"),
+            "C:\\Program Files (x86)\\RV32-Toolchain\\RV32-V2"
+        );
+        let vars = vec![("A".to_string(), "1".to_string())];
+        assert_eq!(with_environment(&vars, "make"), "cmd /c set \"A=1\" && make");
+        assert_eq!(
+            command_sequence(&vars, None, &["gen.bat".to_string()], "obj\\pre.stamp"),
+            "cmd /c set \"A=1\" && pushd \".\" && call gen.bat && popd && type nul > \"obj\\pre.stamp\""
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_posix_host() {
+        assert_eq!(exe_name("riscv32-elf-gcc"), "riscv32-elf-gcc");
+        assert_eq!(to_host_separators("a/b\\c"), "a/b/c");
+        assert_eq!(with_trailing_separator("obj\\Debug\\".to_string()), "obj\\Debug\\");
+        assert_eq!(with_trailing_separator("obj/Debug".to_string()), "obj/Debug/");
+        assert_eq!(default_toolchain_root("V2"), "/opt/RV32-Toolchain/RV32-V2");
+        assert!(archive_command("ar").starts_with("rm -f $out"));
+        assert_eq!(env_assignment("MSG", "it's"), "export MSG='it'\\''s'");
+        let vars = vec![("A".to_string(), "1".to_string())];
+        assert_eq!(with_environment(&vars, "make"), "export A='1' && make");
+        assert_eq!(with_environment(&[], "make"), "make");
+        assert_eq!(
+            command_sequence(&vars, Some("app"), &["./gen.sh".to_string(), "cd out; ls".to_string()], "obj/pre.stamp"),
+            "export A='1' && ( cd 'app' && ./gen.sh ) && ( cd 'app' && cd out; ls ) && touch 'obj/pre.stamp'"
+        );
+    }
+}
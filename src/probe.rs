@@ -0,0 +1,136 @@
+use crate::debug_println;
+use crate::platform::NULL_DEVICE;
+use crate::utils::get_clean_absolute_path;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+/// 通过运行编译器探测到的系统配置
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompilerProbe {
+    pub system_includes: Vec<String>,    // #include <...> 搜索路径，按编译器的搜索顺序
+    pub target_triple: Option<String>,   // 编译器内置的目标三元组 (-v 输出中的 Target:)
+    pub defines: Vec<(String, String)>,  // 内置宏定义 (-dM)
+}
+
+/// 每个编译器路径只探测一次 (工作区模式下多个项目共享同一工具链)
+static PROBE_CACHE: OnceLock<Mutex<HashMap<String, Option<CompilerProbe>>>> = OnceLock::new();
+
+/// 运行 `<compiler> -E -dM -v -x c <null>` 探测系统 include 路径、目标三元组和内置宏，结果按编译器路径缓存
+/// 探测失败时输出警告并返回 None (同一编译器不会重复尝试)
+pub fn probe_compiler_cached(compiler: &str) -> Option<CompilerProbe> {
+    let cache = PROBE_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+    cache
+        .entry(compiler.to_string())
+        .or_insert_with(|| match probe_compiler(compiler) {
+            Ok(probe) => Some(probe),
+            Err(e) => {
+                eprintln!("Warning: Failed to probe compiler {}: {}", compiler, e);
+                None
+            }
+        })
+        .clone()
+}
+
+/// 运行编译器并解析输出 (不使用缓存)
+pub fn probe_compiler(compiler: &str) -> Result<CompilerProbe, Box<dyn std::error::Error>> {
+    debug_println!("[DEBUG probe] Probing compiler: {} -E -dM -v -x c {}", compiler, NULL_DEVICE);
+    let output = Command::new(compiler)
+        .args(["-E", "-dM", "-v", "-x", "c", NULL_DEVICE])
+        .output()?;
+    if !output.status.success() {
+        return Err(format!("compiler exited with {}", output.status).into());
+    }
+
+    // -dM 的宏定义输出到 stdout，-v 的搜索路径和 Target 输出到 stderr
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let probe = parse_probe_output(&stdout, &stderr);
+    if probe.system_includes.is_empty() {
+        return Err("no '#include <...> search starts here' list in compiler output".into());
+    }
+    debug_println!(
+        "[DEBUG probe] Found {} system include dir(s), target {:?}, {} define(s)",
+        probe.system_includes.len(),
+        probe.target_triple,
+        probe.defines.len()
+    );
+    Ok(probe)
+}
+
+/// 解析编译器的 -dM (stdout) 和 -v (stderr) 输出
+pub fn parse_probe_output(stdout: &str, stderr: &str) -> CompilerProbe {
+    let mut probe = CompilerProbe::default();
+
+    let mut in_system_list = false;
+    for line in stderr.lines() {
+        if line.starts_with("#include <...> search starts here:") {
+            in_system_list = true;
+        } else if line.starts_with("End of search list.") {
+            in_system_list = false;
+        } else if in_system_list {
+            // 路径行以空格缩进；gcc 输出中常带有 "bin/../lib" 这样的相对段，先清理
+            let dir = line.trim().trim_end_matches(" (framework directory)");
+            if !dir.is_empty() {
+                let clean = get_clean_absolute_path(Path::new(""), Path::new(dir));
+                probe.system_includes.push(clean.to_string_lossy().into_owned());
+            }
+        } else if let Some(target) = line.strip_prefix("Target: ") {
+            probe.target_triple = Some(target.trim().to_string());
+        }
+    }
+
+    for line in stdout.lines() {
+        if let Some(define) = line.strip_prefix("#define ") {
+            let (name, value) = define.split_once(' ').unwrap_or((define, ""));
+            probe.defines.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    probe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe_output() {
+        let stderr = "Using built-in specs.
+COLLECT_GCC=riscv32-elf-gcc
+Target: riscv32-elf
+gcc version 10.2.0 (GCC)
+#include \"...\" search starts here:
+#include <...> search starts here:
+ /opt/RV32/bin/../lib/gcc/riscv32-elf/10.2.1/include
+ /opt/RV32/bin/../lib/gcc/riscv32-elf/10.2.1/include-fixed
+ /opt/RV32/riscv32-elf/include
+End of search list.
+";
+        let stdout = "#define __riscv 1\n#define __riscv_xlen 32\n#define __GNUC__ 10\n#define __STDC__\n";
+
+        let probe = parse_probe_output(stdout, stderr);
+        assert_eq!(probe.target_triple.as_deref(), Some("riscv32-elf"));
+        let expected: Vec<String> = [
+            "/opt/RV32/lib/gcc/riscv32-elf/10.2.1/include",
+            "/opt/RV32/lib/gcc/riscv32-elf/10.2.1/include-fixed",
+            "/opt/RV32/riscv32-elf/include",
+        ]
+        .iter()
+        .map(|p| get_clean_absolute_path(Path::new(""), Path::new(p)).to_string_lossy().into_owned())
+        .collect();
+        assert_eq!(probe.system_includes, expected);
+        assert_eq!(probe.defines.len(), 4);
+        assert_eq!(probe.defines[1], ("__riscv_xlen".to_string(), "32".to_string()));
+        assert_eq!(probe.defines[3], ("__STDC__".to_string(), String::new()));
+    }
+
+    #[test]
+    fn test_probe_missing_compiler_is_cached() {
+        assert!(probe_compiler("cbp2clangd-no-such-compiler").is_err());
+        assert_eq!(probe_compiler_cached("cbp2clangd-no-such-compiler"), None);
+        assert_eq!(probe_compiler_cached("cbp2clangd-no-such-compiler"), None);
+    }
+}
@@ -0,0 +1,287 @@
+/// 工具链中的各个工具
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Cc,
+    Cxx,
+    As,
+    Ar,
+    Ld,
+    Link,
+    Objcopy,
+    Size,
+}
+
+/// 各工具的可执行文件名 (含工具前缀，不含 .exe 后缀)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolNames {
+    pub cc: String,
+    pub cxx: String,
+    pub asm: String,  // 汇编驱动，通过编译器驱动以支持 .S 的预处理
+    pub ar: String,   // 静态库归档 (Code::Blocks 的 LIB_LINKER)
+    pub ld: String,   // --linker ld 时直接调用的链接器
+    pub link: String, // 链接可执行文件和动态库的驱动 (Code::Blocks 的 LINKER)
+    pub objcopy: String,
+    pub size: String,
+}
+
+impl ToolNames {
+    /// GNU binutils + gcc 的标准工具名
+    pub fn gnu(prefix: &str) -> Self {
+        let name = |tool: &str| format!("{}{}", prefix, tool);
+        ToolNames {
+            cc: name("gcc"),
+            cxx: name("g++"),
+            asm: name("gcc"),
+            ar: name("ar"),
+            ld: name("ld"),
+            link: name("gcc"),
+            objcopy: name("objcopy"),
+            size: name("size"),
+        }
+    }
+
+    /// LLVM 工具链的标准工具名
+    pub fn llvm() -> Self {
+        ToolNames {
+            cc: "clang".to_string(),
+            cxx: "clang++".to_string(),
+            asm: "clang".to_string(),
+            ar: "llvm-ar".to_string(),
+            ld: "ld.lld".to_string(),
+            link: "clang".to_string(),
+            objcopy: "llvm-objcopy".to_string(),
+            size: "llvm-size".to_string(),
+        }
+    }
+
+    pub fn get(&self, tool: Tool) -> &str {
+        match tool {
+            Tool::Cc => &self.cc,
+            Tool::Cxx => &self.cxx,
+            Tool::As => &self.asm,
+            Tool::Ar => &self.ar,
+            Tool::Ld => &self.ld,
+            Tool::Link => &self.link,
+            Tool::Objcopy => &self.objcopy,
+            Tool::Size => &self.size,
+        }
+    }
+}
+
+/// 编译器的选项开关 (Code::Blocks options_<id>.xml 的 <Switch>)
+/// 生成的选项固定使用 GNU 风格 (-I/-L/-l/-D)，只有对象文件扩展名来自编译器定义
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompilerSwitches {
+    pub object_extension: String, // o
+}
+
+impl Default for CompilerSwitches {
+    fn default() -> Self {
+        CompilerSwitches {
+            object_extension: "o".to_string(),
+        }
+    }
+}
+
+/// 未配置 MASTER_PATH 时的工具链根目录
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefaultRoot {
+    /// RV32 工具链按版本安装在各自的目录下 (RV32-Toolchain/RV32-V2)
+    Rv32Versioned,
+    /// 固定的安装位置
+    Fixed(String),
+}
+
+/// 数据驱动的工具链描述：工具前缀、各工具名、系统 include 布局和默认安装位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolchainDef {
+    pub tool_prefix: String,                // riscv32-elf-gcc 中的 riscv32-elf- (用于推断目标三元组)
+    pub tools: ToolNames,
+    pub include_layout: Vec<String>,        // 相对工具链根目录的系统 include 路径，{version} 为编译器版本
+    pub default_root: DefaultRoot,
+    pub switches: CompilerSwitches,
+    pub version_name: Option<String>,       // RV32 工具链的版本名, e.g., "V2"
+    pub gcc_version: Option<String>,        // 已知的编译器版本；未知时从 include 布局中的目录探测
+}
+
+/// 内置工具链的编译器 ID (Code::Blocks 的默认编译器 + RV32 系列)
+pub const BUILTIN_TOOLCHAIN_IDS: &[&str] = &[
+    "riscv32",
+    "riscv32-v2",
+    "riscv32-v3",
+    "gcc",
+    "arm-elf-gcc",
+    "clang",
+    "msp430-gcc",
+    "avr-gcc",
+];
+
+impl ToolchainDef {
+    /// RV32 系列工具链 (riscv32-elf-*)
+    pub fn rv32(version_name: &str, gcc_version: Option<&str>) -> Self {
+        ToolchainDef {
+            tool_prefix: "riscv32-elf-".to_string(),
+            tools: ToolNames::gnu("riscv32-elf-"),
+            include_layout: cross_gcc_layout("riscv32-elf"),
+            default_root: DefaultRoot::Rv32Versioned,
+            switches: CompilerSwitches::default(),
+            version_name: Some(version_name.to_string()),
+            gcc_version: gcc_version.map(str::to_string),
+        }
+    }
+
+    /// 使用 GNU 工具名的 gcc 工具链
+    fn gnu(tool_prefix: &str, include_layout: Vec<String>, windows_root: &str) -> Self {
+        ToolchainDef {
+            tool_prefix: tool_prefix.to_string(),
+            tools: ToolNames::gnu(tool_prefix),
+            include_layout,
+            default_root: DefaultRoot::Fixed(host_root(windows_root)),
+            switches: CompilerSwitches::default(),
+            version_name: None,
+            gcc_version: None,
+        }
+    }
+
+    /// 内置的工具链描述
+    pub fn builtin(compiler_id: &str) -> Option<Self> {
+        let def = match compiler_id {
+            "riscv32" => Self::rv32("V1", Some("6.1.0")),
+            "riscv32-v2" => Self::rv32("V2", Some("10.2.0")),
+            "riscv32-v3" => Self::rv32("V3", Some("14.2.0")),
+            // 宿主 gcc / clang 自带系统头文件搜索路径，clangd 可以自行找到；需要精确路径时使用 --probe-compiler
+            "gcc" => Self::gnu("", Vec::new(), "C:\\MinGW"),
+            "clang" => ToolchainDef {
+                tool_prefix: String::new(),
+                tools: ToolNames::llvm(),
+                include_layout: vec!["lib/clang/{version}/include".to_string()],
+                default_root: DefaultRoot::Fixed(host_root("C:\\Program Files\\LLVM")),
+                switches: CompilerSwitches::default(),
+                version_name: None,
+                gcc_version: None,
+            },
+            "arm-elf-gcc" => Self::gnu("arm-elf-", cross_gcc_layout("arm-elf"), "C:\\Program Files\\GNUARM"),
+            "msp430-gcc" => Self::gnu("msp430-", cross_gcc_layout("msp430"), "C:\\mspgcc"),
+            "avr-gcc" => Self::gnu("avr-", cross_gcc_layout("avr"), "C:\\WinAVR"),
+            _ => return None,
+        };
+        Some(def)
+    }
+
+    /// 由 C 编译器名推断的 gcc 工具链 (arm-none-eabi-gcc -> 前缀 arm-none-eabi-)，用于 Code::Blocks 编译器定义
+    pub fn from_c_compiler(c_compiler: &str) -> Self {
+        let prefix = c_compiler.strip_suffix("gcc").unwrap_or_default();
+        let layout = match prefix.strip_suffix('-') {
+            Some(target) if !target.is_empty() => cross_gcc_layout(target),
+            _ => Vec::new(),
+        };
+        Self::gnu(prefix, layout, "C:\\MinGW")
+    }
+
+    /// 不在内置表中的编译器 ID (来自 default.conf) 的工具链描述
+    /// riscv32-* 按 RV32 系列处理 (如 riscv32-v4)，其他按宿主 gcc 处理
+    pub fn fallback(compiler_id: &str) -> Self {
+        if compiler_id.starts_with("riscv32") {
+            Self::rv32(&derive_version_name(compiler_id), None)
+        } else {
+            Self::gnu("", Vec::new(), "C:\\MinGW")
+        }
+    }
+}
+
+/// 交叉编译 gcc 的标准 include 布局
+fn cross_gcc_layout(target: &str) -> Vec<String> {
+    vec![
+        format!("lib/gcc/{}/{{version}}/include", target),
+        format!("lib/gcc/{}/{{version}}/include-fixed", target),
+        format!("{}/include", target),
+    ]
+}
+
+/// 非 Windows 上的工具链都假定安装在 /usr 下
+fn host_root(windows_root: &str) -> String {
+    if cfg!(windows) {
+        windows_root.to_string()
+    } else {
+        "/usr".to_string()
+    }
+}
+
+/// 从 compiler_id 推导 version_name
+/// 例如 "riscv32-v4" -> "V4"，"riscv32" -> "V1"
+fn derive_version_name(compiler_id: &str) -> String {
+    if let Some(dash_pos) = compiler_id.rfind('-') {
+        let suffix = &compiler_id[dash_pos + 1..];
+        // 如果后缀以 'v' 开头，提取版本号
+        if let Some(stripped) = suffix.strip_prefix('v') {
+            format!("V{}", stripped)
+        } else {
+            format!("V{}", suffix)
+        }
+    } else {
+        "V1".to_string()
+    }
+}
+
+/// 在目录下查找版本号最高的子目录 (lib/gcc/avr/ 下的 5.4.0、7.3.0 -> 7.3.0)
+pub fn detect_version_dir(dir: &str) -> Option<String> {
+    let parse = |name: &str| -> Option<Vec<u32>> { name.split('.').map(|p| p.parse().ok()).collect() };
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            parse(&name).map(|version| (version, name))
+        })
+        .max()
+        .map(|(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_toolchains() {
+        for id in BUILTIN_TOOLCHAIN_IDS {
+            assert!(ToolchainDef::builtin(id).is_some(), "{} should be built in", id);
+        }
+        assert!(ToolchainDef::builtin("sdcc").is_none());
+
+        let avr = ToolchainDef::builtin("avr-gcc").unwrap();
+        assert_eq!(avr.tool_prefix, "avr-");
+        assert_eq!(avr.tools.get(Tool::Objcopy), "avr-objcopy");
+        assert_eq!(avr.include_layout[2], "avr/include");
+
+        let clang = ToolchainDef::builtin("clang").unwrap();
+        assert_eq!(clang.tool_prefix, "");
+        assert_eq!(clang.tools.get(Tool::Cxx), "clang++");
+        assert_eq!(clang.tools.get(Tool::Ar), "llvm-ar");
+
+        // default.conf 中新增的 RV32 版本沿用 RV32 布局
+        let v4 = ToolchainDef::fallback("riscv32-v4");
+        assert_eq!(v4.tool_prefix, "riscv32-elf-");
+        assert_eq!(v4.version_name.as_deref(), Some("V4"));
+        assert_eq!(ToolchainDef::fallback("my-gcc").tool_prefix, "");
+    }
+
+    #[test]
+    fn test_derive_version_name() {
+        assert_eq!(derive_version_name("riscv32-v4"), "V4");
+        assert_eq!(derive_version_name("riscv32-v10"), "V10");
+        assert_eq!(derive_version_name("riscv32"), "V1");
+        assert_eq!(derive_version_name("riscv32-custom"), "Vcustom");
+    }
+
+    #[test]
+    fn test_detect_version_dir() {
+        let dir = std::env::temp_dir().join(format!("cbp2clangd_version_dir_{}", std::process::id()));
+        for name in ["5.4.0", "10.2.0", "7.3.0", "plugin"] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        assert_eq!(detect_version_dir(&dir.to_string_lossy()).as_deref(), Some("10.2.0"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(detect_version_dir(&dir.to_string_lossy()), None);
+    }
+}
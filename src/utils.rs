@@ -1,12 +1,17 @@
+#[cfg(windows)]
 use std::ffi::{OsStr, OsString};
+#[cfg(windows)]
 use std::os::windows::ffi::{OsStrExt, OsStringExt};
 use std::path::{Component, Path, PathBuf};
 
 // Windows API相关导入
+#[cfg(windows)]
 use windows_sys::Win32::Foundation::GetLastError;
+#[cfg(windows)]
 use windows_sys::Win32::Storage::FileSystem::GetShortPathNameW;
 
 // Windows MAX_PATH常量定义
+#[cfg(windows)]
 const MAX_PATH: u32 = 260;
 
 // 全局调试控制标志，默认关闭
@@ -76,6 +81,7 @@ pub fn compute_absolute_path(path: &Path) -> std::io::Result<PathBuf> {
 
 /// 将路径转换为Windows 8.3短文件名格式
 /// 如果路径不包含空格或转换失败，则返回原始路径
+#[cfg(windows)]
 pub fn get_short_path<P: AsRef<Path>>(path: P) -> Result<String, Box<dyn std::error::Error>> {
     let path = path.as_ref();
     let path_str = path.to_string_lossy();
@@ -186,6 +192,15 @@ pub fn get_short_path<P: AsRef<Path>>(path: P) -> Result<String, Box<dyn std::er
     Ok(short_path)
 }
 
+/// POSIX 平台没有 8.3 短文件名，直接返回原始路径
+/// 含空格的路径由调用方通过 quote_if_needed / escape_ninja_path 处理
+#[cfg(not(windows))]
+pub fn get_short_path<P: AsRef<Path>>(path: P) -> Result<String, Box<dyn std::error::Error>> {
+    let path_str = path.as_ref().to_string_lossy().to_string();
+    debug_println!("[DEBUG utils] get_short_path is a no-op on this host: {}", path_str);
+    Ok(path_str)
+}
+
 /// 辅助函数：如果路径包含空格，则用引号包裹
 pub fn quote_if_needed(path: &str) -> String {
     if path.contains(' ') {
@@ -268,6 +283,14 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(not(windows))]
+    fn test_short_path_posix_passthrough() {
+        // POSIX 上即使包含空格也原样返回
+        let path = "/opt/My Toolchain/bin/gcc";
+        assert_eq!(get_short_path(path).unwrap(), path);
+    }
+
     #[test]
     fn test_compute_absolute_path() {
         let p = Path::new("test/../src/main.rs");
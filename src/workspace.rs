@@ -0,0 +1,145 @@
+use crate::debug_println;
+use crate::platform::to_host_separators;
+use roxmltree::Document;
+use std::path::{Path, PathBuf};
+
+/// Code::Blocks 工作区 (.workspace) 信息
+#[derive(Debug, Default)]
+pub struct WorkspaceInfo {
+    pub title: String,                   // 工作区标题
+    pub projects: Vec<WorkspaceProject>, // 成员项目，按文件中的顺序
+}
+
+/// 工作区中的单个成员项目
+#[derive(Debug, Default, PartialEq)]
+pub struct WorkspaceProject {
+    pub filename: String,     // .cbp 路径 (相对于 .workspace 所在目录)
+    pub active: bool,         // 是否为 Code::Blocks 中的活动项目 (active="1")
+    pub depends: Vec<String>, // 依赖的项目 (<Depends filename=...>，相对于 .workspace 所在目录)
+}
+
+impl WorkspaceProject {
+    /// 计算项目文件的路径 (基于 .workspace 所在目录)
+    pub fn path(&self, workspace_dir: &Path) -> PathBuf {
+        workspace_dir.join(to_host_separators(&self.filename))
+    }
+}
+
+impl WorkspaceInfo {
+    /// 按转换顺序返回成员项目：活动项目放在最后，
+    /// 使工作区 .clangd 的公共 CompileFlags 以活动项目为准
+    pub fn projects_in_convert_order(&self) -> Vec<&WorkspaceProject> {
+        let (active, others): (Vec<_>, Vec<_>) = self.projects.iter().partition(|p| p.active);
+        others.into_iter().chain(active).collect()
+    }
+
+    /// 查找 <Depends> 所引用的成员项目 (按解析后的路径比较，兼容不同的分隔符)
+    pub fn find_project(&self, filename: &str, workspace_dir: &Path) -> Option<&WorkspaceProject> {
+        let wanted = workspace_dir.join(to_host_separators(filename));
+        self.projects.iter().find(|p| p.path(workspace_dir) == wanted)
+    }
+}
+
+/// 解析 Code::Blocks 工作区文件
+pub fn parse_workspace_file(xml_content: &str) -> Result<WorkspaceInfo, Box<dyn std::error::Error>> {
+    let doc = Document::parse(xml_content)?;
+    let root = doc.root_element();
+
+    if root.tag_name().name() != "CodeBlocks_workspace_file" {
+        return Err("Not a Code::Blocks workspace file (missing <CodeBlocks_workspace_file>)".into());
+    }
+
+    let workspace = root
+        .children()
+        .find(|n| n.tag_name().name() == "Workspace")
+        .ok_or("No <Workspace> element found")?;
+
+    let title = workspace.attribute("title").unwrap_or("Workspace").to_string();
+    println!("Workspace: {}", title);
+
+    let mut projects = Vec::new();
+    for project in workspace.children().filter(|n| n.tag_name().name() == "Project") {
+        let Some(filename) = project.attribute("filename") else {
+            eprintln!("Warning: <Project> without filename in workspace, skipping");
+            continue;
+        };
+        let active = project.attribute("active") == Some("1");
+        let depends: Vec<String> = project
+            .children()
+            .filter(|n| n.tag_name().name() == "Depends")
+            .filter_map(|n| n.attribute("filename"))
+            .map(str::to_string)
+            .collect();
+        debug_println!(
+            "[DEBUG workspace] Found project: {} (active: {}, depends: {:?})",
+            filename,
+            active,
+            depends
+        );
+        projects.push(WorkspaceProject {
+            filename: filename.to_string(),
+            active,
+            depends,
+        });
+    }
+
+    if projects.is_empty() {
+        return Err("No projects found in workspace.".into());
+    }
+
+    Ok(WorkspaceInfo { title, projects })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_workspace_file() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<CodeBlocks_workspace_file>
+    <Workspace title="firmware">
+        <Project filename="app/app.cbp" active="1">
+            <Depends filename="libs/driver/driver.cbp" />
+            <Depends filename="libs/util/util.cbp" />
+        </Project>
+        <Project filename="libs\driver\driver.cbp" />
+        <Project filename="libs/util/util.cbp" />
+    </Workspace>
+</CodeBlocks_workspace_file>"#;
+
+        let workspace = parse_workspace_file(xml).unwrap();
+        assert_eq!(workspace.title, "firmware");
+        assert_eq!(workspace.projects.len(), 3);
+        assert!(workspace.projects[0].active);
+        assert!(!workspace.projects[1].active);
+        assert_eq!(workspace.projects[0].depends, vec!["libs/driver/driver.cbp", "libs/util/util.cbp"]);
+        assert!(workspace.projects[1].depends.is_empty());
+
+        // <Depends> 中的路径与 <Project> 的分隔符风格不同也能匹配
+        let dep = workspace.find_project(&workspace.projects[0].depends[0], Path::new("ws"));
+        assert_eq!(dep, Some(&workspace.projects[1]));
+        assert!(workspace.find_project("missing.cbp", Path::new("ws")).is_none());
+
+        // 反斜杠路径按宿主平台解析
+        let driver = workspace.projects[1].path(Path::new("ws"));
+        assert_eq!(driver, Path::new("ws").join("libs").join("driver").join("driver.cbp"));
+
+        // 活动项目最后转换
+        let order: Vec<&str> = workspace
+            .projects_in_convert_order()
+            .iter()
+            .map(|p| p.filename.as_str())
+            .collect();
+        assert_eq!(order, vec!["libs\\driver\\driver.cbp", "libs/util/util.cbp", "app/app.cbp"]);
+    }
+
+    #[test]
+    fn test_parse_workspace_file_errors() {
+        assert!(parse_workspace_file("<CodeBlocks_project_file />").is_err());
+        assert!(
+            parse_workspace_file("<CodeBlocks_workspace_file><Workspace /></CodeBlocks_workspace_file>")
+                .is_err()
+        );
+    }
+}
@@ -1,6 +1,17 @@
-use cbp2clangd::{ToolchainConfig, generate_ninja_build, parse_cbp_file};
+use cbp2clangd::{
+    CbCompilerConfig, CbGlobalVariable, NinjaDependency, ToolchainConfig, WorkspaceClangdProject, WorkspaceNinjaProject,
+    generate_build_script, generate_workspace_clangd,
+    CompileCommandsFormat, generate_clangd_config, generate_clangd_fragment, generate_compile_commands,
+    generate_compile_commands_with_format, merge_compile_commands_into, generate_ninja_build, generate_subninja_build, generate_workspace_ninja,
+    parse_cbp_file, parse_cbp_file_with_path,
+};
 use std::path::Path;
 
+/// 将测试中以 `/` 书写的路径转换为宿主平台的分隔符 (Windows: `\`，POSIX: `/`)
+fn host_path(path: &str) -> String {
+    path.replace('/', std::path::MAIN_SEPARATOR_STR)
+}
+
 #[test]
 fn test_generate_ninja_build_for_static_lib() {
     // 创建一个简单的XML内容，包含静态库输出
@@ -89,8 +100,8 @@ fn test_generate_ninja_build_for_executable() {
 
     // 检查生成的ninja内容是否包含预期的规则和目标
     assert!(ninja_content.contains("rule link"));
-    assert!(ninja_content.contains("build Output\\bin\\chatbot.elf: link"));
-    assert!(ninja_content.contains("default Output\\bin\\chatbot.elf"));
+    assert!(ninja_content.contains(&host_path("build Output/bin/chatbot.elf: link")));
+    assert!(ninja_content.contains(&host_path("default Output/bin/chatbot.elf")));
 }
 
 #[test]
@@ -189,14 +200,14 @@ fn test_target_output_dir_path_separator() {
     // 检查 TARGET_OUTPUT_DIR 变量是否被正确替换，并且路径包含分隔符
     // 确保输出路径是 "Output\\bin\\app.rv32" 而不是 "Output\\binapp.rv32"
     assert!(
-        ninja_content.contains(r"Output\bin\app.rv32"),
+        ninja_content.contains(&host_path("Output/bin/app.rv32")),
         "TARGET_OUTPUT_DIR 应该被正确替换，路径应该包含分隔符"
     );
 
     // 检查 TARGET_OBJECT_DIR 变量是否被正确替换，并且路径包含分隔符
     // 确保对象路径是 "obj\\Debug\\" 而不是 "obj\\Debug"（如果在特殊命令中使用的话）
     assert!(
-        ninja_content.contains(r"obj\Debug\"),
+        ninja_content.contains(&host_path("obj/Debug/")),
         "TARGET_OBJECT_DIR 应该被正确替换，路径应该包含分隔符"
     );
 }
@@ -239,16 +250,1168 @@ fn test_special_files_as_implicit_deps() {
 
     // 检查特殊文件的编译规则是否生成
     assert!(ninja_content.contains("rule special_src_special_asm"), "应该生成特殊文件的编译规则");
-    assert!(ninja_content.contains("as src\\special.asm -o"), "应该生成特殊文件的编译命令");
+    assert!(ninja_content.contains(&host_path("as src/special.asm -o")), "应该生成特殊文件的编译命令");
 
     // 检查链接规则是否包含特殊文件作为隐式依赖
     assert!(ninja_content.contains("|"), "链接规则应该包含隐式依赖分隔符");
 
     // 检查普通源文件的编译规则
     assert!(ninja_content.contains("rule cc"), "应该生成普通源文件的编译规则");
-    assert!(ninja_content.contains("build Output\\obj\\Debug\\main.o: cc src\\main.c"), "应该生成普通源文件的编译命令");
+    assert!(ninja_content.contains(&host_path("build Output/obj/Debug/main.o: cc src/main.c")), "应该生成普通源文件的编译命令");
 
     // 检查最终链接规则
-    assert!(ninja_content.contains("build Output\\bin\\test.elf: link"), "应该生成链接规则");
-    assert!(ninja_content.contains("Output\\obj\\Debug\\main.o"), "链接规则应该包含普通源文件的目标文件");
+    assert!(ninja_content.contains(&host_path("build Output/bin/test.elf: link")), "应该生成链接规则");
+    assert!(ninja_content.contains(&host_path("Output/obj/Debug/main.o")), "链接规则应该包含普通源文件的目标文件");
+}
+
+#[test]
+fn test_generate_ninja_build_for_multiple_targets() {
+    // 每个 target 使用独立的对象目录，并生成以 target 名称命名的 phony 目标
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="Output/Debug/app.elf" />
+                <Option object_output="obj/Debug/" />
+                <Compiler>
+                    <Add option="-g" />
+                </Compiler>
+            </Target>
+            <Target title="Release">
+                <Option output="Output/Release/app.elf" />
+                <Option object_output="obj/Release/" />
+                <Compiler>
+                    <Add option="-O2" />
+                </Compiler>
+            </Target>
+        </Build>
+        <Unit filename="src/main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    println!("Generated ninja content for multiple targets:\n{}", ninja_content);
+
+    // 共享规则只输出一次
+    assert_eq!(ninja_content.matches("rule cc\n").count(), 1);
+    assert_eq!(ninja_content.matches("rule link\n").count(), 1);
+
+    // 各 target 的对象文件位于各自的 object_output 下，并使用各自的编译选项
+    assert!(ninja_content.contains(&host_path("build obj/Debug/main.o: cc src/main.c\n  flags = -g")));
+    assert!(ninja_content.contains(&host_path("build obj/Release/main.o: cc src/main.c\n  flags = -O2")));
+    assert!(ninja_content.contains(&host_path("build Output/Debug/app.elf: link obj/Debug/main.o")));
+    assert!(ninja_content.contains(&host_path("build Output/Release/app.elf: link obj/Release/main.o")));
+
+    // 每个 target 一个 phony 目标，默认构建第一个 target
+    assert!(ninja_content.contains(&host_path("build Debug: phony Output/Debug/app.elf")));
+    assert!(ninja_content.contains(&host_path("build Release: phony Output/Release/app.elf")));
+    assert!(ninja_content.contains(&host_path("default Output/Debug/app.elf")));
+}
+
+#[test]
+fn test_unit_target_membership() {
+    // 带 <Option target="..."/> 的 Unit 只编译进所列的 target
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Board">
+                <Option output="Output/Board/app.elf" />
+                <Option object_output="obj/Board/" />
+            </Target>
+            <Target title="Sim">
+                <Option output="Output/Sim/app.elf" />
+                <Option object_output="obj/Sim/" />
+            </Target>
+        </Build>
+        <Unit filename="src/main.c" />
+        <Unit filename="src/board.c">
+            <Option target="Board" />
+        </Unit>
+        <Unit filename="src/sim.c">
+            <Option target="Sim" />
+        </Unit>
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    println!("Generated ninja content for unit membership:\n{}", ninja_content);
+
+    assert!(ninja_content.contains(&host_path("build obj/Board/board.o: cc src/board.c")));
+    assert!(ninja_content.contains(&host_path("build obj/Sim/sim.o: cc src/sim.c")));
+    assert!(!ninja_content.contains(&host_path("obj/Sim/board.o")), "board.c 不应进入 Sim target");
+    assert!(!ninja_content.contains(&host_path("obj/Board/sim.o")), "sim.c 不应进入 Board target");
+
+    // compile_commands.json 同样只包含所属 target 的文件
+    let sim = project_info.select_target(Some("Sim")).unwrap();
+    let commands = generate_compile_commands(&project_info, Path::new("."), &toolchain, Some(sim));
+    let files: Vec<&str> = commands.iter().map(|c| c.file.as_str()).collect();
+    assert_eq!(files.len(), 2);
+    assert!(files.iter().any(|f| f.ends_with("main.c")));
+    assert!(files.iter().any(|f| f.ends_with("sim.c")));
+}
+
+#[test]
+fn test_generate_workspace_subninja_with_dependency() {
+    // 工作区模式下项目路径加上项目目录前缀，依赖项目的静态库作为链接的隐式依赖
+    let lib_xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="driver" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="out/driver.a" />
+                <Option object_output="obj/Debug/" />
+            </Target>
+        </Build>
+        <Unit filename="src/driver.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+    let app_xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="out/app.elf" />
+                <Option object_output="obj/Debug/" />
+            </Target>
+        </Build>
+        <Compiler>
+            <Add directory="include" />
+        </Compiler>
+        <Unit filename="src/main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let lib_info = parse_cbp_file(lib_xml).unwrap();
+    let app_info = parse_cbp_file(app_xml).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let workspace_root = std::env::temp_dir().join("cbp2clangd_ws");
+    let lib_dir = workspace_root.join("libs").join("driver");
+    let app_dir = workspace_root.join("app");
+
+    let dependencies = [NinjaDependency {
+        project_info: &lib_info,
+        project_dir: &lib_dir,
+    }];
+    let app_ninja = generate_subninja_build(&app_info, &app_dir, &toolchain, &workspace_root, &dependencies).unwrap();
+    println!("Generated subninja content:\n{}", app_ninja);
+
+    assert!(app_ninja.contains(&host_path("build app/obj/Debug/main.o: cc app/src/main.c")));
+    assert!(app_ninja.contains(&host_path("-Iapp/include")));
+    assert!(app_ninja.contains(&host_path("build app/out/app.elf: link app/obj/Debug/main.o | libs/driver/out/libdriver.a")));
+    assert!(app_ninja.contains(&host_path("build app/Debug: phony app/out/app.elf")));
+    assert!(!app_ninja.contains("default "), "默认目标由顶层 build.ninja 决定");
+
+    let projects = [
+        WorkspaceNinjaProject { project_dir: &lib_dir, subninja_file: "build.workspace.ninja", default_target: "Debug" },
+        WorkspaceNinjaProject { project_dir: &app_dir, subninja_file: "build.workspace.ninja", default_target: "Debug" },
+    ];
+    let top_ninja = generate_workspace_ninja("firmware", &workspace_root, &projects);
+    assert!(top_ninja.contains(&host_path("subninja libs/driver/build.workspace.ninja\nsubninja app/build.workspace.ninja\n")));
+    assert!(top_ninja.contains(&host_path("default libs/driver/Debug app/Debug")));
+}
+
+#[test]
+fn test_subninja_prefixes_link_paths() {
+    // ninja 在工作区根目录执行，编译和链接选项中项目内的相对路径 (-L/-T/-Map/-include) 都需要加上项目目录前缀
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="out/app.elf" />
+                <Option object_output="obj/Debug/" />
+            </Target>
+        </Build>
+        <Compiler>
+            <Add option="-include cfg.h" />
+            <Add option="-imacros" />
+            <Add option="macros.h" />
+        </Compiler>
+        <Linker>
+            <Add option="-T link.ld" />
+            <Add option="-Wl,-Map=$(TARGET_OUTPUT_DIR)app.map,--gc-sections" />
+            <Add option="-Wl,-Ttext=0x0" />
+            <Add library="bsp" />
+            <Add directory="lib" />
+        </Linker>
+        <Unit filename="src/main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let workspace_root = std::env::temp_dir().join("cbp2clangd_ws_link");
+    let app_dir = workspace_root.join("app");
+    let ninja_content = generate_subninja_build(&project_info, &app_dir, &toolchain, &workspace_root, &[]).unwrap();
+    println!("Generated subninja content:\n{}", ninja_content);
+
+    assert!(ninja_content.contains(&host_path("-Lapp/lib")));
+    assert!(!ninja_content.contains(" -Llib"));
+    assert!(ninja_content.contains(&host_path("-T app/link.ld")));
+    assert!(ninja_content.contains(&host_path("-Wl,-Map=app/out/app.map,--gc-sections")));
+    assert!(ninja_content.contains("-Wl,-Ttext=0x0"), "段地址选项不是路径");
+    assert!(ninja_content.contains(&host_path("-include app/cfg.h")));
+    assert!(ninja_content.contains(&host_path("-imacros app/macros.h")));
+}
+
+#[test]
+fn test_workspace_clangd_keeps_project_fragments() {
+    // 合并后每个项目仍有自己的 PathMatch 片段 (指向合并后的数据库)，项目专属的 -D 和 -xc 不进入公共部分
+    let project_xml = |name: &str, unit: &str| format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="{name}" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="out/{name}.elf" />
+                <Option object_output="obj/Debug/" />
+            </Target>
+        </Build>
+        <Compiler>
+            <Add option="-Wall" />
+            <Add option="-DPROJ_{name}" />
+        </Compiler>
+        <Unit filename="src/{unit}" />
+    </Project>
+</CodeBlocks_project_file>"#);
+    let a_info = parse_cbp_file(&project_xml("a", "main.c")).unwrap();
+    let b_info = parse_cbp_file(&project_xml("b", "main.cpp")).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let workspace_root = std::env::temp_dir().join("cbp2clangd_ws_clangd");
+    let a_dir = workspace_root.join("a");
+    let b_dir = workspace_root.join("b");
+    let projects = [
+        WorkspaceClangdProject {
+            project_info: &a_info,
+            project_dir: &a_dir,
+            toolchain: &toolchain,
+            target: &a_info.targets[0],
+        },
+        WorkspaceClangdProject {
+            project_info: &b_info,
+            project_dir: &b_dir,
+            toolchain: &toolchain,
+            target: &b_info.targets[0],
+        },
+    ];
+
+    // 逐个转换后 .clangd 的状态：主配置来自最后转换的项目，另有用户自己的设置和片段
+    let existing = "CompileFlags:\n  Add:\n    - -DPROJ_b\n\nDiagnostics:\n  UnusedIncludes: None\n\n---\n\
+                    If:\n  PathMatch: a/src/.*\n\nCompileFlags:\n  CompilationDatabase: a/obj/Debug\n\n---\n\
+                    If:\n  PathMatch: tools/.*\n\nCompileFlags:\n  Add:\n    - -DTOOLS";
+    let clangd = generate_workspace_clangd(existing, &projects, &workspace_root, &workspace_root, false);
+    println!("Generated workspace .clangd:\n{}", clangd);
+
+    let parts: Vec<&str> = clangd.split("\n---\n").collect();
+    assert_eq!(parts.len(), 4, "主配置 + 用户片段 + 两个项目片段");
+    let db = workspace_root.to_string_lossy().replace('\\', "/");
+
+    let base = parts[0];
+    assert!(base.contains(&format!("CompilationDatabase: {}", db)));
+    assert!(base.contains("- -Wall") && base.contains("- -target"), "共有的选项留在主配置");
+    assert!(!base.contains("PROJ_"), "项目专属的宏不进入主配置");
+    assert!(!base.contains("-xc"));
+    assert!(base.contains("Diagnostics:"), "保留用户的其他设置");
+    assert!(parts[1].contains("PathMatch: tools/.*"), "保留用户自己的片段");
+
+    let a_fragment = parts.iter().find(|p| p.contains("PathMatch: a/src/.*")).unwrap();
+    assert!(a_fragment.contains(&format!("CompilationDatabase: {}", db)));
+    assert!(a_fragment.contains("- -DPROJ_a") && !a_fragment.contains("PROJ_b"));
+    assert!(a_fragment.contains("- -xc"), "纯 C 项目的片段保留 -xc");
+    assert!(!a_fragment.contains("a/obj/Debug"));
+
+    let b_fragment = parts.iter().find(|p| p.contains("PathMatch: b/src/.*")).unwrap();
+    assert!(b_fragment.contains("- -DPROJ_b") && !b_fragment.contains("PROJ_a"));
+    assert!(!b_fragment.contains("-xc"), "C++ 项目不强制 -xc");
+}
+
+#[test]
+fn test_generate_ninja_build_for_target_types() {
+    // type 2 归档为静态库，type 3 使用 -shared 链接，type 4 不编译也不链接
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="drv" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Static">
+                <Option output="out/libdrv_s.lib" extension_auto="0" />
+                <Option object_output="obj/Static/" />
+                <Option type="2" />
+            </Target>
+            <Target title="Shared">
+                <Option output="out/libdrv.so" />
+                <Option object_output="obj/Shared/" />
+                <Option type="3" />
+            </Target>
+            <Target title="Gen">
+                <Option output="out/gen" />
+                <Option object_output="obj/Gen/" />
+                <Option type="4" />
+            </Target>
+        </Build>
+        <Unit filename="src/drv.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    println!("Generated ninja content for target types:\n{}", ninja_content);
+
+    // 静态库由 type 决定，而不是输出文件扩展名
+    assert!(ninja_content.contains(&host_path("build out/libdrv_s.lib: ar obj/Static/drv.o")));
+
+    // 动态库
+    assert!(ninja_content.contains(&host_path("build out/libdrv.so: link obj/Shared/drv.o")));
+    assert!(ninja_content.contains("  pre_flags = -shared"));
+
+    // 仅执行命令：没有编译步骤，只有 phony 目标
+    assert!(!ninja_content.contains(&host_path("obj/Gen/drv.o")));
+    assert!(ninja_content.contains("build Gen: phony\n"));
+
+    let gen_target = project_info.select_target(Some("Gen")).unwrap();
+    assert!(generate_compile_commands(&project_info, Path::new("."), &toolchain, Some(gen_target)).is_empty());
+}
+
+#[test]
+fn test_macro_expansion_in_flags_and_commands() {
+    // 编译选项、include 路径、链接选项和特殊文件构建命令统一展开 Code::Blocks 宏
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="demo" />
+        <Build>
+            <Target title="Debug">
+                <Option output="out/$(TARGET_NAME)/demo.elf" />
+                <Option object_output="obj/$(TARGET_NAME)" />
+                <Compiler>
+                    <Add option="-DBUILD_TARGET=$(TARGET_NAME)" />
+                    <Add directory="$(PROJECT_DIR)include" />
+                </Compiler>
+                <Linker>
+                    <Add option="-Wl,-Map=$(TARGET_OUTPUT_DIR)$(TARGET_OUTPUT_BASENAME).map" />
+                </Linker>
+            </Target>
+        </Build>
+        <Compiler>
+            <Add option="-DPROJECT=$(PROJECT_NAME)" />
+        </Compiler>
+        <Unit filename="main.c" />
+        <Unit filename="gen.xm">
+            <Option compile="1" />
+            <Option compiler="riscv32-v2" use="1" buildCommand="gen $file -o $(TARGET_OBJECT_DIR)gen.o --elf $(TARGET_OUTPUT_FILE)" />
+        </Unit>
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let target = &project_info.targets[0];
+    assert_eq!(target.output, "out/Debug/demo.elf");
+    assert_eq!(target.object_output, "obj/Debug");
+
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+    let commands = generate_compile_commands(&project_info, Path::new("."), &toolchain, Some(target));
+    assert_eq!(commands.len(), 1);
+    assert!(commands[0].command.contains("-DPROJECT=demo"));
+    assert!(commands[0].command.contains("-DBUILD_TARGET=Debug"));
+    assert!(!commands[0].command.contains("$("));
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    assert!(ninja_content.contains("-DBUILD_TARGET=Debug"));
+    assert!(ninja_content.contains(&format!("-I{}", host_path("./include"))));
+    assert!(ninja_content.contains(&format!("-Wl,-Map={}", host_path("out/Debug/demo.map"))));
+    assert!(ninja_content.contains(&format!(
+        "gen {} -o {} --elf {}",
+        host_path("gen.xm"),
+        host_path("obj/Debug/gen.o"),
+        host_path("out/Debug/demo.elf")
+    )));
+    assert!(!ninja_content.contains("$("));
+}
+
+#[test]
+fn test_global_variables_resolve_include_dirs() {
+    // default.conf 中激活集合的全局变量用于展开 include 路径和链接选项
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="demo" />
+        <Build>
+            <Target title="Debug">
+                <Option output="demo.elf" />
+            </Target>
+        </Build>
+        <Compiler>
+            <Add option="$(#sdk.cflags)" />
+            <Add directory="$(#sdk.include)" />
+        </Compiler>
+        <Linker>
+            <Add directory="$(#sdk.lib)" />
+        </Linker>
+        <Unit filename="main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let sdk = CbGlobalVariable {
+        members: [("base", "/opt/sdk"), ("cflags", "-DUSE_SDK")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    };
+    let cb_config = CbCompilerConfig {
+        global_variable_sets: [("default".to_string(), [("sdk".to_string(), sdk)].into_iter().collect())]
+            .into_iter()
+            .collect(),
+        active_global_set: "default".to_string(),
+        ..Default::default()
+    };
+
+    let project_info =
+        parse_cbp_file_with_path(xml_content, Path::new("demo.cbp"), Some(&cb_config)).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let commands = generate_compile_commands(&project_info, Path::new("."), &toolchain, None);
+    assert!(commands[0].command.contains("-DUSE_SDK"));
+    assert!(commands[0].command.contains(&format!("-I{}", host_path("/opt/sdk/include"))));
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    assert!(ninja_content.contains(&format!("-L{}", host_path("/opt/sdk/lib"))));
+}
+
+#[test]
+#[cfg(not(windows))]
+fn test_environment_exported_to_build_script_and_ninja() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="demo" />
+        <Environment>
+            <Variable name="SDK_HOME" value="/opt/sdk" />
+        </Environment>
+        <Build>
+            <Target title="Debug">
+                <Option output="demo.elf" />
+                <Environment>
+                    <Variable name="PRICE" value="$$5" />
+                </Environment>
+            </Target>
+        </Build>
+        <ExtraCommands>
+            <Add before="./gen.sh" />
+        </ExtraCommands>
+        <Unit filename="main.c" />
+        <Unit filename="table.xm">
+            <Option compile="1" />
+            <Option compiler="riscv32-v2" use="1" buildCommand="pack $file -o obj/table.o" />
+        </Unit>
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let script = generate_build_script(&project_info, &toolchain, Path::new("."), None);
+    assert!(script.contains("export SDK_HOME='/opt/sdk'\nexport PRICE='$5'\n"));
+
+    // ninja 中的自定义构建命令和预构建步骤先设置环境变量，$ 按 ninja 语法转义
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    assert!(ninja_content.contains("command = export SDK_HOME='/opt/sdk' && export PRICE='$$5' && pack table.xm -o obj/table.o"));
+    assert!(ninja_content.contains("command = export SDK_HOME='/opt/sdk' && export PRICE='$$5' && ( ./gen.sh ) && touch"));
+}
+
+#[test]
+fn test_unit_compiler_var_weight_and_build_command() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="demo" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="demo.elf" />
+                <Option object_output="obj/Debug/" />
+            </Target>
+        </Build>
+        <Unit filename="main.c" />
+        <Unit filename="startup.c">
+            <Option weight="10" />
+        </Unit>
+        <Unit filename="driver.cpp">
+            <Option compilerVar="CPP" />
+        </Unit>
+        <Unit filename="fast.c">
+            <Option compiler="riscv32-v2" use="1" buildCommand="$compiler -O3 -c $file -o $object" />
+        </Unit>
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+
+    // compilerVar="CPP" 的 Unit 使用 cxx 规则
+    assert!(ninja_content.contains("rule cxx\n"));
+    assert!(ninja_content.contains("g++"));
+
+    // weight 较小的 Unit 先输出，其余 Unit 以 order-only 依赖它
+    let startup_pos = ninja_content.find("build obj/Debug/startup.o: cc startup.c\n").unwrap();
+    let main_pos = ninja_content.find("build obj/Debug/main.o: cc main.c || obj/Debug/startup.o\n").unwrap();
+    assert!(startup_pos < main_pos);
+    assert!(ninja_content.contains("build obj/Debug/driver.o: cxx driver.cpp || obj/Debug/startup.o\n"));
+
+    // 自定义构建命令替代 cc 规则，$object 展开为默认的对象文件路径
+    assert!(ninja_content.contains("rule custom_fast_c_Debug\n"));
+    assert!(ninja_content.contains("-O3 -c fast.c -o obj/Debug/fast.o -MMD -MF $out.d"));
+    assert!(ninja_content.contains("build obj/Debug/fast.o: custom_fast_c_Debug fast.c || obj/Debug/startup.o\n"));
+}
+
+#[test]
+fn test_language_specific_drivers_and_clangd_language() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="demo" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="demo.elf" />
+                <Option object_output="obj/Debug/" />
+            </Target>
+        </Build>
+        <Unit filename="main.c" />
+        <Unit filename="motor.cpp" />
+        <Unit filename="start.S" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    // C++ 文件按扩展名使用 cxx 规则，汇编文件使用 as 规则
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    assert!(ninja_content.contains("build obj/Debug/main.o: cc main.c\n"));
+    assert!(ninja_content.contains("build obj/Debug/motor.o: cxx motor.cpp\n"));
+    assert!(ninja_content.contains("build obj/Debug/start.o: as start.S\n"));
+    assert!(ninja_content.contains("rule as\n"));
+
+    // compile_commands.json 中每个文件使用对应的驱动
+    let commands = generate_compile_commands(&project_info, Path::new("."), &toolchain, None);
+    let motor = commands.iter().find(|c| c.file.ends_with("motor.cpp")).unwrap();
+    assert!(motor.command.starts_with("riscv32-elf-g++ "));
+    let main = commands.iter().find(|c| c.file.ends_with("main.c")).unwrap();
+    assert!(main.command.starts_with("riscv32-elf-gcc "));
+
+    // 含 C++ 的项目不强制 -xc，公共 CompileFlags 也不包含 -xc
+    let base = generate_clangd_config(&project_info, &toolchain, false, None).unwrap();
+    assert!(!base.contains("-xc"));
+    let (_, fragment) =
+        generate_clangd_fragment(&project_info, Path::new("/ws/demo"), Path::new("/ws"), Path::new("."), None).unwrap();
+    assert!(!fragment.contains("-xc"));
+
+    // 只含 C 源文件的项目在片段中添加 -xc
+    let c_only = parse_cbp_file(&xml_content.replace("<Unit filename=\"motor.cpp\" />", "")).unwrap();
+    let (_, fragment) =
+        generate_clangd_fragment(&c_only, Path::new("/ws/demo"), Path::new("/ws"), Path::new("."), None).unwrap();
+    assert!(fragment.ends_with("  Add:\n    - -xc"));
+}
+
+#[test]
+fn test_compile_commands_arguments_format_and_merge() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="demo" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="demo.elf" />
+                <Option object_output="obj/Debug/" />
+            </Target>
+        </Build>
+        <Compiler>
+            <Add option='-DBANNER="hello world"' />
+        </Compiler>
+        <Unit filename="src/main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+    let project_dir = std::env::temp_dir().join("cbp2clangd_args");
+
+    // arguments 数组原样保存包含引号和空格的选项
+    let commands = generate_compile_commands_with_format(
+        &project_info,
+        &project_dir,
+        &toolchain,
+        None,
+        CompileCommandsFormat::Arguments,
+    );
+    let json = serde_json::to_value(&commands).unwrap();
+    let entry = &json[0];
+    assert!(entry.get("command").is_none());
+    assert_eq!(entry["arguments"][1], "-c");
+    assert!(entry["arguments"].as_array().unwrap().iter().any(|arg| arg == "-DBANNER=\"hello world\""));
+    let output = entry["output"].as_str().unwrap();
+    assert!(output.ends_with(&host_path("obj/Debug/main.o")), "{}", output);
+
+    // 默认仍写出 command 字符串
+    let command_form = generate_compile_commands(&project_info, &project_dir, &toolchain, None);
+    let json = serde_json::to_value(&command_form).unwrap();
+    assert!(json[0]["command"].as_str().unwrap().starts_with("riscv32-elf-gcc -c"));
+    assert!(json[0].get("arguments").is_none());
+
+    // 合并时两种写法都能读取，并按原写法写回
+    std::fs::create_dir_all(&project_dir).unwrap();
+    let first = project_dir.join("first.json");
+    let second = project_dir.join("second.json");
+    std::fs::write(&first, serde_json::to_string(&command_form).unwrap()).unwrap();
+    std::fs::write(
+        &second,
+        r#"[{"directory": "/lib", "arguments": ["cc", "-c", "lib.c"], "file": "/lib/lib.c"}]"#,
+    )
+    .unwrap();
+    let merged_path = project_dir.join("compile_commands.json");
+    merge_compile_commands_into(&[first, second], &merged_path).unwrap();
+    let merged: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&merged_path).unwrap()).unwrap();
+    assert_eq!(merged.as_array().unwrap().len(), 2);
+    assert!(merged[0]["command"].is_string());
+    assert_eq!(merged[1]["arguments"], serde_json::json!(["cc", "-c", "lib.c"]));
+}
+
+#[test]
+fn test_clangd_target_triple() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="demo" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="demo.elf" />
+                <Compiler>
+                    <Add option="-march=rv64imac_xvendor" />
+                    <Add option="-mabi=lp64" />
+                </Compiler>
+            </Target>
+        </Build>
+        <Unit filename="main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+    assert_eq!(toolchain.target_triple(), "riscv32-unknown-elf");
+
+    // 三元组位宽跟随 -march，自定义扩展被去掉，-mabi 保持不变
+    let config = generate_clangd_config(&project_info, &toolchain, false, None).unwrap();
+    assert!(config.contains("    - -target\n    - riscv64-unknown-elf\n"), "{}", config);
+    assert!(config.contains("    - -march=rv64imac\n    - -mabi=lp64\n"), "{}", config);
+    assert!(config.contains("  Remove:\n    - -march=rv64imac_xvendor\n"), "{}", config);
+
+    // 项目通过 <Extensions> 覆盖三元组
+    let overridden = xml_content.replace(
+        "<Unit filename=\"main.c\" />",
+        "<Unit filename=\"main.c\" />\n        <Extensions>\n            <cbp2clangd target_triple=\"arm-none-eabi\" />\n        </Extensions>",
+    );
+    let project_info = parse_cbp_file(&overridden).unwrap();
+    assert_eq!(project_info.target_triple.as_deref(), Some("arm-none-eabi"));
+    let config = generate_clangd_config(&project_info, &toolchain, false, None).unwrap();
+    assert!(config.contains("    - -target\n    - arm-none-eabi\n"), "{}", config);
+    // 非 RISC-V 目标的 -march 原样传递
+    assert!(config.contains("    - -march=rv64imac_xvendor\n"), "{}", config);
+}
+
+#[test]
+fn test_generic_gcc_toolchain_tools() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="blink" />
+        <Option compiler="avr-gcc" />
+        <Build>
+            <Target title="Release">
+                <Option output="bin/libblink.a" prefix_auto="0" extension_auto="0" />
+                <Option object_output="obj/Release/" />
+                <Option type="2" />
+            </Target>
+        </Build>
+        <Compiler>
+            <Add option="-mmcu=atmega328p" />
+        </Compiler>
+        <Unit filename="src/blink.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::resolve_toolchain(&project_info.compiler_id, None).unwrap();
+
+    // 工具名来自 avr-gcc 的工具链描述，而不是 riscv32-elf-*
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    assert!(ninja_content.contains("avr-gcc"), "{}", ninja_content);
+    assert!(ninja_content.contains("avr-ar"), "{}", ninja_content);
+    assert!(!ninja_content.contains("riscv32-elf-"), "{}", ninja_content);
+
+    let commands = generate_compile_commands(&project_info, Path::new("."), &toolchain, None);
+    assert!(commands[0].command.contains("avr-gcc"), "{}", commands[0].command);
+
+    let config = generate_clangd_config(&project_info, &toolchain, false, None).unwrap();
+    assert!(config.contains("    - -target\n    - avr-unknown-elf\n"), "{}", config);
+}
+
+#[test]
+fn test_cb_compiler_default_options() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="app.elf" prefix_auto="0" extension_auto="0" />
+                <Option object_output="obj/" />
+                <Linker>
+                    <Add directory="libs" />
+                </Linker>
+            </Target>
+        </Build>
+        <Compiler>
+            <Add option="-O0" />
+        </Compiler>
+        <Linker>
+            <Add option="-Wl,--gc-sections" />
+        </Linker>
+        <Unit filename="main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let mut toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+    toolchain.cb_compiler_options = vec!["-Os".to_string()];
+    toolchain.cb_linker_options = vec!["-nostartfiles".to_string()];
+    toolchain.cb_library_dirs = vec!["/opt/rv32/lib".to_string()];
+
+    // 编译器的全局选项位于项目选项之前，项目选项可以覆盖
+    let commands = generate_compile_commands(&project_info, Path::new("."), &toolchain, None);
+    let command = &commands[0].command;
+    assert!(command.find("-Os").unwrap() < command.find("-O0").unwrap(), "{}", command);
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    assert!(ninja_content.contains("-Os -O0"), "{}", ninja_content);
+    assert!(ninja_content.contains("-nostartfiles -Wl,--gc-sections"), "{}", ninja_content);
+    let pre_flags = ninja_content.lines().find(|l| l.trim_start().starts_with("pre_flags")).unwrap();
+    assert!(pre_flags.find("-Llibs").unwrap() < pre_flags.find("-L/opt/rv32/lib").unwrap(), "{}", pre_flags);
+
+    let config = generate_clangd_config(&project_info, &toolchain, false, None).unwrap();
+    assert!(config.contains("    - -Os\n    - -O0\n"), "{}", config);
+}
+
+#[test]
+fn test_options_relation_in_generated_commands() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="firmware" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="app">
+                <Option output="app.elf" prefix_auto="0" extension_auto="0" />
+                <Option object_output="obj/app/" />
+                <Compiler>
+                    <Add option="-DAPP" />
+                </Compiler>
+            </Target>
+            <Target title="bootloader">
+                <Option output="boot.elf" prefix_auto="0" extension_auto="0" />
+                <Option object_output="obj/boot/" />
+                <Option projectCompilerOptionsRelation="1" />
+                <Option projectLinkerOptionsRelation="1" />
+                <Option projectIncludeDirsRelation="1" />
+                <Option projectLibDirsRelation="2" />
+                <Compiler>
+                    <Add option="-DBOOT" />
+                    <Add directory="boot" />
+                </Compiler>
+                <Linker>
+                    <Add option="-Tboot.ld" />
+                    <Add directory="bootlib" />
+                </Linker>
+            </Target>
+        </Build>
+        <Compiler>
+            <Add option="-DUSE_RTOS" />
+            <Add directory="rtos" />
+        </Compiler>
+        <Linker>
+            <Add option="-Tapp.ld" />
+            <Add library="rtos" />
+            <Add directory="lib" />
+        </Linker>
+        <Unit filename="main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+    let app = project_info.select_target(Some("app")).unwrap();
+    let boot = project_info.select_target(Some("bootloader")).unwrap();
+
+    let app_command = &generate_compile_commands(&project_info, Path::new("."), &toolchain, Some(app))[0].command;
+    assert!(app_command.contains("-DUSE_RTOS -DAPP"), "{}", app_command);
+    assert!(app_command.contains("rtos"), "{}", app_command);
+
+    // bootloader 只使用自己的选项
+    let boot_command = &generate_compile_commands(&project_info, Path::new("."), &toolchain, Some(boot))[0].command;
+    assert!(boot_command.contains("-DBOOT"), "{}", boot_command);
+    assert!(!boot_command.contains("-DUSE_RTOS"), "{}", boot_command);
+    assert!(!boot_command.contains("rtos"), "{}", boot_command);
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    let boot_section = &ninja_content[ninja_content.find("boot.elf").unwrap()..];
+    let boot_flags = boot_section.lines().find(|l| l.trim_start().starts_with("pre_flags")).unwrap();
+    assert!(boot_flags.contains("-Tboot.ld") && !boot_flags.contains("-Tapp.ld"), "{}", boot_flags);
+    assert!(boot_flags.find("-Lbootlib").unwrap() < boot_flags.find("-Llib").unwrap(), "{}", boot_flags);
+    assert!(!boot_section.lines().any(|l| l.contains("-lrtos")), "{}", boot_section);
+    assert!(ninja_content.contains("-DUSE_RTOS -DAPP"), "{}", ninja_content);
+
+    let config = generate_clangd_config(&project_info, &toolchain, false, Some(boot)).unwrap();
+    assert!(config.contains("-DBOOT") && !config.contains("-DUSE_RTOS"), "{}", config);
+}
+
+#[test]
+fn test_virtual_target_phony() {
+    // 虚拟 target 生成以别名命名的 phony 目标，依赖各成员 target 的产物
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="out/Debug/app.elf" />
+                <Option object_output="obj/Debug/" />
+            </Target>
+            <Target title="Test">
+                <Option output="out/Test/app_test.elf" />
+                <Option object_output="obj/Test/" />
+            </Target>
+            <Target title="Docs">
+                <Option type="4" />
+            </Target>
+        </Build>
+        <VirtualTargets>
+            <Add alias="All" targets="Debug;Test;Docs;" />
+        </VirtualTargets>
+        <Unit filename="src/main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    println!("Generated ninja content with virtual target:\n{}", ninja_content);
+    assert!(ninja_content.contains(&host_path("build All: phony out/Debug/app.elf out/Test/app_test.elf Docs\n")));
+    // 默认目标仍为第一个普通 target
+    assert!(ninja_content.contains(&host_path("default out/Debug/app.elf")));
+
+    // 工作区模式下别名同样加上项目目录前缀
+    let workspace_root = std::env::temp_dir().join("cbp2clangd_ws_alias");
+    let project_dir = workspace_root.join("app");
+    let subninja = generate_subninja_build(&project_info, &project_dir, &toolchain, &workspace_root, &[]).unwrap();
+    assert!(subninja.contains(&host_path(
+        "build app/All: phony app/out/Debug/app.elf app/out/Test/app_test.elf app/Docs\n"
+    )));
+}
+
+#[test]
+fn test_target_extra_commands_in_ninja() {
+    // target 级 ExtraCommands 生成构建边：预构建步骤每次执行并在编译之前完成，后构建步骤在链接之后执行
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="out/app.elf" />
+                <Option object_output="obj/Debug/" />
+                <ExtraCommands>
+                    <Add before="./gen_version.sh $(TARGET_NAME)" />
+                    <Add after="./sign.sh $(TARGET_OUTPUT_FILE)" />
+                </ExtraCommands>
+            </Target>
+            <Target title="Flash">
+                <Option object_output="obj/Flash/" />
+                <Option type="4" />
+                <ExtraCommands>
+                    <Add after="./flash.sh" />
+                    <Mode after="always" />
+                </ExtraCommands>
+            </Target>
+        </Build>
+        <Unit filename="src/main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    println!("Generated ninja content with extra commands:\n{}", ninja_content);
+
+    // 预构建：依赖不存在的 phony 目标，每次构建都执行；编译和链接边以 order-only 依赖其 stamp
+    let prebuild_stamp = host_path("obj/Debug/Debug.prebuild.stamp");
+    assert!(ninja_content.contains("build cbp2clangd_always: phony\n"));
+    assert!(ninja_content.contains(&format!("build {}: prebuild_Debug | cbp2clangd_always\n", prebuild_stamp)));
+    assert!(ninja_content.contains(&format!(
+        "build {}: cc src/main.c || {}\n",
+        host_path("obj/Debug/main.o"),
+        prebuild_stamp
+    )));
+    assert!(ninja_content.contains(&format!(
+        "build {}: link {} || {}\n",
+        host_path("out/app.elf"),
+        host_path("obj/Debug/main.o"),
+        prebuild_stamp
+    )));
+
+    // 后构建：产物重新生成后执行，target 的 phony 目标和默认目标包含其 stamp
+    let postbuild_stamp = host_path("obj/Debug/Debug.postbuild.stamp");
+    assert!(ninja_content.contains(&format!("build {}: postbuild_Debug {}\n", postbuild_stamp, host_path("out/app.elf"))));
+    assert!(ninja_content.contains(&format!("build Debug: phony {} {}\n", host_path("out/app.elf"), postbuild_stamp)));
+    assert!(ninja_content.contains(&format!("default {} {}\n", host_path("out/app.elf"), postbuild_stamp)));
+
+    // 仅执行命令的 target：after="always" 的后构建步骤每次都执行
+    let flash_stamp = host_path("obj/Flash/Flash.postbuild.stamp");
+    assert!(ninja_content.contains(&format!("build {}: postbuild_Flash | cbp2clangd_always\n", flash_stamp)));
+    assert!(ninja_content.contains(&format!("build Flash: phony {}\n", flash_stamp)));
+    assert_eq!(ninja_content.matches("build cbp2clangd_always: phony").count(), 1);
+
+    // 命令中的宏按所属 target 展开
+    if !cfg!(windows) {
+        assert!(ninja_content.contains(
+            "  command = ( ./gen_version.sh Debug ) && touch 'obj/Debug/Debug.prebuild.stamp'\n"
+        ));
+        assert!(ninja_content.contains("( ./sign.sh out/app.elf )"));
+    }
+}
+
+#[test]
+fn test_extra_commands_use_build_toolchain() {
+    // ExtraCommands 中的 $compiler 使用生成 build.ninja 时实际使用的工具链，而不是按编译器 ID 回退到 RV32
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="my_arm" />
+        <Build>
+            <Target title="Debug">
+                <Option output="out/app.elf" />
+                <Option object_output="obj/Debug/" />
+                <ExtraCommands>
+                    <Add before="$compiler --version" />
+                </ExtraCommands>
+            </Target>
+        </Build>
+        <ExtraCommands>
+            <Add before="$compiler -E -P cfg.h.in -o cfg.h" />
+        </ExtraCommands>
+        <Unit filename="src/main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let toolchain = ToolchainConfig::from_compiler_id("arm-elf-gcc").unwrap();
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    println!("Generated ninja content with $compiler commands:\n{}", ninja_content);
+
+    let compiler = format!("\"{}\"", toolchain.compiler_path());
+    assert!(ninja_content.contains(&format!("{} -E -P cfg.h.in -o cfg.h", compiler)));
+    assert!(ninja_content.contains(&format!("{} --version", compiler)));
+    assert!(!ninja_content.contains("riscv32-elf-gcc"));
+    assert!(!ninja_content.contains("$$compiler"));
+}
+
+#[test]
+fn test_project_extra_commands_in_ninja() {
+    // 项目级 ExtraCommands 由 build.ninja 执行：预构建步骤先于所有编译边，后构建步骤在每个 target 链接之后
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="out/Debug/app.elf" />
+                <Option object_output="obj/Debug/" />
+                <ExtraCommands>
+                    <Add before="./check_env.sh" />
+                </ExtraCommands>
+            </Target>
+            <Target title="Release">
+                <Option output="out/Release/app.elf" />
+                <Option object_output="obj/Release/" />
+            </Target>
+        </Build>
+        <ExtraCommands>
+            <Add before="./gen_version.sh gen/version.h" />
+            <Add after="./pack.sh $(PROJECT_NAME)" />
+        </ExtraCommands>
+        <Extensions>
+            <cbp2clangd>
+                <prebuild_output file="gen/version.h" />
+            </cbp2clangd>
+        </Extensions>
+        <Unit filename="src/main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    assert_eq!(project_info.prebuild_outputs, vec!["gen/version.h"]);
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    println!("Generated ninja content with project extra commands:\n{}", ninja_content);
+
+    // 项目级预构建步骤只生成一次，声明的生成文件作为其输出 (restat 避免文件未变时重新编译)
+    let project_stamp = host_path("obj/Debug/app.project_prebuild.stamp");
+    assert_eq!(ninja_content.matches("rule project_prebuild\n").count(), 1);
+    assert!(ninja_content.contains("  restat = 1\n"));
+    assert!(ninja_content.contains(&format!(
+        "build {} {}: project_prebuild | cbp2clangd_always\n",
+        project_stamp,
+        host_path("gen/version.h")
+    )));
+
+    // target 级预构建步骤在项目级之后，编译边以 order-only 依赖全部预构建 stamp
+    let debug_stamp = host_path("obj/Debug/Debug.prebuild.stamp");
+    assert!(ninja_content.contains(&format!(
+        "build {}: prebuild_Debug | cbp2clangd_always || {}\n",
+        debug_stamp, project_stamp
+    )));
+    assert!(ninja_content.contains(&format!(
+        "build {}: cc src/main.c || {} {}\n",
+        host_path("obj/Debug/main.o"),
+        project_stamp,
+        debug_stamp
+    )));
+    assert!(ninja_content.contains(&format!(
+        "build {}: cc src/main.c || {}\n",
+        host_path("obj/Release/main.o"),
+        project_stamp
+    )));
+
+    // 项目级后构建步骤在每个 target 的产物之后执行，并由 target 的 phony 目标和默认目标引用
+    let release_post = host_path("obj/Release/Release.project_postbuild.stamp");
+    assert!(ninja_content.contains(&format!(
+        "build {}: project_postbuild_Release {}\n",
+        release_post,
+        host_path("out/Release/app.elf")
+    )));
+    assert!(ninja_content.contains(&format!("build Release: phony {} {}\n", host_path("out/Release/app.elf"), release_post)));
+    assert!(ninja_content.contains(&format!(
+        "default {} {}\n",
+        host_path("out/Debug/app.elf"),
+        host_path("obj/Debug/Debug.project_postbuild.stamp")
+    )));
+
+    // 构建脚本只运行 ninja，不再重复执行预/后构建命令
+    let script = generate_build_script(&project_info, &toolchain, Path::new("."), None);
+    assert!(!script.contains("gen_version.sh"));
+    assert!(!script.contains("pack.sh"));
+}
+
+#[test]
+fn test_project_postbuild_expands_per_target() {
+    // 项目级后构建命令按正在构建的 target 展开宏 (与 Code::Blocks 一致)，而不是固定使用第一个 target
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="out/Debug/app.elf" />
+                <Option object_output="obj/Debug/" />
+            </Target>
+            <Target title="Release">
+                <Option output="out/Release/app.elf" />
+                <Option object_output="obj/Release/" />
+            </Target>
+        </Build>
+        <ExtraCommands>
+            <Add after="cp $(TARGET_OUTPUT_FILE) $(TARGET_NAME).bin" />
+        </ExtraCommands>
+        <Unit filename="src/main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    assert_eq!(project_info.postbuild_commands, vec!["cp $(TARGET_OUTPUT_FILE) $(TARGET_NAME).bin"]);
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+
+    let ninja_content = generate_ninja_build(&project_info, Path::new("."), &toolchain).unwrap();
+    println!("Generated ninja content with per-target project postbuild:\n{}", ninja_content);
+
+    let rule_command = |rule: &str| {
+        let start = ninja_content.find(&format!("rule {}\n", rule)).unwrap();
+        ninja_content[start..].lines().nth(1).unwrap().to_string()
+    };
+    let release = rule_command("project_postbuild_Release");
+    assert!(release.contains("Release.bin"), "{}", release);
+    assert!(release.contains(&host_path("out/Release/app.elf")), "{}", release);
+    assert!(!release.contains("Debug"), "{}", release);
+
+    let debug = rule_command("project_postbuild_Debug");
+    assert!(debug.contains("Debug.bin"), "{}", debug);
+    assert!(debug.contains(&host_path("out/Debug/app.elf")), "{}", debug);
+    assert!(!debug.contains("Release"), "{}", debug);
 }
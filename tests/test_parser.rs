@@ -1,4 +1,7 @@
-use cbp2clangd::parse_cbp_file;
+use cbp2clangd::{
+    CompilerVar, DEFAULT_UNIT_WEIGHT, ExtraCommands, OptionsRelation, TargetSelectError, TargetType, ToolchainConfig,
+    VirtualTarget, parse_cbp_file, parse_cbp_file_with_path,
+};
 
 #[test]
 fn test_parse_cbp_file() {
@@ -42,8 +45,8 @@ fn test_parse_cbp_file() {
 
     assert_eq!(project_info.source_files.len(), 1);
     assert_eq!(project_info.source_files[0].filename, "src/chatbot.c");
-    assert_eq!(project_info.source_files[0].compile, true);
-    assert_eq!(project_info.source_files[0].link, true);
+    assert!(project_info.source_files[0].compile);
+    assert!(project_info.source_files[0].link);
 }
 
 #[test]
@@ -94,29 +97,29 @@ fn test_parse_unit_compile_link_attributes() {
 
     // 验证普通源文件的compile和link属性
     let main_file = project_info.source_files.iter().find(|f| f.filename == "src/main.c").expect("应该包含src/main.c");
-    assert_eq!(main_file.compile, true, "src/main.c的compile属性应该为true");
-    assert_eq!(main_file.link, true, "src/main.c的link属性应该为true");
+    assert!(main_file.compile, "src/main.c的compile属性应该为true");
+    assert!(main_file.link, "src/main.c的link属性应该为true");
 
     let only_compile_file = project_info.source_files.iter().find(|f| f.filename == "src/only_compile.c").expect("应该包含src/only_compile.c");
-    assert_eq!(only_compile_file.compile, true, "src/only_compile.c的compile属性应该为true");
-    assert_eq!(only_compile_file.link, false, "src/only_compile.c的link属性应该为false");
+    assert!(only_compile_file.compile, "src/only_compile.c的compile属性应该为true");
+    assert!(!only_compile_file.link, "src/only_compile.c的link属性应该为false");
 
     let only_link_file = project_info.source_files.iter().find(|f| f.filename == "src/only_link.c").expect("应该包含src/only_link.c");
-    assert_eq!(only_link_file.compile, false, "src/only_link.c的compile属性应该为false");
-    assert_eq!(only_link_file.link, true, "src/only_link.c的link属性应该为true");
+    assert!(!only_link_file.compile, "src/only_link.c的compile属性应该为false");
+    assert!(only_link_file.link, "src/only_link.c的link属性应该为true");
 
     // 验证特殊文件的compile和link属性
     let special_file = project_info.special_files.iter().find(|f| f.filename == "src/special.asm").expect("应该包含src/special.asm");
-    assert_eq!(special_file.compile, true, "src/special.asm的compile属性应该为true");
-    assert_eq!(special_file.link, true, "src/special.asm的link属性应该为true");
+    assert!(special_file.compile, "src/special.asm的compile属性应该为true");
+    assert!(special_file.link, "src/special.asm的link属性应该为true");
 
     let special_no_link_file = project_info.special_files.iter().find(|f| f.filename == "src/special_no_link.asm").expect("应该包含src/special_no_link.asm");
-    assert_eq!(special_no_link_file.compile, true, "src/special_no_link.asm的compile属性应该为true");
-    assert_eq!(special_no_link_file.link, false, "src/special_no_link.asm的link属性应该为false");
+    assert!(special_no_link_file.compile, "src/special_no_link.asm的compile属性应该为true");
+    assert!(!special_no_link_file.link, "src/special_no_link.asm的link属性应该为false");
 
     let special_no_compile_file = project_info.special_files.iter().find(|f| f.filename == "src/special_no_compile.asm").expect("应该包含src/special_no_compile.asm");
-    assert_eq!(special_no_compile_file.compile, false, "src/special_no_compile.asm的compile属性应该为false");
-    assert_eq!(special_no_compile_file.link, true, "src/special_no_compile.asm的link属性应该为true");
+    assert!(!special_no_compile_file.compile, "src/special_no_compile.asm的compile属性应该为false");
+    assert!(special_no_compile_file.link, "src/special_no_compile.asm的link属性应该为true");
 }
 
 #[test]
@@ -150,8 +153,8 @@ fn test_parse_special_files_compile_default() {
 
     // 验证特殊文件的compile默认值
     let special_file = &project_info.special_files[0];
-    assert_eq!(special_file.compile, false, "特殊文件的compile默认值应该为false");
-    assert_eq!(special_file.link, false, "特殊文件的link默认值应该为false");
+    assert!(!special_file.compile, "特殊文件的compile默认值应该为false");
+    assert!(!special_file.link, "特殊文件的link默认值应该为false");
 }
 
 #[test]
@@ -289,16 +292,25 @@ fn test_parse_extra_commands() {
 
     // 验证预构建命令数量
     assert_eq!(project_info.prebuild_commands.len(), 2, "应该有2个预构建命令");
+    // 解析时不展开宏，$compiler 由生成 build.ninja 时实际使用的工具链决定
+    assert!(project_info.prebuild_commands[0].starts_with("$compiler $options $includes"));
+
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+    let commands = project_info
+        .command_macros(&project_info.targets[0], &toolchain)
+        .expand_all(&project_info.prebuild_commands);
 
     // 验证第一个预构建命令是否包含预期内容
-    let first_command = &project_info.prebuild_commands[0];
+    let first_command = &commands[0];
     assert!(first_command.contains("riscv32-elf-gcc"), "第一个命令应该包含编译器路径");
     assert!(first_command.contains("-Wall -g"), "第一个命令应该包含编译选项");
-    assert!(first_command.contains(".\\output\\bin\\copy_tone.xm"), "第一个命令应该包含替换后的项目目录路径");
-    assert!(first_command.contains(".\\output\\bin\\copy_tone.bat"), "第一个命令应该包含替换后的项目目录路径");
+    // $(PROJECT_DIR) 展开为宿主平台风格的当前目录 (Windows: .\\，POSIX: ./)
+    let project_dir = format!(".{}", std::path::MAIN_SEPARATOR);
+    assert!(first_command.contains(&format!("{}output\\bin\\copy_tone.xm", project_dir)), "第一个命令应该包含替换后的项目目录路径");
+    assert!(first_command.contains(&format!("{}output\\bin\\copy_tone.bat", project_dir)), "第一个命令应该包含替换后的项目目录路径");
 
     // 验证第二个预构建命令是否包含预期内容
-    let second_command = &project_info.prebuild_commands[1];
+    let second_command = &commands[1];
     assert!(second_command.contains("Output\\bin\\prebuild.bat"), "第二个命令应该包含原始路径");
     assert!(second_command.contains("TestProject"), "第二个命令应该包含项目名称");
 }
@@ -338,11 +350,11 @@ fn test_parse_unit_compile_0() {
     
     // 验证src/main.c的compile属性为false
     let main_file = project_info.source_files.iter().find(|f| f.filename == "src/main.c").expect("应该包含src/main.c文件");
-    assert_eq!(main_file.compile, false, "src/main.c的compile属性应该为false");
+    assert!(!main_file.compile, "src/main.c的compile属性应该为false");
     
     // 验证src/helper.c的compile属性为true
     let helper_file = project_info.source_files.iter().find(|f| f.filename == "src/helper.c").expect("应该包含src/helper.c文件");
-    assert_eq!(helper_file.compile, true, "src/helper.c的compile属性应该为true");
+    assert!(helper_file.compile, "src/helper.c的compile属性应该为true");
 }
 
 #[test]
@@ -970,3 +982,522 @@ fn test_parse_missing_output() {
     assert_eq!(project_info.targets[0].output, "TestProject.elf", "应该使用默认output格式：<project_name>.elf");
     assert_eq!(project_info.targets[0].object_output, "custom_obj_dir", "应该使用自定义object_output");
 }
+
+#[test]
+fn test_parse_unit_target_membership() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Build>
+            <Target title="Board" />
+            <Target title="Sim" />
+            <Target title="Test" />
+        </Build>
+        <Unit filename="src/main.c" />
+        <Unit filename="src/board.c">
+            <Option target="Board" />
+            <Option target="Test" />
+        </Unit>
+        <Unit filename="src/startup.asm">
+            <Option compile="1" />
+            <Option target="Board" />
+        </Unit>
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+
+    // 未列出 target 的 Unit 属于所有 target
+    let main_file = project_info.source_files.iter().find(|f| f.filename == "src/main.c").unwrap();
+    assert!(main_file.targets.is_empty());
+    assert!(main_file.belongs_to("Sim"));
+
+    // 可以列出多个 target
+    let board_file = project_info.source_files.iter().find(|f| f.filename == "src/board.c").unwrap();
+    assert_eq!(board_file.targets, vec!["Board", "Test"]);
+    assert!(board_file.belongs_to("Test"));
+    assert!(!board_file.belongs_to("Sim"));
+
+    // 特殊文件同样记录所属 target
+    let startup = &project_info.special_files[0];
+    assert!(startup.belongs_to("Board"));
+    assert!(!startup.belongs_to("Sim"));
+}
+
+#[test]
+fn test_parse_target_type() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Build>
+            <Target title="App">
+                <Option output="bin/app.elf" />
+                <Option type="1" />
+            </Target>
+            <Target title="Lib">
+                <Option output="bin/app.lib" />
+                <Option type="2" />
+            </Target>
+            <Target title="LegacyLib">
+                <Option output="bin/legacy.a" />
+            </Target>
+            <Target title="Cmds">
+                <Option type="4" />
+            </Target>
+        </Build>
+        <Unit filename="src/main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let types: Vec<TargetType> = project_info.targets.iter().map(|t| t.target_type).collect();
+    assert_eq!(
+        types,
+        vec![
+            TargetType::ConsoleApp,
+            TargetType::StaticLib,
+            // 未指定 type 时按 .a 扩展名推断为静态库
+            TargetType::StaticLib,
+            TargetType::CommandsOnly,
+        ]
+    );
+}
+
+#[test]
+fn test_artifact_output_prefix_and_extension_auto() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="drv" />
+        <Build>
+            <Target title="Static">
+                <Option output="out/drv" prefix_auto="1" extension_auto="1" />
+                <Option type="2" />
+            </Target>
+            <Target title="StaticNoPrefix">
+                <Option output="out/drv.lib" prefix_auto="0" extension_auto="1" />
+                <Option type="2" />
+            </Target>
+            <Target title="Shared">
+                <Option output="out/drv.dll" />
+                <Option type="3" />
+            </Target>
+            <Target title="App">
+                <Option output="out/app" prefix_auto="1" extension_auto="1" />
+                <Option type="1" />
+            </Target>
+            <Target title="AppHex">
+                <Option output="out/app.hex" />
+                <Option type="1" />
+            </Target>
+            <Target title="WinStatic">
+                <Option output="bin\Debug\drv" prefix_auto="1" extension_auto="1" />
+                <Option type="2" />
+            </Target>
+            <Target title="Manual">
+                <Option output="out/drv.lib" prefix_auto="0" extension_auto="0" />
+                <Option type="2" />
+            </Target>
+        </Build>
+        <Unit filename="src/drv.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let artifact = |name: &str| project_info.select_target(Some(name)).unwrap().artifact_output();
+
+    assert_eq!(artifact("Static"), "out/libdrv.a");
+    assert_eq!(artifact("StaticNoPrefix"), "out/drv.a");
+    // 未指定时 prefix_auto/extension_auto 默认开启
+    assert_eq!(artifact("Shared"), "out/libdrv.so");
+    // 可执行程序只在没有扩展名时补充 .elf
+    assert_eq!(artifact("App"), "out/app.elf");
+    assert_eq!(artifact("AppHex"), "out/app.hex");
+    assert_eq!(artifact("Manual"), "out/drv.lib");
+    // Windows 上保存的反斜杠路径在任何宿主上都只替换文件名部分
+    assert_eq!(artifact("WinStatic"), "bin\\Debug\\libdrv.a");
+}
+
+#[test]
+fn test_parse_extra_commands_macro_expansion() {
+    // 项目级 ExtraCommands 在第一个 target 的上下文中展开，$(PROJECT_FILENAME) 来自 .cbp 路径
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="demo" />
+        <Build>
+            <Target title="Release">
+                <Option output="bin/Release/demo" />
+            </Target>
+        </Build>
+        <ExtraCommands>
+            <Add before="echo $(PROJECT_FILENAME) ${PROJECT_NAME}" />
+            <Add after="objcopy -O binary $(TARGET_OUTPUT_FILE) $(TARGET_OUTPUT_DIR)$(TARGET_OUTPUT_BASENAME).bin" />
+            <Add after="echo $$HOME $(TODAY)" />
+        </ExtraCommands>
+        <Unit filename="main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+    let expand = |project_info: &cbp2clangd::ProjectInfo, commands: &[String]| {
+        project_info.command_macros(&project_info.targets[0], &toolchain).expand_all(commands)
+    };
+
+    let project_info = parse_cbp_file_with_path(xml_content, std::path::Path::new("fw/demo.cbp"), None).unwrap();
+    assert_eq!(expand(&project_info, &project_info.prebuild_commands), vec!["echo demo.cbp demo"]);
+    let postbuild_commands = expand(&project_info, &project_info.postbuild_commands);
+    // $(TARGET_OUTPUT_DIR) 以宿主平台分隔符结尾
+    assert_eq!(
+        postbuild_commands[0],
+        format!("objcopy -O binary bin/Release/demo.elf bin/Release{}demo.bin", std::path::MAIN_SEPARATOR)
+    );
+    // $$ 转义为 $，日期宏按 YYYY-MM-DD 展开
+    let date_command = &postbuild_commands[1];
+    assert!(date_command.starts_with("echo $HOME "));
+    assert_eq!(date_command.len(), "echo $HOME ".len() + "YYYY-MM-DD".len());
+
+    // 未提供 .cbp 路径时 $(PROJECT_FILENAME) 无法确定
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    assert_eq!(expand(&project_info, &project_info.prebuild_commands), vec!["echo  demo"]);
+}
+
+#[test]
+fn test_parse_environment_variables() {
+    // 项目级和 target 级 <Environment> 变量注册为宏，target 级变量覆盖同名的项目级变量
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="demo" />
+        <Environment>
+            <Variable name="TOOLS" value="$(PROJECT_DIR)tools" />
+            <Variable name="MODE" value="debug" />
+        </Environment>
+        <Build>
+            <Target title="Release">
+                <Option output="bin/$(MODE)/demo.elf" />
+                <Environment>
+                    <Variable name="MODE" value="release" />
+                    <Variable name="PACK" value="$(TOOLS)/pack" />
+                </Environment>
+                <Compiler>
+                    <Add option="-DMODE=$(MODE)" />
+                </Compiler>
+            </Target>
+        </Build>
+        <ExtraCommands>
+            <Add after="$(PACK) $(MODE)" />
+        </ExtraCommands>
+        <Unit filename="main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    let tools = format!(".{}tools", std::path::MAIN_SEPARATOR);
+    assert_eq!(
+        project_info.environment,
+        vec![("TOOLS".to_string(), tools.clone()), ("MODE".to_string(), "debug".to_string())]
+    );
+
+    let target = &project_info.targets[0];
+    assert_eq!(target.output, "bin/release/demo.elf");
+    assert_eq!(
+        target.environment,
+        vec![("MODE".to_string(), "release".to_string()), ("PACK".to_string(), format!("{}/pack", tools))]
+    );
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+    assert_eq!(
+        project_info.command_macros(target, &toolchain).expand_all(&project_info.postbuild_commands),
+        vec![format!("{}/pack release", tools)]
+    );
+    assert_eq!(project_info.target_macros(target).expand(&target.cflags[0]), "-DMODE=release");
+
+    // 合并后的环境：项目级变量在前，同名变量取 target 级的值
+    let names: Vec<String> = project_info
+        .target_environment(target)
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    assert_eq!(names, vec![format!("TOOLS={}", tools), "MODE=release".to_string(), format!("PACK={}/pack", tools)]);
+}
+
+#[test]
+fn test_parse_unit_compiler_var_weight_and_build_command() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="demo" />
+        <Option compiler="riscv32-v2" />
+        <Unit filename="main.c" />
+        <Unit filename="driver.cpp">
+            <Option compilerVar="CPP" />
+            <Option weight="10" />
+        </Unit>
+        <Unit filename="fast.c">
+            <Option compilerVar="CC" />
+            <Option weight="abc" />
+            <Option compiler="gcc" use="1" buildCommand="gcc -c $file" />
+            <Option compiler="riscv32-v2" use="1" buildCommand="$compiler -O3 -c $file -o $object" />
+        </Unit>
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project_info = parse_cbp_file(xml_content).unwrap();
+    assert_eq!(project_info.source_files.len(), 3);
+
+    let main = &project_info.source_files[0];
+    assert_eq!(main.compiler_var, None);
+    assert_eq!(main.weight, DEFAULT_UNIT_WEIGHT);
+    assert_eq!(main.build_command, None);
+
+    let driver = &project_info.source_files[1];
+    assert_eq!(driver.compiler_var, Some(CompilerVar::Cpp));
+    assert_eq!(driver.weight, 10);
+
+    // 无效的 weight 使用默认值；只采用与项目编译器匹配的 buildCommand
+    let fast = &project_info.source_files[2];
+    assert_eq!(fast.compiler_var, Some(CompilerVar::Cc));
+    assert_eq!(fast.weight, DEFAULT_UNIT_WEIGHT);
+    assert_eq!(fast.build_command.as_deref(), Some("$compiler -O3 -c $file -o $object"));
+}
+
+#[test]
+fn test_parse_options_relations() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="firmware" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="app">
+                <Option output="app.elf" />
+                <Compiler>
+                    <Add option="-DAPP" />
+                    <Add directory="app/include" />
+                </Compiler>
+            </Target>
+            <Target title="bootloader">
+                <Option output="boot.elf" />
+                <Option projectCompilerOptionsRelation="1" />
+                <Option projectLinkerOptionsRelation="1" />
+                <Option projectIncludeDirsRelation="2" />
+                <Option projectLibDirsRelation="0" />
+                <Option projectResourceIncludeDirsRelation="9" />
+                <Compiler>
+                    <Add option="-march=rv32imc" />
+                    <Add option="-DBOOT" />
+                    <Add directory="boot/include" />
+                </Compiler>
+                <Linker>
+                    <Add option="-Tboot.ld" />
+                    <Add library="bootrom" />
+                    <Add directory="boot/lib" />
+                </Linker>
+            </Target>
+            <Target title="sim">
+                <Option output="sim.elf" />
+                <Option projectCompilerOptionsRelation="0" />
+                <Compiler>
+                    <Add option="-march=rv32i" />
+                </Compiler>
+            </Target>
+        </Build>
+        <Compiler>
+            <Add option="-march=rv32imac" />
+            <Add option="-Wall" />
+            <Add directory="include" />
+        </Compiler>
+        <Linker>
+            <Add option="-Tapp.ld" />
+            <Add library="m" />
+            <Add directory="lib" />
+        </Linker>
+        <Unit filename="main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project = parse_cbp_file(xml_content).unwrap();
+    let app = project.select_target(Some("app")).unwrap();
+    let boot = project.select_target(Some("bootloader")).unwrap();
+    let sim = project.select_target(Some("sim")).unwrap();
+
+    // 未指定时 target 选项追加在项目选项之后
+    assert_eq!(app.relations.compiler_options, OptionsRelation::AppendTarget);
+    let app_flags = project.target_flags(app);
+    assert_eq!(app_flags.cflags, vec!["-march=rv32imac", "-Wall", "-DAPP"]);
+    assert_eq!(app_flags.include_dirs, vec!["-Iinclude", "-Iapp/include"]);
+
+    assert_eq!(boot.relations.compiler_options, OptionsRelation::TargetOnly);
+    assert_eq!(boot.relations.include_dirs, OptionsRelation::PrependTarget);
+    assert_eq!(boot.relations.lib_dirs, OptionsRelation::ProjectOnly);
+    // 无法识别的值保持默认
+    assert_eq!(boot.relations.resource_include_dirs, OptionsRelation::AppendTarget);
+    let boot_flags = project.target_flags(boot);
+    assert_eq!(boot_flags.cflags, vec!["-march=rv32imc", "-DBOOT"]);
+    assert_eq!(boot_flags.include_dirs, vec!["-Iboot/include", "-Iinclude"]);
+    assert_eq!(boot_flags.linker_options, vec!["-Tboot.ld"]);
+    assert_eq!(boot_flags.linker_libs.len(), 1);
+    assert_eq!(boot_flags.lib_dirs, vec!["-Llib"]);
+
+    // -march 跟随实际生效的编译选项
+    assert_eq!(app.march_info.full_march, "-march=rv32imac");
+    assert_eq!(boot.march_info.full_march, "-march=rv32imc");
+    assert_eq!(sim.march_info.full_march, "-march=rv32imac");
+    assert_eq!(project.target_flags(sim).cflags, vec!["-march=rv32imac", "-Wall"]);
+}
+
+#[test]
+fn test_parse_keeps_lossless_model() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Compiler>
+            <Add option="-Wall" />
+            <Add directory="include" />
+        </Compiler>
+        <ResourceCompiler>
+            <Add directory="res" />
+        </ResourceCompiler>
+        <Linker>
+            <Add library="a" />
+            <Add option="-Wl,--whole-archive" />
+            <Add library="b" />
+        </Linker>
+        <Unit filename="main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project = parse_cbp_file(xml_content).unwrap();
+    // 扁平化字段不记录 library 和 option 之间的顺序，原始结构中保留
+    let linker: Vec<_> = project
+        .model
+        .project_section("Linker")
+        .unwrap()
+        .adds()
+        .map(|add| add.attributes[0].1.as_str())
+        .collect();
+    assert_eq!(linker, vec!["a", "-Wl,--whole-archive", "b"]);
+    assert!(project.model.project_section("ResourceCompiler").is_some());
+    assert_eq!(project.model.units().count(), project.source_files.len());
+}
+
+#[test]
+fn test_parse_virtual_targets() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="bin/Debug/app.elf" />
+                <Option object_output="obj/Debug/" />
+            </Target>
+            <Target title="Release">
+                <Option output="bin/Release/app.elf" />
+                <Option object_output="obj/Release/" />
+            </Target>
+            <Target title="Test">
+                <Option output="bin/Test/app.elf" />
+                <Option object_output="obj/Test/" />
+            </Target>
+        </Build>
+        <VirtualTargets>
+            <Add alias="All" targets="Test;Debug;Missing;" />
+            <Add alias="Debug" targets="Release;" />
+            <Add alias="Ghost" targets="Missing;" />
+        </VirtualTargets>
+        <Unit filename="main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project = parse_cbp_file(xml_content).unwrap();
+
+    // 不存在的成员被忽略，与普通 target 同名或没有有效成员的别名被丢弃
+    assert_eq!(
+        project.virtual_targets,
+        vec![VirtualTarget {
+            name: "All".to_string(),
+            targets: vec!["Test".to_string(), "Debug".to_string()],
+        }]
+    );
+
+    // 虚拟 target 解析为其成员 (按别名中的顺序)，select_target 返回第一个成员
+    let names = |name: Option<&str>| -> Vec<String> {
+        project.resolve_targets(name).unwrap().iter().map(|t| t.name.clone()).collect()
+    };
+    assert_eq!(names(Some("All")), vec!["Test", "Debug"]);
+    assert_eq!(names(Some("Release")), vec!["Release"]);
+    assert_eq!(names(None), vec!["Debug"]);
+    assert_eq!(project.select_target(Some("All")).unwrap().name, "Test");
+
+    // 错误信息中列出普通 target 和虚拟 target
+    let TargetSelectError::UnknownTarget { available, .. } = project.resolve_targets(Some("Ghost")).unwrap_err();
+    assert_eq!(available, vec!["Debug", "Release", "Test", "All"]);
+}
+
+#[test]
+fn test_parse_target_extra_commands() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<CodeBlocks_project_file>
+    <FileVersion major="1" minor="6" />
+    <Project>
+        <Option title="app" />
+        <Option compiler="riscv32-v2" />
+        <Build>
+            <Target title="Debug">
+                <Option output="bin/Debug/app.elf" />
+                <ExtraCommands>
+                    <Add before="gen_version.sh $(TARGET_NAME)" />
+                    <Add after="objcopy -O ihex $(TARGET_OUTPUT_FILE) app.hex" />
+                    <Add after="  " />
+                    <Mode after="always" />
+                </ExtraCommands>
+            </Target>
+            <Target title="Release">
+                <Option output="bin/Release/app.elf" />
+            </Target>
+        </Build>
+        <ExtraCommands>
+            <Add before="echo $(PROJECT_NAME)" />
+            <Mode after="always" />
+        </ExtraCommands>
+        <Unit filename="main.c" />
+    </Project>
+</CodeBlocks_project_file>"#;
+
+    let project = parse_cbp_file(xml_content).unwrap();
+
+    // target 级命令保存原始文本，按所属 target 展开宏
+    let debug = project.select_target(Some("Debug")).unwrap();
+    assert_eq!(
+        debug.extra_commands,
+        ExtraCommands {
+            before: vec!["gen_version.sh $(TARGET_NAME)".to_string()],
+            after: vec!["objcopy -O ihex $(TARGET_OUTPUT_FILE) app.hex".to_string()],
+            after_always: true,
+        }
+    );
+    let toolchain = ToolchainConfig::from_compiler_id("riscv32-v2").unwrap();
+    let macros = project.command_macros(debug, &toolchain);
+    assert_eq!(macros.expand_all(&debug.extra_commands.before), vec!["gen_version.sh Debug"]);
+    assert_eq!(macros.expand_all(&debug.extra_commands.after), vec!["objcopy -O ihex bin/Debug/app.elf app.hex"]);
+    assert!(project.select_target(Some("Release")).unwrap().extra_commands.is_empty());
+
+    // 项目级命令仍然写入 prebuild_commands / postbuild_commands
+    assert_eq!(project.prebuild_commands, vec!["echo $(PROJECT_NAME)"]);
+    assert!(project.postbuild_commands.is_empty());
+    assert!(project.postbuild_always);
+}